@@ -0,0 +1,104 @@
+//! Append-only audit log of completed delegations
+//!
+//! Each delegation becomes one JSON line. Prompts are recorded as a SHA-256
+//! hash unless full prompt logging is explicitly enabled, so the log can be
+//! kept for compliance without holding sensitive content.
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+use crate::llm::{CostSource, LlmResponse, TokenUsage};
+
+/// JSON-lines audit sink
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    write_lock: Mutex<()>,
+}
+
+/// One line of the audit log
+#[derive(Debug, Serialize)]
+struct AuditEntry<'a> {
+    timestamp: String,
+    provider: &'a str,
+    model: &'a str,
+    prompt_sha256: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prompt: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session_id: Option<&'a str>,
+    tokens: Option<&'a TokenUsage>,
+    cost_usd: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cost_source: Option<CostSource>,
+    duration_ms: u64,
+}
+
+impl AuditLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    /// File the log is appended to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append an entry for a completed delegation
+    ///
+    /// The prompt is always hashed; `include_prompt` also records it verbatim.
+    /// Write failures are logged rather than returned; a broken audit sink
+    /// shouldn't fail the delegation that already succeeded.
+    pub async fn record(
+        &self,
+        provider: &str,
+        prompt: &str,
+        include_prompt: bool,
+        response: &LlmResponse,
+    ) {
+        let entry = AuditEntry {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            provider,
+            model: &response.primary_model,
+            prompt_sha256: sha256_hex(prompt),
+            prompt: include_prompt.then_some(prompt),
+            session_id: response.metadata.session_id.as_deref(),
+            tokens: response.tokens.as_ref(),
+            cost_usd: response.cost_usd,
+            cost_source: response.metadata.cost_source,
+            duration_ms: response.api_duration_ms,
+        };
+
+        if let Err(e) = self.append(&entry).await {
+            tracing::warn!("Failed to write audit log {}: {}", self.path.display(), e);
+        }
+    }
+
+    async fn append(&self, entry: &AuditEntry<'_>) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+
+        // Serialize writers so concurrent delegations never interleave lines
+        let _guard = self.write_lock.lock().await;
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        file.write_all(&line).await?;
+        file.flush().await
+    }
+}
+
+fn sha256_hex(text: &str) -> String {
+    Sha256::digest(text.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
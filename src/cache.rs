@@ -0,0 +1,207 @@
+//! In-memory LRU cache of responses to stateless requests
+
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::llm::{LlmRequest, LlmResponse};
+
+#[derive(Debug)]
+struct CacheEntry {
+    response: LlmResponse,
+    inserted_at: Instant,
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    entries: HashMap<String, CacheEntry>,
+    /// Monotonic use counter; the entry with the lowest `last_used` is evicted first
+    tick: u64,
+}
+
+/// Bounded, TTL-limited response cache shared across tool calls
+#[derive(Debug)]
+pub struct ResponseCache {
+    capacity: usize,
+    ttl: Duration,
+    state: Mutex<CacheState>,
+}
+
+impl ResponseCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            ttl,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    /// Most responses kept at once
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// How long a response stays fresh
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Cache key for a request, or `None` if its answer can't be reused
+    ///
+    /// The key covers every field that shapes the answer: the provider, model
+    /// and fallback model, both system prompts, the prompt, the stdin context,
+    /// output format, token and thinking budgets, temperature, and the tool
+    /// permissions.
+    ///
+    /// Requests in a session depend on earlier turns, requests with a
+    /// working directory or attachments depend on files that may have changed,
+    /// requests with their own `env` may reach a different endpoint or
+    /// account, raw `extra_args` or a `binary` override can change anything,
+    /// and `return_raw` asks for this run's own stdout, so all of those bypass
+    /// the cache.
+    pub fn key(provider: &str, request: &LlmRequest) -> Option<String> {
+        if request.session_id.is_some()
+            || request.continue_last
+            || request.return_raw
+            || request.working_dir.is_some()
+            || request.env.as_ref().is_some_and(|env| !env.is_empty())
+            || request.attachments.as_ref().is_some_and(|a| !a.is_empty())
+            || request.extra_args.as_ref().is_some_and(|args| !args.is_empty())
+            || request.binary.is_some()
+        {
+            return None;
+        }
+
+        let mut hasher = Sha256::new();
+        for part in [
+            Some(provider),
+            request.model.as_deref(),
+            request.fallback_model.as_deref(),
+            request.system_prompt.as_deref(),
+            request.append_system_prompt.as_deref(),
+            Some(request.prompt.as_str()),
+            request.stdin_context.as_deref(),
+            Some(&format!("{:?}", request.output_format)),
+            request.max_tokens.map(|n| n.to_string()).as_deref(),
+            request.thinking_budget.map(|n| n.to_string()).as_deref(),
+            request.temperature.map(|t| t.to_string()).as_deref(),
+            Some(if request.skip_permissions { "skip" } else { "check" }),
+        ] {
+            hash_part(&mut hasher, part);
+        }
+        for tools in [&request.allowed_tools, &request.disallowed_tools] {
+            hash_part(&mut hasher, tools.as_ref().map(|tools| tools.len().to_string()).as_deref());
+            for tool in tools.iter().flatten() {
+                hash_part(&mut hasher, Some(tool));
+            }
+        }
+
+        Some(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+
+    /// Look up a fresh entry, marking it as recently used
+    pub fn get(&self, key: &str) -> Option<LlmResponse> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.tick += 1;
+        let tick = state.tick;
+
+        match state.entries.get_mut(key) {
+            Some(entry) if entry.inserted_at.elapsed() < self.ttl => {
+                entry.last_used = tick;
+                Some(entry.response.clone())
+            }
+            Some(_) => {
+                state.entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Store a response, evicting the least recently used entry when full
+    pub fn insert(&self, key: String, response: LlmResponse) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.tick += 1;
+        let tick = state.tick;
+
+        if !state.entries.contains_key(&key) && state.entries.len() >= self.capacity {
+            let ttl = self.ttl;
+            state.entries.retain(|_, entry| entry.inserted_at.elapsed() < ttl);
+
+            if state.entries.len() >= self.capacity {
+                let oldest = state
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    state.entries.remove(&oldest);
+                }
+            }
+        }
+
+        state.entries.insert(
+            key,
+            CacheEntry {
+                response,
+                inserted_at: Instant::now(),
+                last_used: tick,
+            },
+        );
+    }
+}
+
+/// Feed one key part to the hasher
+///
+/// Tagged and length-prefixed so that field boundaries can't be forged and an
+/// unset field differs from an empty one.
+fn hash_part(hasher: &mut Sha256, part: Option<&str>) {
+    match part {
+        Some(part) => {
+            hasher.update([1]);
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part.as_bytes());
+        }
+        None => hasher.update([0]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{LlmRequestBuilder, OutputFormat};
+
+    fn key(build: impl FnOnce(LlmRequestBuilder) -> LlmRequestBuilder) -> Option<String> {
+        ResponseCache::key("claude", &build(LlmRequest::builder("hello")).build())
+    }
+
+    #[test]
+    fn key_covers_every_output_affecting_field() {
+        let base = key(|r| r).unwrap();
+        assert_eq!(key(|r| r), Some(base.clone()));
+
+        let variants = [
+            key(|r| r.with_temperature(0.5)),
+            key(|r| r.with_allowed_tools(vec!["Read".to_string()])),
+            key(|r| r.with_allowed_tools(Vec::new())),
+            key(|r| r.with_disallowed_tools(vec!["Bash".to_string()])),
+            key(|r| r.with_skip_permissions(true)),
+            key(|r| r.with_output_format(OutputFormat::Text)),
+            key(|r| r.with_max_tokens(100)),
+            key(|r| r.with_fallback_model("sonnet")),
+            key(|r| r.with_thinking_budget(1024)),
+        ];
+        for (i, variant) in variants.iter().enumerate() {
+            let variant = variant.as_ref().unwrap();
+            assert_ne!(variant, &base, "variant {} hashed like the base request", i);
+            assert!(variants[..i].iter().all(|other| other.as_ref() != Some(variant)), "variant {}", i);
+        }
+    }
+
+    #[test]
+    fn raw_output_requests_are_not_cached() {
+        assert_eq!(key(|r| r.with_return_raw(true)), None);
+    }
+}
@@ -0,0 +1,216 @@
+//! Per-provider circuit breaker
+//!
+//! After enough consecutive failures a provider's circuit opens and calls fail
+//! fast with `ProviderUnavailable` instead of each waiting out a timeout. Once
+//! the cooldown passes, one probe call is let through: success closes the
+//! circuit, failure opens it for another cooldown.
+//!
+//! Short of opening, a provider is reported degraded while its circuit isn't
+//! closed or when many of its recent calls failed.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::LlmError;
+
+/// Consecutive failures that open a circuit by default
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+
+/// How long an open circuit fails fast by default
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// Recent calls the error rate is measured over
+const ERROR_RATE_WINDOW: usize = 20;
+
+/// Recent calls needed before an error rate means anything
+const ERROR_RATE_MIN_CALLS: usize = 4;
+
+/// Share of recent calls failing that marks a provider degraded
+const DEGRADED_ERROR_RATE: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    /// A probe call is running; others keep failing fast until it finishes
+    HalfOpen { probe_started: Instant },
+}
+
+/// Circuit state as reported by `check_providers`
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitStatus {
+    /// `closed`, `open`, or `half_open`
+    pub state: &'static str,
+    /// Consecutive failures so far while closed
+    pub consecutive_failures: u32,
+    /// Seconds until an open circuit lets a probe through
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_in_seconds: Option<u64>,
+}
+
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    provider: String,
+    /// Consecutive failures that open the circuit; 0 disables the breaker
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<State>,
+    /// Whether each of the last `ERROR_RATE_WINDOW` calls failed, newest last
+    recent: Mutex<VecDeque<bool>>,
+}
+
+impl CircuitBreaker {
+    pub fn new(provider: impl Into<String>, threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            provider: provider.into(),
+            threshold,
+            cooldown,
+            state: Mutex::new(State::Closed { failures: 0 }),
+            recent: Mutex::new(VecDeque::with_capacity(ERROR_RATE_WINDOW)),
+        }
+    }
+
+    /// Let a call through, or fail fast while the circuit is open
+    pub fn allow(&self) -> Result<(), LlmError> {
+        if self.threshold == 0 {
+            return Ok(());
+        }
+
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let wait = match *state {
+            State::Closed { .. } => return Ok(()),
+            State::Open { until } if now < until => until - now,
+            // A probe that never reported back (e.g. its caller went away) doesn't block forever
+            State::HalfOpen { probe_started } if probe_started.elapsed() < self.cooldown => {
+                self.cooldown - probe_started.elapsed()
+            }
+            State::Open { .. } | State::HalfOpen { .. } => {
+                tracing::info!("🔌 Probing {} after circuit cooldown", self.provider);
+                *state = State::HalfOpen { probe_started: now };
+                return Ok(());
+            }
+        };
+
+        Err(LlmError::ProviderUnavailable {
+            provider: self.provider.clone(),
+            reason: format!(
+                "circuit open after {} consecutive failures; retrying in {}s",
+                self.threshold,
+                wait.as_secs().max(1)
+            ),
+        })
+    }
+
+    /// Record a call's outcome
+    ///
+    /// Only provider-side failures count; a rejected request says nothing
+    /// about the provider's health, but still ends a probe.
+    pub fn record(&self, result: &Result<impl Sized, LlmError>) {
+        let failed = match result {
+            Ok(_) => Some(false),
+            Err(e) => counts_as_failure(e).then_some(true),
+        };
+        if let Some(failed) = failed {
+            let mut recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+            if recent.len() == ERROR_RATE_WINDOW {
+                recent.pop_front();
+            }
+            recent.push_back(failed);
+        }
+
+        if self.threshold == 0 {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match result {
+            Ok(_) => {
+                if !matches!(*state, State::Closed { .. }) {
+                    tracing::info!("✅ {} recovered; circuit closed", self.provider);
+                }
+                *state = State::Closed { failures: 0 };
+            }
+            Err(e) if counts_as_failure(e) => {
+                let failures = match *state {
+                    State::Closed { failures } => failures + 1,
+                    _ => self.threshold,
+                };
+                if failures >= self.threshold {
+                    tracing::warn!(
+                        "⛔ {} failed {} times in a row; circuit open for {}s ({})",
+                        self.provider,
+                        failures,
+                        self.cooldown.as_secs(),
+                        e
+                    );
+                    *state = State::Open {
+                        until: Instant::now() + self.cooldown,
+                    };
+                } else {
+                    *state = State::Closed { failures };
+                }
+            }
+            Err(_) => {
+                if let State::HalfOpen { .. } = *state {
+                    // Let the next call probe instead
+                    *state = State::Open { until: Instant::now() };
+                }
+            }
+        }
+    }
+
+    /// Why the provider counts as degraded, if it does
+    ///
+    /// Degraded while the circuit is open or probing, or when at least half of
+    /// the recent calls failed.
+    pub fn degraded(&self) -> Option<String> {
+        match self.status().state {
+            "open" => return Some(format!("circuit open after {} consecutive failures", self.threshold)),
+            "half_open" => return Some("recovering: probing after circuit cooldown".to_string()),
+            _ => {}
+        }
+
+        let recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+        let failed = recent.iter().filter(|&&failed| failed).count();
+        (recent.len() >= ERROR_RATE_MIN_CALLS && failed as f64 >= recent.len() as f64 * DEGRADED_ERROR_RATE)
+            .then(|| format!("{} of the last {} calls failed", failed, recent.len()))
+    }
+
+    pub fn status(&self) -> CircuitStatus {
+        let state = *self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match state {
+            State::Closed { failures } => CircuitStatus {
+                state: "closed",
+                consecutive_failures: failures,
+                retry_in_seconds: None,
+            },
+            State::Open { until } => CircuitStatus {
+                state: "open",
+                consecutive_failures: self.threshold,
+                retry_in_seconds: Some(until.saturating_duration_since(Instant::now()).as_secs()),
+            },
+            State::HalfOpen { .. } => CircuitStatus {
+                state: "half_open",
+                consecutive_failures: self.threshold,
+                retry_in_seconds: None,
+            },
+        }
+    }
+}
+
+/// Whether an error points at the provider itself being broken
+fn counts_as_failure(error: &LlmError) -> bool {
+    matches!(
+        error.root(),
+        LlmError::ProviderUnavailable { .. }
+            | LlmError::AuthenticationFailed { .. }
+            | LlmError::CliExecutionFailed { .. }
+            | LlmError::Timeout { .. }
+            | LlmError::RateLimited { .. }
+            | LlmError::ModelOverloaded { .. }
+            | LlmError::Io(_)
+    )
+}
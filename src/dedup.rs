@@ -0,0 +1,79 @@
+//! Single-flight deduplication of identical concurrent requests
+//!
+//! A stateless request that arrives while an identical one (same cache key)
+//! is already running waits for that run's response instead of starting a
+//! CLI of its own. If the running request fails or is cancelled, the waiting
+//! ones run on their own.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+use crate::llm::LlmResponse;
+
+type Slot = watch::Receiver<Option<LlmResponse>>;
+
+/// Requests currently running, by cache key
+#[derive(Debug, Default)]
+pub struct Dedup {
+    flights: Mutex<HashMap<String, Slot>>,
+}
+
+/// Outcome of `Dedup::join`
+pub enum Flight {
+    /// No identical request is running; this one does the work
+    Leader(FlightGuard),
+    /// An identical request is running; `wait` for its response
+    Follower(Slot),
+}
+
+impl Dedup {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lead the run for `key`, or follow the one already running
+    pub fn join(self: &Arc<Self>, key: &str) -> Flight {
+        let mut flights = self.flights.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(slot) = flights.get(key) {
+            return Flight::Follower(slot.clone());
+        }
+
+        let (sender, slot) = watch::channel(None);
+        flights.insert(key.to_string(), slot);
+        Flight::Leader(FlightGuard {
+            dedup: self.clone(),
+            key: key.to_string(),
+            sender,
+        })
+    }
+}
+
+/// The leading run for a key; followers are released when it is dropped
+pub struct FlightGuard {
+    dedup: Arc<Dedup>,
+    key: String,
+    sender: watch::Sender<Option<LlmResponse>>,
+}
+
+impl FlightGuard {
+    /// Hand a successful response to every follower
+    pub fn finish(self, response: &LlmResponse) {
+        self.sender.send_replace(Some(response.clone()));
+    }
+}
+
+impl Drop for FlightGuard {
+    fn drop(&mut self) {
+        self.dedup
+            .flights
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.key);
+    }
+}
+
+/// Wait for the leader's response, or `None` if it failed or was cancelled
+pub async fn wait(mut slot: Slot) -> Option<LlmResponse> {
+    slot.wait_for(Option::is_some).await.ok()?.clone()
+}
@@ -1,5 +1,35 @@
 use thiserror::Error;
 
+use crate::llm::redact::redact;
+
+/// How much of the offending CLI output a `ParseError` keeps
+const PARSE_ERROR_OUTPUT_BYTES: usize = 4096;
+
+/// "Exit code: 1", or "Terminated by signal 9 (SIGKILL, ...)" for a killed process
+fn describe_exit(exit_code: i32, signal: Option<i32>) -> String {
+    let Some(signal) = signal else {
+        return format!("Exit code: {}", exit_code);
+    };
+
+    let name = match signal {
+        1 => " (SIGHUP)",
+        2 => " (SIGINT)",
+        6 => " (SIGABRT, the CLI aborted)",
+        9 => " (SIGKILL, often the OOM killer or a resource limit)",
+        11 => " (SIGSEGV, the CLI crashed)",
+        13 => " (SIGPIPE)",
+        15 => " (SIGTERM)",
+        24 => " (SIGXCPU, CPU time limit exceeded)",
+        _ => "",
+    };
+    format!("Terminated by signal {}{}", signal, name)
+}
+
+/// Whether an IO error means the filesystem has no space left
+fn is_storage_full(error: &std::io::Error) -> bool {
+    error.kind() == std::io::ErrorKind::StorageFull
+}
+
 #[derive(Debug, Error)]
 pub enum LlmError {
     #[error("Provider '{provider}' is unavailable: {reason}")]
@@ -8,22 +38,37 @@ pub enum LlmError {
     #[error("Authentication failed for {provider}: {message}")]
     AuthenticationFailed { provider: String, message: String },
 
-    #[error("CLI execution failed: {command}\nExit code: {exit_code}\nStderr: {stderr}")]
+    #[error("CLI execution failed: {command}\n{}\nStderr: {stderr}", describe_exit(*exit_code, *signal))]
     CliExecutionFailed {
         command: String,
         stderr: String,
+        /// -1 when the process was killed by a signal
         exit_code: i32,
+        /// Signal that terminated the process, e.g. 9 for an OOM kill (Unix only)
+        signal: Option<i32>,
     },
 
-    #[error("Failed to parse {format} response: {source}")]
+    #[error(
+        "Failed to parse {format} response: {source}{}",
+        output.as_deref().map(|o| format!("\nOutput: {}", o)).unwrap_or_default()
+    )]
     ParseError {
         format: String,
         #[source]
         source: Box<dyn std::error::Error + Send + Sync>,
+        /// Leading part of the CLI output that failed to parse
+        output: Option<String>,
     },
 
-    #[error("Request timeout after {seconds}s")]
-    Timeout { seconds: u64 },
+    #[error(
+        "Request timeout after {seconds}s{}",
+        partial_content.as_deref().map(|c| format!("\nPartial output before the timeout:\n{}", c)).unwrap_or_default()
+    )]
+    Timeout {
+        seconds: u64,
+        /// What the CLI had written to stdout when it was killed, if anything
+        partial_content: Option<String>,
+    },
 
     #[error("Model '{model}' not available for provider '{provider}': {reason}")]
     ModelNotAvailable {
@@ -32,12 +77,55 @@ pub enum LlmError {
         reason: String,
     },
 
+    #[error("Session not found: {session_id} (it may have expired; start a new session)")]
+    SessionNotFound { session_id: String },
+
+    #[error("Request {request_id} was cancelled")]
+    Cancelled { request_id: String },
+
     #[error("Invalid request: {message}")]
     InvalidRequest { message: String },
 
     #[error("API error from {provider}: {message}")]
     ApiError { provider: String, message: String },
 
+    #[error("Rate limited by {provider}: {message}")]
+    RateLimited { provider: String, message: String },
+
+    #[error(
+        "Model '{model}' is overloaded{}",
+        if *fallback_available { "; retry with a fallback_model" } else { " and so is its fallback" }
+    )]
+    ModelOverloaded {
+        model: String,
+        /// No fallback model was tried, so retrying with one may succeed
+        fallback_available: bool,
+    },
+
+    #[error("{provider} output exceeded {limit_bytes} bytes; the CLI was stopped")]
+    OutputTooLarge { provider: String, limit_bytes: usize },
+
+    #[error(
+        "Cannot {operation} {}: {source}{}",
+        path.display(),
+        if is_storage_full(source) { " (disk full; free space or set PRAXIO_TEMP_ROOT)" } else { "" }
+    )]
+    TempDirFailed {
+        /// What was being attempted, e.g. "create temp directory in"
+        operation: String,
+        path: std::path::PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// Another error, tagged with the provider and request it came from
+    #[error("{source} (provider: {provider}, request_id: {request_id})")]
+    WithContext {
+        provider: String,
+        request_id: String,
+        source: Box<LlmError>,
+    },
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
@@ -45,11 +133,133 @@ pub enum LlmError {
     Json(#[from] serde_json::Error),
 }
 
+impl LlmError {
+    /// Tag the error with the provider and request it came from
+    ///
+    /// An error that already carries context keeps it.
+    pub fn with_context(self, provider: &str, request_id: &str) -> Self {
+        match self {
+            LlmError::WithContext { .. } => self,
+            other => LlmError::WithContext {
+                provider: provider.to_string(),
+                request_id: request_id.to_string(),
+                source: Box::new(other),
+            },
+        }
+    }
+
+    /// The error itself, without any provider and request context
+    pub fn root(&self) -> &LlmError {
+        match self {
+            LlmError::WithContext { source, .. } => source.root(),
+            other => other,
+        }
+    }
+
+    /// Whether another provider might succeed where this one failed
+    ///
+    /// Used by cross-provider fallback: outages, timeouts, rate limits and overloads are
+    /// worth retrying elsewhere, while bad requests or parse failures are not.
+    pub fn is_fallback_eligible(&self) -> bool {
+        matches!(
+            self.root(),
+            LlmError::ProviderUnavailable { .. }
+                | LlmError::Timeout { .. }
+                | LlmError::RateLimited { .. }
+                | LlmError::ModelOverloaded { .. }
+        )
+    }
+
+    /// Whether the error was caused by a full disk rather than the provider
+    pub fn is_disk_full(&self) -> bool {
+        match self.root() {
+            LlmError::TempDirFailed { source, .. } | LlmError::Io(source) => is_storage_full(source),
+            _ => false,
+        }
+    }
+
+    /// Short, stable name of the error variant, for metrics labels and logs
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LlmError::ProviderUnavailable { .. } => "provider_unavailable",
+            LlmError::AuthenticationFailed { .. } => "authentication_failed",
+            LlmError::CliExecutionFailed { .. } => "cli_execution_failed",
+            LlmError::ParseError { .. } => "parse_error",
+            LlmError::Timeout { .. } => "timeout",
+            LlmError::ModelNotAvailable { .. } => "model_not_available",
+            LlmError::SessionNotFound { .. } => "session_not_found",
+            LlmError::Cancelled { .. } => "cancelled",
+            LlmError::InvalidRequest { .. } => "invalid_request",
+            LlmError::ApiError { .. } => "api_error",
+            LlmError::RateLimited { .. } => "rate_limited",
+            LlmError::ModelOverloaded { .. } => "model_overloaded",
+            LlmError::OutputTooLarge { .. } => "output_too_large",
+            LlmError::TempDirFailed { .. } if self.is_disk_full() => "disk_full",
+            LlmError::TempDirFailed { .. } => "temp_dir_failed",
+            LlmError::WithContext { source, .. } => source.kind(),
+            LlmError::Io(_) => "io",
+            LlmError::Json(_) => "json",
+        }
+    }
+
+    /// Attach the CLI output that failed to parse, so schema drift can be diagnosed
+    ///
+    /// Only the first few KB are kept. Errors other than `ParseError` pass through.
+    pub fn with_raw_output(self, raw: &str) -> Self {
+        match self {
+            LlmError::ParseError { format, source, .. } => {
+                let mut end = raw.len().min(PARSE_ERROR_OUTPUT_BYTES);
+                while !raw.is_char_boundary(end) {
+                    end -= 1;
+                }
+                let mut output = raw[..end].to_string();
+                if end < raw.len() {
+                    output.push_str("...");
+                }
+
+                LlmError::ParseError {
+                    format,
+                    source,
+                    output: Some(output),
+                }
+            }
+            other => other,
+        }
+    }
+
+    /// Move a timeout's partial output into the debug log instead of the error
+    ///
+    /// For providers whose output is JSON, where a cut-off document is noise
+    /// to the caller but can still help diagnose what the CLI was doing.
+    pub fn log_partial_content(self, provider: &str) -> Self {
+        match self {
+            LlmError::Timeout {
+                seconds,
+                partial_content: Some(partial),
+            } => {
+                tracing::debug!(provider, "Output before the {}s timeout: {}", seconds, partial);
+                LlmError::Timeout {
+                    seconds,
+                    partial_content: None,
+                }
+            }
+            other => other,
+        }
+    }
+}
+
 // Convert LlmError to McpError via ServerError
 impl From<LlmError> for turbomcp::McpError {
     fn from(err: LlmError) -> Self {
-        // Use ServerError as intermediary since McpError implements From<ServerError>
-        let server_err = turbomcp::ServerError::Internal(err.to_string());
+        // An unknown session is the caller's to fix, so report it as invalid params
+        if let LlmError::SessionNotFound { .. } = err.root() {
+            return turbomcp::McpError::from(turbomcp::ProtocolError::invalid_params(err.to_string()));
+        }
+
+        // Use ServerError as intermediary since McpError implements From<ServerError>;
+        // the message names the provider and request when the error carries them.
+        // Mask secrets again in case a message was built outside classify_error
+        let server_err = turbomcp::ServerError::Internal(redact(&err.to_string()).into_owned());
         turbomcp::McpError::from(server_err)
     }
 }
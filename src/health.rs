@@ -0,0 +1,73 @@
+//! Liveness and readiness probes for container orchestration
+//!
+//! The report is built from cached availability results, so probing never
+//! spawns a CLI. `serve` exposes it over a minimal HTTP listener, like the
+//! metrics endpoint, for deployments that can't call an MCP tool.
+
+use serde::Serialize;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::server::PraxioServer;
+
+/// Snapshot returned by the `health` tool and the HTTP probes
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    /// `ok`, or `shutting_down` once the server refuses new delegations
+    pub status: &'static str,
+    /// At least one provider is available and the server isn't shutting down
+    pub ready: bool,
+    pub uptime_seconds: u64,
+    /// Providers whose last availability check passed and whose circuit isn't open
+    pub providers_available: usize,
+}
+
+/// Serve `GET /healthz` (liveness) and `GET /readyz` (readiness) on `addr`
+///
+/// `/healthz` answers 200 while the process is up; `/readyz` answers 503
+/// until a provider is available and again once shutdown begins.
+pub async fn serve(server: PraxioServer, addr: SocketAddr) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    tracing::info!("💓 Serving health probes on http://{}/healthz and /readyz", listener.local_addr()?);
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let server = server.clone();
+        tokio::spawn(async move {
+            if let Err(e) = respond(&server, stream).await {
+                tracing::debug!("health connection error: {}", e);
+            }
+        });
+    }
+}
+
+async fn respond(server: &PraxioServer, mut stream: TcpStream) -> std::io::Result<()> {
+    // Only the request line matters; probes send small GET requests
+    let mut buf = [0u8; 1024];
+    let n = stream.read(&mut buf).await?;
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let path = request.split_whitespace().nth(1).unwrap_or("");
+
+    let (status, body) = match path {
+        "/healthz" | "/readyz" if request.starts_with("GET ") => {
+            let report = server.health_report().await;
+            let status = if path == "/readyz" && !report.ready {
+                "503 Service Unavailable"
+            } else {
+                "200 OK"
+            };
+            (status, serde_json::to_string(&report).unwrap_or_default())
+        }
+        _ => ("404 Not Found", r#"{"error":"not found"}"#.to_string()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
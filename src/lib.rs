@@ -1,9 +1,20 @@
 // Allow turbomcp macros to use their own cfg conditions
 #![allow(unexpected_cfgs)]
 
+pub mod audit;
+pub mod cache;
+pub mod circuit;
+pub mod dedup;
 pub mod error;
+pub mod health;
 pub mod llm;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod ratelimit;
+pub mod schema;
 pub mod server;
+pub mod template;
+pub mod usage;
 
 pub use error::LlmError;
-pub use server::PraxioServer;
+pub use server::{PraxioServer, PraxioServerBuilder};
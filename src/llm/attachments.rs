@@ -0,0 +1,87 @@
+//! File attachments for delegated prompts
+//!
+//! None of the CLIs take arbitrary files as flags, so attachments are copied
+//! into the temp directory the CLI runs in and referenced from the prompt in
+//! whatever form that CLI understands. When the request names a `working_dir`,
+//! the caller's directory is left untouched and the original paths are used.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use super::types::LlmRequest;
+use crate::error::LlmError;
+
+/// Largest single attachment accepted (10 MiB)
+pub const MAX_ATTACHMENT_BYTES: u64 = 10 * 1024 * 1024;
+
+/// File extensions treated as images by CLIs with a dedicated image flag
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp"];
+
+/// Check that every attachment is a readable file within the size limit
+///
+/// Attachments are copied under their file name, so two paths with the same
+/// name are rejected rather than one silently replacing the other.
+pub fn validate(paths: &[PathBuf]) -> Result<(), LlmError> {
+    let mut names = HashSet::new();
+
+    for path in paths {
+        let invalid = |reason: &str| LlmError::InvalidRequest {
+            message: format!("attachment {}: {}", path.display(), reason),
+        };
+
+        let metadata = std::fs::metadata(path).map_err(|e| invalid(&e.to_string()))?;
+        if !metadata.is_file() {
+            return Err(invalid("not a regular file"));
+        }
+        if metadata.len() > MAX_ATTACHMENT_BYTES {
+            return Err(invalid(&format!(
+                "{} bytes exceeds the {} byte limit",
+                metadata.len(),
+                MAX_ATTACHMENT_BYTES
+            )));
+        }
+        std::fs::File::open(path).map_err(|e| invalid(&e.to_string()))?;
+
+        let name = file_name(path).ok_or_else(|| invalid("path has no file name"))?;
+        if !names.insert(name) {
+            return Err(invalid("another attachment has the same file name"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Copy attachments into `dir` under their file names
+pub fn copy_into(paths: &[PathBuf], dir: &Path) -> Result<(), LlmError> {
+    for path in paths {
+        if let Some(name) = file_name(path) {
+            std::fs::copy(path, dir.join(name)).map_err(LlmError::Io)?;
+        }
+    }
+    Ok(())
+}
+
+/// How the prompt should refer to the request's attachments
+///
+/// Copied attachments are referred to by file name, relative to the directory
+/// the CLI runs in. With a caller-supplied `working_dir` nothing is copied, so
+/// the original paths are used instead.
+pub fn references(request: &LlmRequest) -> Vec<String> {
+    let paths = request.attachments.as_deref().unwrap_or(&[]);
+    if request.working_dir.is_some() {
+        paths.iter().map(|path| path.display().to_string()).collect()
+    } else {
+        paths.iter().filter_map(|path| file_name(path)).collect()
+    }
+}
+
+/// Whether the attachment looks like an image, judging by its extension
+pub fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+fn file_name(path: &Path) -> Option<String> {
+    path.file_name().map(|name| name.to_string_lossy().into_owned())
+}
@@ -0,0 +1,141 @@
+//! Data-driven mapping from CLI stderr to error kinds
+//!
+//! CLIs change their error wording between releases. Each bundled provider is
+//! built with an ordered list of `StderrRule`s, regexes over the (redacted)
+//! stderr that each name the `LlmError` to report; the first match wins.
+//! Checks that need more than the text (Claude's overload detection, Gemini's
+//! session check, exit code 127) stay in the providers. The defaults can be
+//! extended with a provider's `with_stderr_rule`, replaced with
+//! `with_stderr_rules`, or patched from a JSON file passed to `load_rules`.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::error::LlmError;
+
+/// The error a matching rule reports, named as in `LlmError::kind()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    AuthenticationFailed,
+    RateLimited,
+    ProviderUnavailable,
+    ModelNotAvailable,
+    ApiError,
+}
+
+/// One stderr pattern and the error it maps to
+#[derive(Debug, Clone)]
+pub struct StderrRule {
+    pattern: Regex,
+    kind: ErrorKind,
+    message: Option<String>,
+}
+
+impl StderrRule {
+    /// Match `pattern` (a regex; use `(?i)` for case-insensitive) as `kind`
+    pub fn new(pattern: &str, kind: ErrorKind) -> Result<Self, LlmError> {
+        let pattern = Regex::new(pattern).map_err(|e| LlmError::InvalidRequest {
+            message: format!("invalid stderr rule pattern '{}': {}", pattern, e),
+        })?;
+        Ok(Self {
+            pattern,
+            kind,
+            message: None,
+        })
+    }
+
+    /// Report `message` instead of the raw stderr; `{stderr}` is replaced with it
+    pub fn with_message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn pattern(&self) -> &str {
+        self.pattern.as_str()
+    }
+
+    fn to_error(&self, provider: &str, stderr: &str) -> LlmError {
+        let message = match self.message {
+            Some(ref message) => message.replace("{stderr}", stderr),
+            None => stderr.to_string(),
+        };
+        let provider = provider.to_string();
+
+        match self.kind {
+            ErrorKind::AuthenticationFailed => LlmError::AuthenticationFailed { provider, message },
+            ErrorKind::RateLimited => LlmError::RateLimited { provider, message },
+            ErrorKind::ProviderUnavailable => LlmError::ProviderUnavailable { provider, reason: message },
+            ErrorKind::ModelNotAvailable => LlmError::ModelNotAvailable {
+                model: "unknown".to_string(),
+                provider,
+                reason: message.trim().to_string(),
+            },
+            ErrorKind::ApiError => LlmError::ApiError { provider, message },
+        }
+    }
+}
+
+/// Compile built-in rules given as (pattern, kind, message)
+pub fn defaults(rules: &[(&str, ErrorKind, Option<&str>)]) -> Vec<StderrRule> {
+    rules
+        .iter()
+        .map(|&(pattern, kind, message)| {
+            let rule = StderrRule::new(pattern, kind).expect("built-in stderr rule is valid");
+            match message {
+                Some(message) => rule.with_message(message),
+                None => rule,
+            }
+        })
+        .collect()
+}
+
+/// The error of the first rule matching `stderr`, if any
+pub fn classify(rules: &[StderrRule], provider: &str, stderr: &str) -> Option<LlmError> {
+    rules
+        .iter()
+        .find(|rule| rule.pattern.is_match(stderr))
+        .map(|rule| rule.to_error(provider, stderr))
+}
+
+#[derive(Deserialize)]
+struct RuleSpec {
+    pattern: String,
+    kind: ErrorKind,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+/// Read per-provider rules from a JSON file
+///
+/// The file maps provider names to rule lists:
+/// `{ "claude": [{ "pattern": "(?i)credit balance", "kind": "rate_limited" }] }`.
+/// An optional `message` replaces the stderr in the error. Nothing is
+/// returned if any pattern fails to compile.
+pub fn load_rules(path: &Path) -> Result<HashMap<String, Vec<StderrRule>>, LlmError> {
+    let specs: HashMap<String, Vec<RuleSpec>> = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+
+    specs
+        .into_iter()
+        .map(|(provider, specs)| {
+            let rules = specs
+                .into_iter()
+                .map(|spec| {
+                    let rule = StderrRule::new(&spec.pattern, spec.kind)?;
+                    Ok(match spec.message {
+                        Some(message) => rule.with_message(message),
+                        None => rule,
+                    })
+                })
+                .collect::<Result<Vec<_>, LlmError>>()?;
+            Ok((provider, rules))
+        })
+        .collect()
+}
@@ -1,25 +1,87 @@
 mod types;
 
 use async_trait::async_trait;
+use futures::stream::{self, BoxStream, StreamExt};
+use regex::Regex;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 use tokio::process::Command;
-use tokio::time::{timeout, Duration};
-
-use super::provider::{LlmProvider, ProviderAvailability};
+use tokio::time::{timeout, Instant};
+
+use super::attachments;
+use super::classify::{self, ErrorKind, StderrRule};
+use super::parse;
+use super::pool::{ProcessPool, WarmProcess};
+use super::process::{
+    apply_env, apply_overrides, decode_output, log_command, max_output_bytes, run_child, run_cli, spawn_cli, CliExit, CliLines,
+    RunDir,
+};
+use super::provider::{parse_version, LlmProvider, ProviderAvailability, ProviderCapabilities};
+use super::redact::redact;
+use super::which::find_executable;
 use super::types::{
-    LlmRequest, LlmResponse, LlmResponseMetadata, ModelBreakdown, OutputFormat, TokenUsage,
+    CostSource, LlmRequest, LlmResponse, LlmResponseMetadata, ModelBreakdown, OutputFormat, StreamEvent,
+    TokenUsage,
 };
 use crate::error::LlmError;
-use types::ClaudeJsonResponse;
+use types::{ClaudeJsonResponse, ClaudeModelUsage};
+
+/// `ModelOverloaded` if `message` is the API's overload error
+///
+/// The model is taken from the message where the CLI names it, otherwise from
+/// the request. `fallback_model` is what the request already fell back to.
+fn overload_error(message: &str, model: Option<&str>, fallback_model: Option<&str>) -> Option<LlmError> {
+    static MODEL: OnceLock<Regex> = OnceLock::new();
+
+    if !message.to_lowercase().contains("overloaded") {
+        return None;
+    }
+
+    let model_pattern = MODEL.get_or_init(|| Regex::new(r"\bclaude-[a-z0-9][a-z0-9.\-]*").expect("model pattern is valid"));
+    let model = model_pattern
+        .find(message)
+        .map(|m| m.as_str())
+        .or(model)
+        .unwrap_or("default");
+
+    Some(LlmError::ModelOverloaded {
+        model: model.to_string(),
+        fallback_available: fallback_model.is_none(),
+    })
+}
+
+/// Built-in stderr rules, checked after overload detection
+const DEFAULT_STDERR_RULES: &[(&str, ErrorKind, Option<&str>)] = &[
+    ("Authentication failed|setup-token", ErrorKind::AuthenticationFailed, None),
+    ("rate limit|429", ErrorKind::RateLimited, None),
+    ("not found", ErrorKind::ProviderUnavailable, Some("CLI not found in PATH")),
+];
+
+/// The prompt as sent to the CLI, pointing Claude's file tools at any attachments
+fn prompt_text(request: &LlmRequest) -> String {
+    let attachments = attachments::references(request);
+    if attachments.is_empty() {
+        request.prompt.clone()
+    } else {
+        format!("{}\n\nAttached files: {}", request.prompt, attachments.join(", "))
+    }
+}
 
 /// Claude CLI provider
 pub struct ClaudeProvider {
     timeout_seconds: u64,
+    log_stderr: bool,
+    stderr_rules: Vec<StderrRule>,
+    pool: Option<Arc<ProcessPool>>,
 }
 
 impl ClaudeProvider {
     pub fn new() -> Self {
         Self {
             timeout_seconds: 30,
+            log_stderr: true,
+            stderr_rules: classify::defaults(DEFAULT_STDERR_RULES),
+            pool: None,
         }
     }
 
@@ -28,55 +90,128 @@ impl ClaudeProvider {
         self
     }
 
-    /// Build command for Claude CLI invocation
-    fn build_command(&self, request: &LlmRequest) -> Command {
-        let mut cmd = Command::new("claude");
-        cmd.arg("--print");
-        cmd.arg(&request.prompt);
+    /// Toggle live debug logging of the CLI's stderr while it runs
+    pub fn with_stderr_logging(mut self, enabled: bool) -> Self {
+        self.log_stderr = enabled;
+        self
+    }
+
+    /// Check `rule` before the others, so it can override a built-in one
+    pub fn with_stderr_rule(mut self, rule: StderrRule) -> Self {
+        self.stderr_rules.insert(0, rule);
+        self
+    }
+
+    /// Replace the rules mapping stderr to error kinds, dropping the built-in ones
+    pub fn with_stderr_rules(mut self, rules: Vec<StderrRule>) -> Self {
+        self.stderr_rules = rules;
+        self
+    }
+
+    /// Keep up to `size` started CLIs per configuration waiting for requests
+    ///
+    /// Only requests with a `working_dir` and no session to resume are served
+    /// from the pool; the rest spawn a CLI as usual. A process unused for
+    /// `idle_timeout` is killed.
+    pub fn with_process_pool(mut self, size: usize, idle_timeout: Duration) -> Self {
+        self.pool = Some(ProcessPool::new(size, idle_timeout));
+        self
+    }
+
+    /// Program and arguments for a Claude CLI invocation
+    ///
+    /// `build_command` spawns exactly this, so it's also what a dry run reports.
+    pub fn command_args(&self, request: &LlmRequest) -> Vec<String> {
+        let mut args = vec!["claude".to_string(), "--print".to_string(), prompt_text(request)];
 
         // Session management: use --resume for context continuity
         // Note: Use session_id from previous response's metadata.session_id
         if let Some(ref session_id) = request.session_id {
-            cmd.arg("--resume").arg(session_id);
+            args.extend(["--resume".to_string(), session_id.clone()]);
+        } else if request.continue_last {
+            args.push("--continue".to_string());
         }
 
         if let Some(ref sys_prompt) = request.system_prompt {
-            cmd.arg("--system-prompt").arg(sys_prompt);
+            args.extend(["--system-prompt".to_string(), sys_prompt.clone()]);
+        }
+
+        if let Some(ref append) = request.append_system_prompt {
+            args.extend(["--append-system-prompt".to_string(), append.clone()]);
         }
 
         if let Some(ref model) = request.model {
-            cmd.arg("--model").arg(model);
+            args.extend(["--model".to_string(), model.clone()]);
         }
 
         // Fallback model for reliability (only works with --print mode)
         if let Some(ref fallback) = request.fallback_model {
-            cmd.arg("--fallback-model").arg(fallback);
+            args.extend(["--fallback-model".to_string(), fallback.clone()]);
         }
 
         // Always use JSON for metadata
         match request.output_format {
-            OutputFormat::Json => {
-                cmd.arg("--output-format").arg("json");
+            OutputFormat::Json | OutputFormat::Text => {
+                args.extend(["--output-format".to_string(), "json".to_string()]);
             }
-            OutputFormat::Text => {
-                cmd.arg("--output-format").arg("json");
+            OutputFormat::StreamJson => {
+                // stream-json requires --verbose in --print mode
+                args.extend(["--output-format".to_string(), "stream-json".to_string()]);
+                args.push("--verbose".to_string());
             }
         }
 
-        // Skip permissions for MCP usage (delegation context)
-        // This is safe because the delegated Claude runs in an isolated subprocess
-        cmd.arg("--dangerously-skip-permissions");
+        let allowed_tools = request.allowed_tools.as_deref().unwrap_or(&[]);
+        if !allowed_tools.is_empty() {
+            args.extend(["--allowedTools".to_string(), allowed_tools.join(",")]);
+        }
+
+        let disallowed_tools = request.disallowed_tools.as_deref().unwrap_or(&[]);
+        if !disallowed_tools.is_empty() {
+            args.extend(["--disallowedTools".to_string(), disallowed_tools.join(",")]);
+        }
+
+        // Skip permissions only when asked to (see `LlmRequest::skip_permissions`).
+        // An allow-list means the caller wants permissions enforced, so keep them on.
+        if request.skip_permissions && allowed_tools.is_empty() {
+            args.push("--dangerously-skip-permissions".to_string());
+        }
+
+        apply_overrides(&mut args, request);
+        args
+    }
+
+    /// Build command for Claude CLI invocation
+    fn build_command(&self, request: &LlmRequest) -> Command {
+        let args = self.command_args(request);
+        log_command(&args, request, "claude");
+        self.command_from(&args, request)
+    }
+
+    fn command_from(&self, args: &[String], request: &LlmRequest) -> Command {
+        let mut cmd = Command::new(&args[0]);
+        cmd.args(&args[1..]);
+        apply_env(&mut cmd, request);
+
+        // The CLI has no flag for this; it reads the budget from the environment
+        if let Some(budget) = request.thinking_budget {
+            cmd.env("MAX_THINKING_TOKENS", budget.to_string());
+        }
 
         // Note: current_dir will be set in invoke() to a unique temp directory
         cmd
     }
 
     /// Parse JSON response from Claude
-    fn parse_json_response(&self, json_str: &str) -> Result<LlmResponse, LlmError> {
-        let claude_resp: ClaudeJsonResponse = serde_json::from_str(json_str).map_err(|e| {
+    ///
+    /// Works on the raw bytes so invalid UTF-8 is reported where it occurs
+    /// rather than replaced before parsing.
+    fn parse_json_response(&self, json: &[u8]) -> Result<LlmResponse, LlmError> {
+        let mut claude_resp: ClaudeJsonResponse = parse::from_slice_lenient(json, "claude").map_err(|e| {
             LlmError::ParseError {
                 format: "json".to_string(),
                 source: Box::new(e),
+                output: None,
             }
         })?;
 
@@ -88,6 +223,36 @@ impl ClaudeProvider {
             });
         }
 
+        // A max_tokens stop or an exhausted --max-turns leaves the answer incomplete
+        let truncated = claude_resp.stop_reason.as_deref() == Some("max_tokens")
+            || claude_resp.subtype == "error_max_turns";
+        let finish_reason = claude_resp
+            .stop_reason
+            .take()
+            .unwrap_or_else(|| claude_resp.subtype.clone());
+        let permission_denials: Vec<String> = claude_resp
+            .permission_denials
+            .iter()
+            .map(|denial| denial.describe())
+            .collect();
+
+        // Some responses omit modelUsage; synthesize an entry from the top-level
+        // usage so token/cost accounting stays consistent
+        let usage = &claude_resp.usage;
+        if claude_resp.model_usage.is_empty() && usage.input_tokens.saturating_add(usage.output_tokens) > 0 {
+            claude_resp.model_usage.insert(
+                "unknown".to_string(),
+                ClaudeModelUsage {
+                    input_tokens: usage.input_tokens,
+                    output_tokens: usage.output_tokens,
+                    cache_read_input_tokens: usage.cache_read_input_tokens,
+                    cache_creation_input_tokens: usage.cache_creation_input_tokens,
+                    cost_usd: claude_resp.total_cost_usd,
+                    context_window: 0, // Unknown without modelUsage
+                },
+            );
+        }
+
         // Extract primary model (one with highest output tokens)
         let primary_model = claude_resp
             .model_usage
@@ -118,10 +283,13 @@ impl ClaudeProvider {
         let total_tokens = TokenUsage {
             input: claude_resp.usage.input_tokens,
             output: claude_resp.usage.output_tokens,
-            total: claude_resp.usage.input_tokens + claude_resp.usage.output_tokens,
+            total: claude_resp
+                .usage
+                .input_tokens
+                .saturating_add(claude_resp.usage.output_tokens),
             cache_creation: claude_resp.usage.cache_creation_input_tokens,
             cache_read: claude_resp.usage.cache_read_input_tokens,
-            extended_thinking: None,
+            extended_thinking: claude_resp.usage.thinking_tokens,
         };
 
         Ok(LlmResponse {
@@ -130,7 +298,8 @@ impl ClaudeProvider {
             all_models_used,
             provider: "claude".to_string(),
             tokens: Some(total_tokens),
-            duration_ms: claude_resp.duration_ms,
+            api_duration_ms: claude_resp.duration_api_ms,
+            total_duration_ms: None,
             cost_usd: Some(claude_resp.total_cost_usd),
             model_breakdown: Some(model_breakdown),
             metadata: LlmResponseMetadata {
@@ -140,18 +309,72 @@ impl ClaudeProvider {
                 service_tier: Some(claude_resp.usage.service_tier),
                 api_errors: None,
                 tool_calls: None,
+                cost_source: Some(CostSource::Reported),
+                command: None,
+                budget_remaining_usd: None,
+                cached: None,
+                deduplicated: None,
+                cost_warning: None,
+                finish_reason: Some(finish_reason),
+                truncated,
+                request_id: None,
+                previous_session_id: None,
+                permission_denials: (!permission_denials.is_empty()).then_some(permission_denials),
+                lines_added: None,
+                lines_removed: None,
+                provider_version: None,
             },
+            raw: None,
         })
     }
 
-    /// Classify error from stderr
-    fn classify_error(&self, stderr: &str, exit_code: i32) -> LlmError {
-        if stderr.contains("Authentication failed") || stderr.contains("setup-token") {
-            LlmError::AuthenticationFailed {
-                provider: "claude".to_string(),
-                message: stderr.to_string(),
+    /// Parse one line of `--output-format stream-json` output
+    ///
+    /// The final `result` event has the same shape as the `--output-format json`
+    /// envelope, so it is parsed with `parse_json_response`.
+    fn parse_stream_line(&self, line: &str) -> Result<Option<StreamEvent>, LlmError> {
+        let event: serde_json::Value = serde_json::from_str(line).map_err(|e| {
+            LlmError::ParseError {
+                format: "stream-json".to_string(),
+                source: Box::new(e),
+                output: None,
+            }
+        })?;
+
+        match event["type"].as_str() {
+            Some("system") if event["subtype"] == "init" => Ok(Some(StreamEvent::Started {
+                session_id: event["session_id"].as_str().map(str::to_string),
+            })),
+            Some("assistant") => {
+                let text: String = event["message"]["content"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter(|block| block["type"] == "text")
+                    .filter_map(|block| block["text"].as_str())
+                    .collect();
+
+                Ok((!text.is_empty()).then_some(StreamEvent::Delta { text }))
             }
-        } else if stderr.contains("not found") || exit_code == 127 {
+            Some("result") => Ok(Some(StreamEvent::Completed {
+                response: Box::new(self.parse_json_response(line.as_bytes())?),
+            })),
+            _ => Ok(None),
+        }
+    }
+
+    /// Classify error from stderr
+    fn classify_error(&self, stderr: &str, exit: CliExit, request: &LlmRequest) -> LlmError {
+        // The message is returned to the client, so mask secrets first
+        let stderr = &*redact(stderr);
+
+        if let Some(overloaded) =
+            overload_error(stderr, request.model.as_deref(), request.fallback_model.as_deref())
+        {
+            overloaded
+        } else if let Some(error) = classify::classify(&self.stderr_rules, "claude", stderr) {
+            error
+        } else if exit.code == 127 {
             LlmError::ProviderUnavailable {
                 provider: "claude".to_string(),
                 reason: "CLI not found in PATH".to_string(),
@@ -160,10 +383,135 @@ impl ClaudeProvider {
             LlmError::CliExecutionFailed {
                 command: "claude".to_string(),
                 stderr: stderr.to_string(),
-                exit_code,
+                exit_code: exit.code,
+                signal: exit.signal,
             }
         }
     }
+
+    /// Arguments for a pooled CLI, which reads the prompt from stdin, and its pool key
+    ///
+    /// `None` for requests a process started ahead of them can't serve: without a
+    /// `working_dir` each run gets a fresh directory, and a resumed session or
+    /// per-request environment would make the configuration one-off.
+    fn pooled_command(&self, request: &LlmRequest) -> Option<(Vec<String>, String)> {
+        let working_dir = request.working_dir.as_ref()?;
+        if request.session_id.is_some()
+            || request.continue_last
+            || request.stdin_context.is_some()
+            || request.env.is_some()
+        {
+            return None;
+        }
+
+        let mut template = request.clone();
+        template.prompt = String::new();
+        template.attachments = None;
+        template.output_format = OutputFormat::StreamJson;
+        let mut args = self.command_args(&template);
+        args.splice(2..3, ["--input-format".to_string(), "stream-json".to_string()]);
+
+        let key = serde_json::json!({
+            "args": args,
+            "working_dir": working_dir,
+            "env_allowlist": request.env_allowlist,
+            "thinking_budget": request.thinking_budget,
+        })
+        .to_string();
+        Some((args, key))
+    }
+
+    /// Start a CLI that waits on stdin for its prompt
+    fn spawn_warm(&self, args: &[String], request: &LlmRequest) -> Result<WarmProcess, LlmError> {
+        log_command(args, request, "claude");
+        let mut cmd = self.command_from(args, request);
+        if let Some(ref dir) = request.working_dir {
+            cmd.current_dir(dir);
+        }
+        spawn_cli(cmd, "claude", true).map(WarmProcess::new)
+    }
+
+    /// Serve a request from the pool, starting a replacement for the process it takes
+    async fn invoke_pooled(
+        &self,
+        pool: &Arc<ProcessPool>,
+        args: Vec<String>,
+        key: String,
+        request: LlmRequest,
+    ) -> Result<LlmResponse, LlmError> {
+        let warm = match pool.take(&key) {
+            Some(warm) => {
+                tracing::debug!("♻️  Using a warm claude process");
+                warm
+            }
+            None => self.spawn_warm(&args, &request)?,
+        };
+
+        // The next request with this configuration finds one already started
+        if pool.has_room(&key) {
+            match self.spawn_warm(&args, &request) {
+                Ok(spare) => pool.put(key, spare),
+                Err(e) => tracing::warn!("⚠️  Could not start a warm claude process: {}", e),
+            }
+        }
+
+        // One user message, then end of input so the CLI exits after answering
+        let message = serde_json::json!({
+            "type": "user",
+            "message": { "role": "user", "content": prompt_text(&request) },
+        });
+        let timeout_secs = request.timeout_seconds.unwrap_or(self.timeout_seconds);
+        let output = run_child(
+            warm.child,
+            timeout_secs,
+            "claude",
+            self.log_stderr,
+            Some(&format!("{}\n", message)),
+            max_output_bytes(&request),
+        )
+        .await?;
+
+        if !output.status.success() {
+            let stderr = decode_output(&output.stderr, "claude", "stderr");
+            let exit = CliExit::from_status(&output.status);
+            return Err(self.classify_error(&stderr, exit, &request));
+        }
+
+        // The stream's result event is the same object `--output-format json` prints
+        let stdout = decode_output(&output.stdout, "claude", "stdout");
+        let Some(result) = stdout.lines().rev().find(|line| {
+            serde_json::from_str::<serde_json::Value>(line).is_ok_and(|event| event["type"] == "result")
+        }) else {
+            let err = LlmError::ParseError {
+                format: "stream-json".to_string(),
+                source: "stream ended without a result event".into(),
+                output: None,
+            };
+            return Err(err.with_raw_output(&stdout));
+        };
+        let mut response = self
+            .parse_json_response(result.as_bytes())
+            .map_err(|e| self.classify_api_error(e, &request).with_raw_output(result))?;
+        if request.return_raw {
+            response.raw = Some(result.to_string());
+        }
+        Ok(response)
+    }
+
+    /// Turn an `ApiError` carrying the overload message into `ModelOverloaded`
+    ///
+    /// With `--output-format json` the CLI reports API failures in the result
+    /// envelope rather than on stderr.
+    fn classify_api_error(&self, err: LlmError, request: &LlmRequest) -> LlmError {
+        if let LlmError::ApiError { ref message, .. } = err {
+            if let Some(overloaded) =
+                overload_error(message, request.model.as_deref(), request.fallback_model.as_deref())
+            {
+                return overloaded;
+            }
+        }
+        err
+    }
 }
 
 impl Default for ClaudeProvider {
@@ -175,74 +523,218 @@ impl Default for ClaudeProvider {
 #[async_trait]
 impl LlmProvider for ClaudeProvider {
     async fn invoke(&self, request: LlmRequest) -> Result<LlmResponse, LlmError> {
-        // Use temp directory from request (managed by server)
-        // Each session has its own isolated directory
-        let temp_dir = request.temp_dir.clone().unwrap_or_else(|| {
-            std::env::temp_dir().join("praxio-default")
-        });
-        std::fs::create_dir_all(&temp_dir).map_err(LlmError::Io)?;
+        if let Some(ref pool) = self.pool {
+            if let Some((args, key)) = self.pooled_command(&request) {
+                return self.invoke_pooled(pool, args, key, request).await;
+            }
+        }
 
-        let mut cmd = self.build_command(&request);
-        cmd.current_dir(&temp_dir);
+        // Run in the caller's working directory, or the session temp directory
+        // (managed by server) so each session stays isolated
+        let run_dir = RunDir::prepare(&request, "praxio-claude-")?;
 
-        // Explicitly configure stdio - close stdin, capture stdout/stderr
-        cmd.stdin(std::process::Stdio::null());
-        cmd.stdout(std::process::Stdio::piped());
-        cmd.stderr(std::process::Stdio::piped());
+        let mut cmd = self.build_command(&request);
+        cmd.current_dir(run_dir.path());
 
         // Use timeout from request or provider default
         let timeout_secs = request.timeout_seconds.unwrap_or(self.timeout_seconds);
 
-        // Execute with timeout
-        let output = timeout(Duration::from_secs(timeout_secs), cmd.output())
-            .await
-            .map_err(|_| LlmError::Timeout {
-                seconds: timeout_secs,
-            })?
-            .map_err(LlmError::Io)?;
-
-        // Clean up temp directory
-        let _ = std::fs::remove_dir_all(&temp_dir);
+        // Execute with timeout, streaming stderr to tracing as it arrives
+        let output = run_cli(
+            cmd,
+            timeout_secs,
+            "claude",
+            self.log_stderr,
+            request.stdin_context.as_deref(),
+            max_output_bytes(&request),
+        )
+        .await
+        // Cut-off JSON is no use to the caller
+        .map_err(|e| e.log_partial_content("claude"))?;
+
+        // Remove the temp directory if this run created it
+        drop(run_dir);
 
         // Check exit status
         if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-            let exit_code = output.status.code().unwrap_or(-1);
-            return Err(self.classify_error(&stderr, exit_code));
+            let stderr = decode_output(&output.stderr, "claude", "stderr");
+            let exit = CliExit::from_status(&output.status);
+            return Err(self.classify_error(&stderr, exit, &request));
         }
 
         // Parse response
-        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-        self.parse_json_response(&stdout)
+        let stdout = decode_output(&output.stdout, "claude", "stdout");
+        let mut response = self
+            .parse_json_response(&output.stdout)
+            .map_err(|e| self.classify_api_error(e, &request).with_raw_output(&stdout))?;
+        if request.return_raw {
+            response.raw = Some(stdout);
+        }
+        Ok(response)
     }
 
-    async fn check_availability(&self) -> ProviderAvailability {
-        // Check if CLI exists
-        let cli_check = Command::new("which")
-            .arg("claude")
-            .output()
-            .await;
-
-        match cli_check {
-            Ok(output) if output.status.success() => {
-                // CLI exists, try to get version
-                let version_check = Command::new("claude")
-                    .arg("--version")
-                    .output()
-                    .await;
-
-                match version_check {
-                    Ok(output) if output.status.success() => ProviderAvailability::Available,
-                    Ok(_) => ProviderAvailability::Unavailable {
-                        reason: "claude CLI found but not responding correctly".to_string(),
-                    },
-                    Err(e) => ProviderAvailability::Unavailable {
-                        reason: format!("claude CLI error: {}", e),
-                    },
+    fn invoke_stream(&self, mut request: LlmRequest) -> BoxStream<'_, Result<StreamEvent, LlmError>> {
+        /// State carried between polls of the event stream
+        struct StreamState {
+            lines: CliLines,
+            run_dir: RunDir,
+            deadline: Instant,
+            timeout_secs: u64,
+            return_raw: bool,
+            completed: bool,
+            /// Kept to classify overload errors
+            request: LlmRequest,
+        }
+
+        request.output_format = OutputFormat::StreamJson;
+
+        let run_dir = match RunDir::prepare(&request, "praxio-claude-") {
+            Ok(run_dir) => run_dir,
+            Err(e) => return stream::once(async { Err(e) }).boxed(),
+        };
+
+        let mut cmd = self.build_command(&request);
+        cmd.current_dir(run_dir.path());
+
+        let lines = match CliLines::spawn(
+            cmd,
+            "claude",
+            self.log_stderr,
+            request.stdin_context.clone(),
+            max_output_bytes(&request),
+        ) {
+            Ok(lines) => lines,
+            Err(e) => return stream::once(async { Err(e) }).boxed(),
+        };
+
+        let timeout_secs = request.timeout_seconds.unwrap_or(self.timeout_seconds);
+        let state = StreamState {
+            lines,
+            run_dir,
+            deadline: Instant::now() + std::time::Duration::from_secs(timeout_secs),
+            timeout_secs,
+            return_raw: request.return_raw,
+            completed: false,
+            request,
+        };
+
+        stream::unfold(Some(state), move |state| async move {
+            let mut state = state?;
+
+            loop {
+                let next = timeout(
+                    state.deadline.saturating_duration_since(Instant::now()),
+                    state.lines.next_line(),
+                )
+                .await;
+
+                let line = match next {
+                    Err(_) => {
+                        // Text already arrived as deltas, so there is nothing to salvage
+                        let err = LlmError::Timeout {
+                            seconds: state.timeout_secs,
+                            partial_content: None,
+                        };
+                        return Some((Err(err), None));
+                    }
+                    Ok(Err(e)) => return Some((Err(e), None)),
+                    Ok(Ok(Some(line))) => line,
+                    Ok(Ok(None)) => {
+                        // End of output: reap the child and report failures
+                        let completed = state.completed;
+                        let finished = state.lines.finish().await;
+                        drop(state.run_dir);
+
+                        let (status, stderr) = match finished {
+                            Ok(finished) => finished,
+                            Err(e) => return Some((Err(e), None)),
+                        };
+
+                        if !status.success() {
+                            let stderr = decode_output(&stderr, "claude", "stderr");
+                            let exit = CliExit::from_status(&status);
+                            return Some((Err(self.classify_error(&stderr, exit, &state.request)), None));
+                        }
+
+                        if !completed {
+                            let err = LlmError::ParseError {
+                                format: "stream-json".to_string(),
+                                source: "stream ended without a result event".into(),
+                                output: None,
+                            };
+                            return Some((Err(err), None));
+                        }
+
+                        return None;
+                    }
+                };
+
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                match self.parse_stream_line(&line) {
+                    Ok(Some(mut event)) => {
+                        if let StreamEvent::Completed { ref mut response } = event {
+                            state.completed = true;
+                            if state.return_raw {
+                                response.raw = Some(line);
+                            }
+                        }
+                        return Some((Ok(event), Some(state)));
+                    }
+                    Ok(None) => continue,
+                    Err(e) => {
+                        let err = self.classify_api_error(e, &state.request);
+                        return Some((Err(err.with_raw_output(&line)), None));
+                    }
                 }
             }
-            _ => ProviderAvailability::Unavailable {
+        })
+        .boxed()
+    }
+
+    fn command_args(&self, request: &LlmRequest) -> Vec<String> {
+        ClaudeProvider::command_args(self, request)
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            supports_sessions: true,
+            supports_fallback: true,
+            supports_attachments: true,
+            supports_temperature: false,
+            supports_thinking_budget: true,
+            reports_cost: true,
+            supports_process_pool: true,
+            max_context: Some(200_000),
+        }
+    }
+
+    fn default_timeout_seconds(&self) -> Option<u64> {
+        Some(self.timeout_seconds)
+    }
+
+    async fn check_availability(&self) -> ProviderAvailability {
+        // Check if CLI exists
+        let Some(cli_path) = find_executable("claude") else {
+            return ProviderAvailability::Unavailable {
                 reason: "claude CLI not found in PATH".to_string(),
+            };
+        };
+
+        // CLI exists, try to get version
+        let version_check = Command::new(&cli_path).arg("--version").output().await;
+
+        match version_check {
+            Ok(output) if output.status.success() => ProviderAvailability::Available {
+                version: parse_version(&output.stdout),
+            },
+            Ok(_) => ProviderAvailability::Unavailable {
+                reason: "claude CLI found but not responding correctly".to_string(),
+            },
+            Err(e) => ProviderAvailability::Unavailable {
+                reason: format!("claude CLI error: {}", e),
             },
         }
     }
@@ -251,3 +743,138 @@ impl LlmProvider for ClaudeProvider {
         "claude"
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `--output-format json` envelope, with `model_usage` as its `modelUsage` if given
+    fn envelope(model_usage: Option<serde_json::Value>) -> Vec<u8> {
+        let mut json = serde_json::json!({
+            "type": "result",
+            "subtype": "success",
+            "is_error": false,
+            "duration_ms": 1500,
+            "duration_api_ms": 1200,
+            "num_turns": 1,
+            "result": "4",
+            "session_id": "session-1",
+            "total_cost_usd": 0.25,
+            "usage": {
+                "input_tokens": 100,
+                "cache_creation_input_tokens": 10,
+                "cache_read_input_tokens": 20,
+                "output_tokens": 50,
+                "service_tier": "standard"
+            },
+            "uuid": "uuid-1"
+        });
+        if let Some(model_usage) = model_usage {
+            json["modelUsage"] = model_usage;
+        }
+        serde_json::to_vec(&json).unwrap()
+    }
+
+    fn assert_synthesized_from_usage(response: &LlmResponse) {
+        assert_eq!(response.primary_model, "unknown");
+        assert_eq!(response.all_models_used, vec!["unknown".to_string()]);
+
+        let breakdown = response.model_breakdown.as_deref().unwrap();
+        assert_eq!(breakdown.len(), 1);
+        assert_eq!(breakdown[0].input_tokens, 100);
+        assert_eq!(breakdown[0].output_tokens, 50);
+        assert_eq!(breakdown[0].cache_read_tokens, 20);
+        assert_eq!(breakdown[0].cache_creation_tokens, 10);
+        assert_eq!(breakdown[0].cost_usd, 0.25);
+        assert_eq!(breakdown[0].context_window, 0);
+    }
+
+    fn args(request: LlmRequest) -> Vec<String> {
+        ClaudeProvider::new().command_args(&request)
+    }
+
+    #[test]
+    fn command_args_pass_model_session_and_system_prompt() {
+        let request = LlmRequest::builder("What is 2+2?")
+            .with_model("sonnet")
+            .with_fallback_model("haiku")
+            .with_session_id("session-1")
+            .with_system_prompt("Be brief.")
+            .build();
+
+        assert_eq!(
+            args(request),
+            [
+                "claude", "--print", "What is 2+2?",
+                "--resume", "session-1",
+                "--system-prompt", "Be brief.",
+                "--model", "sonnet",
+                "--fallback-model", "haiku",
+                "--output-format", "json",
+            ]
+        );
+    }
+
+    #[test]
+    fn command_args_keep_permissions_on_with_an_allow_list() {
+        let skipped = args(LlmRequest::builder("hi").with_skip_permissions(true).build());
+        assert!(skipped.contains(&"--dangerously-skip-permissions".to_string()));
+
+        let allowed = args(
+            LlmRequest::builder("hi")
+                .with_skip_permissions(true)
+                .with_allowed_tools(vec!["Read".to_string(), "Bash(git log:*)".to_string()])
+                .build(),
+        );
+        assert!(!allowed.contains(&"--dangerously-skip-permissions".to_string()));
+        assert!(allowed.ends_with(&["--allowedTools".to_string(), "Read,Bash(git log:*)".to_string()]));
+    }
+
+    #[test]
+    fn missing_model_usage_is_synthesized_from_usage() {
+        let response = ClaudeProvider::new().parse_json_response(&envelope(None)).unwrap();
+        assert_synthesized_from_usage(&response);
+    }
+
+    #[test]
+    fn empty_model_usage_is_synthesized_from_usage() {
+        let response = ClaudeProvider::new()
+            .parse_json_response(&envelope(Some(serde_json::json!({}))))
+            .unwrap();
+        assert_synthesized_from_usage(&response);
+    }
+
+    #[test]
+    fn invalid_utf8_is_a_parse_error_at_the_bad_byte() {
+        let json = envelope(None);
+        let text = String::from_utf8(json.clone()).unwrap();
+        let at = text.find("\"4\"").unwrap() + 1;
+        let mut corrupted = json;
+        corrupted[at] = 0xff;
+
+        let error = ClaudeProvider::new().parse_json_response(&corrupted).unwrap_err();
+        assert!(matches!(error, LlmError::ParseError { .. }), "{:?}", error);
+        let message = error.to_string();
+        assert!(message.contains("invalid unicode"), "{}", message);
+        assert!(message.contains("line 1 column"), "{}", message);
+    }
+
+    #[test]
+    fn reported_model_usage_is_kept() {
+        let model_usage = serde_json::json!({
+            "claude-sonnet-4-5": {
+                "inputTokens": 100,
+                "outputTokens": 50,
+                "cacheReadInputTokens": 20,
+                "cacheCreationInputTokens": 10,
+                "costUSD": 0.25,
+                "contextWindow": 200000
+            }
+        });
+        let response = ClaudeProvider::new()
+            .parse_json_response(&envelope(Some(model_usage)))
+            .unwrap();
+        assert_eq!(response.primary_model, "claude-sonnet-4-5");
+        assert_eq!(response.model_breakdown.unwrap()[0].context_window, 200000);
+    }
+}
@@ -7,43 +7,86 @@ pub struct ClaudeJsonResponse {
     #[serde(rename = "type")]
     #[allow(dead_code)]
     pub response_type: String,
-    #[allow(dead_code)]
     pub subtype: String,
+    /// Model stop reason (`end_turn`, `max_tokens`, ...), when the CLI reports it
+    #[serde(default)]
+    pub stop_reason: Option<String>,
     pub is_error: bool,
-    pub duration_ms: u64,
     #[allow(dead_code)]
+    pub duration_ms: u64,
     pub duration_api_ms: u64,
     pub num_turns: u32,
     pub result: String, // The actual content
     pub session_id: String,
     pub total_cost_usd: f64,
     pub usage: ClaudeUsage,
-    #[serde(rename = "modelUsage")]
+    #[serde(rename = "modelUsage", default)]
     pub model_usage: HashMap<String, ClaudeModelUsage>,
-    #[allow(dead_code)]
-    pub permission_denials: Vec<String>,
+    #[serde(default)]
+    pub permission_denials: Vec<ClaudePermissionDenial>,
     pub uuid: String,
 }
 
+/// An action the CLI refused because permission checks were on
+///
+/// Current CLIs report the tool call; plain strings are accepted too.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ClaudePermissionDenial {
+    Tool {
+        tool_name: String,
+        #[serde(default)]
+        tool_input: serde_json::Value,
+    },
+    Text(String),
+}
+
+impl ClaudePermissionDenial {
+    /// Describe the denied call in `allowed_tools` notation, e.g. `Bash(git push)`
+    pub fn describe(&self) -> String {
+        match self {
+            ClaudePermissionDenial::Text(text) => text.clone(),
+            ClaudePermissionDenial::Tool { tool_name, tool_input } => {
+                let target = ["command", "file_path", "path", "url"]
+                    .iter()
+                    .find_map(|key| tool_input.get(key).and_then(|v| v.as_str()).map(str::to_string))
+                    .or_else(|| {
+                        tool_input
+                            .as_object()
+                            .filter(|input| !input.is_empty())
+                            .map(|_| tool_input.to_string())
+                    });
+                match target {
+                    Some(target) => format!("{}({})", tool_name, target),
+                    None => tool_name.clone(),
+                }
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct ClaudeUsage {
-    pub input_tokens: u32,
-    pub cache_creation_input_tokens: u32,
-    pub cache_read_input_tokens: u32,
-    pub output_tokens: u32,
+    pub input_tokens: u64,
+    pub cache_creation_input_tokens: u64,
+    pub cache_read_input_tokens: u64,
+    pub output_tokens: u64,
+    /// Thinking tokens, when the CLI reports them apart from `output_tokens`
+    #[serde(default)]
+    pub thinking_tokens: Option<u64>,
     pub service_tier: String,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct ClaudeModelUsage {
     #[serde(rename = "inputTokens")]
-    pub input_tokens: u32,
+    pub input_tokens: u64,
     #[serde(rename = "outputTokens")]
-    pub output_tokens: u32,
+    pub output_tokens: u64,
     #[serde(rename = "cacheReadInputTokens")]
-    pub cache_read_input_tokens: u32,
+    pub cache_read_input_tokens: u64,
     #[serde(rename = "cacheCreationInputTokens")]
-    pub cache_creation_input_tokens: u32,
+    pub cache_creation_input_tokens: u64,
     #[serde(rename = "costUSD")]
     pub cost_usd: f64,
     #[serde(rename = "contextWindow")]
@@ -2,18 +2,47 @@ mod types;
 
 use async_trait::async_trait;
 use tokio::process::Command;
-use tokio::time::{timeout, Duration};
 
-use super::provider::{LlmProvider, ProviderAvailability};
+use super::attachments;
+use super::classify::{self, ErrorKind, StderrRule};
+use super::parse;
+use super::process::{apply_env, apply_overrides, decode_output, log_command, max_output_bytes, run_cli, CliExit, RunDir};
+use super::provider::{parse_version, LlmProvider, ProviderAvailability, ProviderCapabilities};
+use super::redact::redact;
+use super::which::find_executable;
+use super::pricing;
 use super::types::{
-    LlmRequest, LlmResponse, LlmResponseMetadata, TokenUsage,
+    CostSource, LlmRequest, LlmResponse, LlmResponseMetadata, ModelBreakdown, TokenUsage,
 };
 use crate::error::LlmError;
 use types::GeminiJsonResponse;
 
+/// Line prefixes the Gemini CLI prints around its JSON output
+const DEFAULT_NOISE_PREFIXES: &[&str] = &[
+    "Loaded cached credentials",
+    "Flushing log events",
+    "Data collection is disabled",
+    "(node:", // Node.js deprecation and experimental warnings
+];
+
+/// Built-in stderr rules, checked after the session check
+const DEFAULT_STDERR_RULES: &[(&str, ErrorKind, Option<&str>)] = &[
+    (
+        "GEMINI_API_KEY environment variable not found",
+        ErrorKind::ProviderUnavailable,
+        Some("GEMINI_API_KEY environment variable not set"),
+    ),
+    ("RESOURCE_EXHAUSTED|Quota exceeded", ErrorKind::RateLimited, None),
+    ("Error when talking to Gemini API", ErrorKind::ApiError, None),
+    ("not found", ErrorKind::ProviderUnavailable, Some("CLI not found in PATH")),
+];
+
 /// Gemini CLI provider
 pub struct GeminiProvider {
     timeout_seconds: u64,
+    log_stderr: bool,
+    stderr_rules: Vec<StderrRule>,
+    noise_prefixes: Vec<String>,
 }
 
 impl GeminiProvider {
@@ -21,6 +50,9 @@ impl GeminiProvider {
         // Gemini can be slower, so default to a longer timeout
         Self {
             timeout_seconds: 60,
+            log_stderr: true,
+            stderr_rules: classify::defaults(DEFAULT_STDERR_RULES),
+            noise_prefixes: DEFAULT_NOISE_PREFIXES.iter().map(|p| p.to_string()).collect(),
         }
     }
 
@@ -29,100 +61,237 @@ impl GeminiProvider {
         self
     }
 
-    /// Build command for Gemini CLI invocation
-    fn build_command(&self, request: &LlmRequest) -> Command {
-        let mut cmd = Command::new("gemini");
-        cmd.arg(&request.prompt);
+    /// Toggle live debug logging of the CLI's stderr while it runs
+    pub fn with_stderr_logging(mut self, enabled: bool) -> Self {
+        self.log_stderr = enabled;
+        self
+    }
+
+    /// Check `rule` before the others, so it can override a built-in one
+    pub fn with_stderr_rule(mut self, rule: StderrRule) -> Self {
+        self.stderr_rules.insert(0, rule);
+        self
+    }
+
+    /// Replace the rules mapping stderr to error kinds, dropping the built-in ones
+    pub fn with_stderr_rules(mut self, rules: Vec<StderrRule>) -> Self {
+        self.stderr_rules = rules;
+        self
+    }
+
+    /// Drop stdout lines starting with `prefix` before parsing the JSON
+    pub fn with_noise_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.noise_prefixes.push(prefix.into());
+        self
+    }
 
-        // Session management: use --resume for context continuity
+    /// Program and arguments for a Gemini CLI invocation
+    pub fn command_args(&self, request: &LlmRequest) -> Vec<String> {
+        // `@file` references make Gemini read the attachments into context
+        let attachments = attachments::references(request);
+        let prompt = if attachments.is_empty() {
+            request.prompt.clone()
+        } else {
+            let references: Vec<String> = attachments.iter().map(|name| format!("@{}", name)).collect();
+            format!("{}\n\n{}", request.prompt, references.join(" "))
+        };
+        let mut args = vec!["gemini".to_string(), prompt];
+
+        // Session management: the CLI saves each chat under the project (run
+        // directory) and `--resume` takes the saved session's ID, which it
+        // reports as `session_id`; the server reuses the session's directory
         if let Some(ref session_id) = request.session_id {
-            cmd.arg("--resume").arg(session_id);
+            args.extend(["--resume".to_string(), session_id.clone()]);
         }
 
         if let Some(ref system_prompt) = request.system_prompt {
-            cmd.arg("--system-prompt").arg(system_prompt);
+            args.extend(["--system-prompt".to_string(), system_prompt.clone()]);
         }
 
         if let Some(ref model) = request.model {
-            cmd.arg("--model").arg(model);
+            args.extend(["--model".to_string(), model.clone()]);
         }
 
         // Always use JSON for metadata
-        cmd.arg("--output-format").arg("json");
+        args.extend(["--output-format".to_string(), "json".to_string()]);
+
+        apply_overrides(&mut args, request);
+        args
+    }
 
+    /// Build command for Gemini CLI invocation
+    fn build_command(&self, request: &LlmRequest) -> Command {
+        let args = self.command_args(request);
+        log_command(&args, request, "gemini");
+        let mut cmd = Command::new(&args[0]);
+        cmd.args(&args[1..]);
+        apply_env(&mut cmd, request);
         cmd
     }
 
     /// Clean stdout from Gemini CLI
+    ///
+    /// Strips ANSI escape sequences, drops lines starting with a known noise
+    /// prefix, then extracts the span from the first `{` to the last `}` so any
+    /// remaining preamble or trailer around the JSON object is ignored.
     fn clean_stdout(&self, stdout: &str) -> String {
-        stdout
+        let stripped = strip_ansi(stdout);
+        let filtered = stripped
             .lines()
-            .filter(|line| !line.starts_with("Loaded cached credentials"))
+            .filter(|line| {
+                let line = line.trim_start();
+                !self.noise_prefixes.iter().any(|prefix| line.starts_with(prefix.as_str()))
+            })
             .collect::<Vec<_>>()
-            .join("\n")
+            .join("\n");
+
+        match (filtered.find('{'), filtered.rfind('}')) {
+            (Some(start), Some(end)) if start < end => filtered[start..=end].to_string(),
+            _ => filtered,
+        }
     }
 
     /// Parse JSON response from Gemini
-    fn parse_json_response(&self, json_str: &str) -> Result<LlmResponse, LlmError> {
-        let gemini_resp: GeminiJsonResponse = serde_json::from_str(json_str).map_err(|e| {
+    fn parse_json_response(&self, json: &[u8]) -> Result<LlmResponse, LlmError> {
+        let gemini_resp: GeminiJsonResponse = parse::from_slice_lenient(json, "gemini").map_err(|e| {
             LlmError::ParseError {
                 format: "json".to_string(),
                 source: Box::new(e),
+                output: None,
             }
         })?;
 
-        // Extract the primary model and its stats (should only be one)
-        let (model_name, model_stats) =
-            gemini_resp.stats.models.iter().next().ok_or_else(|| {
-                LlmError::ParseError {
-                    format: "json".to_string(),
-                    source: "No model stats found in Gemini response".into(),
-                }
-            })?;
+        if gemini_resp.stats.models.is_empty() {
+            return Err(LlmError::ParseError {
+                format: "json".to_string(),
+                source: "No model stats found in Gemini response".into(),
+                output: None,
+            });
+        }
+
+        // Sort by name so tie-breaks and ordering don't depend on HashMap iteration
+        let mut models: Vec<_> = gemini_resp.stats.models.iter().collect();
+        models.sort_by_key(|(name, _)| *name);
+
+        // Extract primary model (one with highest output tokens)
+        let primary_model = models
+            .iter()
+            .max_by_key(|(_, stats)| stats.tokens.candidates)
+            .map(|(model, _)| (*model).clone())
+            .unwrap_or_else(|| "unknown".to_string());
+
+        // Get all models used
+        let all_models_used: Vec<String> = models.iter().map(|(model, _)| (*model).clone()).collect();
 
-        // Calculate total tokens
-        let total_tokens = TokenUsage {
-            input: model_stats.tokens.prompt,
-            output: model_stats.tokens.candidates,
-            total: model_stats.tokens.total,
+        let mut total_tokens = TokenUsage {
+            input: 0,
+            output: 0,
+            total: 0,
             cache_creation: 0, // Not provided by Gemini
-            cache_read: model_stats.tokens.cached,
-            extended_thinking: Some(model_stats.tokens.thoughts),
+            cache_read: 0,
+            extended_thinking: Some(0),
         };
+        let mut duration_ms = 0;
+        let mut api_errors = 0;
+        let mut cost_usd = Some(0.0);
+        let mut model_breakdown = Vec::with_capacity(models.len());
+
+        for (model, stats) in &models {
+            let usage = TokenUsage {
+                input: stats.tokens.prompt,
+                output: stats.tokens.candidates,
+                total: stats.tokens.total,
+                cache_creation: 0,
+                cache_read: stats.tokens.cached,
+                extended_thinking: Some(stats.tokens.thoughts),
+            };
+
+            // Gemini's prompt count includes cached tokens; bill them at the cache rate
+            let billable = TokenUsage {
+                input: usage.input.saturating_sub(usage.cache_read),
+                ..usage.clone()
+            };
+            let model_cost = pricing::estimate_cost(model, &billable);
+
+            // Only report a total when every model could be priced
+            cost_usd = cost_usd.zip(model_cost).map(|(sum, cost)| sum + cost);
+
+            total_tokens.input = total_tokens.input.saturating_add(usage.input);
+            total_tokens.output = total_tokens.output.saturating_add(usage.output);
+            total_tokens.total = total_tokens.total.saturating_add(usage.total);
+            total_tokens.cache_read = total_tokens.cache_read.saturating_add(usage.cache_read);
+            total_tokens.extended_thinking = Some(
+                total_tokens
+                    .extended_thinking
+                    .unwrap_or(0)
+                    .saturating_add(stats.tokens.thoughts),
+            );
+            duration_ms += stats.api.total_latency_ms;
+            api_errors += stats.api.total_errors;
+
+            model_breakdown.push(ModelBreakdown {
+                model: (*model).clone(),
+                input_tokens: usage.input,
+                output_tokens: usage.output,
+                cache_read_tokens: usage.cache_read,
+                cache_creation_tokens: 0,
+                cost_usd: model_cost.unwrap_or(0.0),
+                context_window: 0, // Not provided by Gemini
+            });
+        }
 
         Ok(LlmResponse {
             content: gemini_resp.response,
-            primary_model: model_name.clone(),
-            all_models_used: vec![model_name.clone()],
+            primary_model,
+            all_models_used,
             provider: "gemini".to_string(),
             tokens: Some(total_tokens),
-            duration_ms: model_stats.api.total_latency_ms,
-            cost_usd: None, // Not provided by Gemini CLI
-            model_breakdown: None, // Gemini uses single model per request
+            api_duration_ms: duration_ms,
+            total_duration_ms: None,
+            cost_usd, // Not provided by Gemini CLI, estimated from tokens
+            // Only worth reporting when more than one model contributed
+            model_breakdown: (model_breakdown.len() > 1).then_some(model_breakdown),
             metadata: LlmResponseMetadata {
                 session_id: gemini_resp.session_id,
                 uuid: gemini_resp.uuid,
                 num_turns: gemini_resp.num_turns,
                 service_tier: None, // Not provided by Gemini
-                api_errors: Some(model_stats.api.total_errors),
+                api_errors: Some(api_errors),
                 tool_calls: Some(gemini_resp.stats.tools.total_calls),
+                cost_source: cost_usd.map(|_| CostSource::Estimated),
+                command: None,
+                budget_remaining_usd: None,
+                cached: None,
+                deduplicated: None,
+                cost_warning: None,
+                finish_reason: None, // Not reported by Gemini CLI
+                truncated: false,
+                request_id: None,
+                previous_session_id: None,
+                permission_denials: None,
+                lines_added: Some(gemini_resp.stats.files.total_lines_added),
+                lines_removed: Some(gemini_resp.stats.files.total_lines_removed),
+                provider_version: None,
             },
+            raw: None,
         })
     }
 
     /// Classify error from stderr
-    fn classify_error(&self, stderr: &str, exit_code: i32) -> LlmError {
-        if stderr.contains("GEMINI_API_KEY environment variable not found") {
-            LlmError::ProviderUnavailable {
-                provider: "gemini".to_string(),
-                reason: "GEMINI_API_KEY environment variable not set".to_string(),
-            }
-        } else if stderr.contains("Error when talking to Gemini API") {
-            LlmError::ApiError {
-                provider: "gemini".to_string(),
-                message: stderr.to_string(),
+    fn classify_error(&self, stderr: &str, exit: CliExit, session_id: Option<&str>) -> LlmError {
+        // The message is returned to the client, so mask secrets first
+        let stderr = &*redact(stderr);
+        let lower = stderr.to_lowercase();
+
+        if let Some(session_id) = session_id.filter(|_| {
+            lower.contains("session") && (lower.contains("not found") || lower.contains("invalid"))
+        }) {
+            LlmError::SessionNotFound {
+                session_id: session_id.to_string(),
             }
-        } else if stderr.contains("not found") || exit_code == 127 {
+        } else if let Some(error) = classify::classify(&self.stderr_rules, "gemini", stderr) {
+            error
+        } else if exit.code == 127 {
             LlmError::ProviderUnavailable {
                 provider: "gemini".to_string(),
                 reason: "CLI not found in PATH".to_string(),
@@ -131,12 +300,68 @@ impl GeminiProvider {
             LlmError::CliExecutionFailed {
                 command: "gemini".to_string(),
                 stderr: stderr.to_string(),
-                exit_code,
+                exit_code: exit.code,
+                signal: exit.signal,
             }
         }
     }
 }
 
+/// The span from the first `{` to the last `}`, or all of `bytes` if there is none
+fn json_object_span(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|&byte| byte == b'{');
+    let end = bytes.iter().rposition(|&byte| byte == b'}');
+    match (start, end) {
+        (Some(start), Some(end)) if start < end => &bytes[start..=end],
+        _ => bytes,
+    }
+}
+
+/// Remove ANSI escape sequences (colors, cursor movement, OSC titles)
+fn strip_ansi(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+
+        match chars.peek() {
+            // CSI: ESC [ params... final byte in @..~
+            Some('[') => {
+                chars.next();
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: ESC ] ... terminated by BEL or ESC \
+            Some(']') => {
+                chars.next();
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // Two-character escape
+            Some(_) => {
+                chars.next();
+            }
+            None => {}
+        }
+    }
+
+    out
+}
+
 impl Default for GeminiProvider {
     fn default() -> Self {
         Self::new()
@@ -146,46 +371,90 @@ impl Default for GeminiProvider {
 #[async_trait]
 impl LlmProvider for GeminiProvider {
     async fn invoke(&self, request: LlmRequest) -> Result<LlmResponse, LlmError> {
-        // Use temp directory from request (managed by server)
-        // Each session has its own isolated directory
-        let temp_dir = request.temp_dir.clone().unwrap_or_else(|| {
-            std::env::temp_dir().join("praxio-gemini-default")
-        });
-        std::fs::create_dir_all(&temp_dir).map_err(LlmError::Io)?;
+        // Run in the caller's working directory, or the session temp directory
+        // (managed by server) so each session stays isolated
+        let run_dir = RunDir::prepare(&request, "praxio-gemini-")?;
 
         let mut cmd = self.build_command(&request);
-        cmd.current_dir(&temp_dir);
-
-        // Explicitly configure stdio - close stdin, capture stdout/stderr
-        cmd.stdin(std::process::Stdio::null());
-        cmd.stdout(std::process::Stdio::piped());
-        cmd.stderr(std::process::Stdio::piped());
+        cmd.current_dir(run_dir.path());
 
         // Use timeout from request or provider default
         let timeout_secs = request.timeout_seconds.unwrap_or(self.timeout_seconds);
 
-        // Execute with timeout
-        let output = timeout(Duration::from_secs(timeout_secs), cmd.output())
-            .await
-            .map_err(|_| LlmError::Timeout {
-                seconds: timeout_secs,
-            })?
-            .map_err(LlmError::Io)?;
+        // Execute with timeout, streaming stderr to tracing as it arrives
+        let output = run_cli(
+            cmd,
+            timeout_secs,
+            "gemini",
+            self.log_stderr,
+            request.stdin_context.as_deref(),
+            max_output_bytes(&request),
+        )
+        .await
+        // Cut-off JSON is no use to the caller
+        .map_err(|e| e.log_partial_content("gemini"))?;
 
-        // Clean up temp directory
-        let _ = std::fs::remove_dir_all(&temp_dir);
+        // Remove the temp directory if this run created it
+        drop(run_dir);
 
         // Check exit status
         if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-            let exit_code = output.status.code().unwrap_or(-1);
-            return Err(self.classify_error(&stderr, exit_code));
+            let stderr = decode_output(&output.stderr, "gemini", "stderr");
+            let exit = CliExit::from_status(&output.status);
+            return Err(self.classify_error(&stderr, exit, request.session_id.as_deref()));
         }
 
-        // Parse response
-        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-        let cleaned_stdout = self.clean_stdout(&stdout);
-        self.parse_json_response(&cleaned_stdout)
+        // Parse response. Cleaning works on text, so output that isn't UTF-8
+        // goes to the parser as raw bytes, which reports where the bad byte is.
+        let stdout = decode_output(&output.stdout, "gemini", "stdout");
+        let json = match std::str::from_utf8(&output.stdout) {
+            Ok(text) => self.clean_stdout(text).into_bytes(),
+            Err(_) => json_object_span(&output.stdout).to_vec(),
+        };
+        let mut response = self
+            .parse_json_response(&json)
+            .map_err(|e| e.with_raw_output(&stdout))?;
+
+        // A CLI that couldn't resume may start a fresh chat instead; don't
+        // let the caller believe the earlier context is still there
+        if let (Some(requested), Some(reported)) = (&request.session_id, &response.metadata.session_id) {
+            if requested != reported {
+                tracing::warn!(
+                    "⚠️  Gemini started session {} instead of resuming {}",
+                    reported,
+                    requested
+                );
+                return Err(LlmError::SessionNotFound {
+                    session_id: requested.clone(),
+                });
+            }
+        }
+
+        if request.return_raw {
+            response.raw = Some(stdout);
+        }
+        Ok(response)
+    }
+
+    fn command_args(&self, request: &LlmRequest) -> Vec<String> {
+        GeminiProvider::command_args(self, request)
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            supports_sessions: true,
+            supports_fallback: false,
+            supports_attachments: true,
+            supports_temperature: false,
+            supports_thinking_budget: false,
+            reports_cost: false,
+            supports_process_pool: false,
+            max_context: Some(1_048_576),
+        }
+    }
+
+    fn default_timeout_seconds(&self) -> Option<u64> {
+        Some(self.timeout_seconds)
     }
 
     async fn check_availability(&self) -> ProviderAvailability {
@@ -197,17 +466,118 @@ impl LlmProvider for GeminiProvider {
         }
 
         // 2. Check if CLI exists
-        let cli_check = Command::new("which").arg("gemini").output().await;
-
-        match cli_check {
-            Ok(output) if output.status.success() => ProviderAvailability::Available,
-            _ => ProviderAvailability::Unavailable {
+        let Some(cli_path) = find_executable("gemini") else {
+            return ProviderAvailability::Unavailable {
                 reason: "gemini CLI not found in PATH".to_string(),
-            },
-        }
+            };
+        };
+
+        // 3. Record the version; a CLI that can't report one is still usable
+        let version = match Command::new(&cli_path).arg("--version").output().await {
+            Ok(output) if output.status.success() => parse_version(&output.stdout),
+            _ => None,
+        };
+        ProviderAvailability::Available { version }
     }
 
     fn name(&self) -> &str {
         "gemini"
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `--output-format json` output from a call that routed through two models
+    const TWO_MODEL_OUTPUT: &str = r#"{
+  "response": "Done.",
+  "session_id": "session-1",
+  "stats": {
+    "models": {
+      "gemini-2.5-flash": {
+        "api": { "totalRequests": 1, "totalErrors": 0, "totalLatencyMs": 300 },
+        "tokens": { "prompt": 200, "candidates": 20, "total": 225, "cached": 0, "thoughts": 5, "tool": 0 }
+      },
+      "gemini-2.5-pro": {
+        "api": { "totalRequests": 2, "totalErrors": 1, "totalLatencyMs": 1700 },
+        "tokens": { "prompt": 1000, "candidates": 400, "total": 1450, "cached": 100, "thoughts": 50, "tool": 0 }
+      }
+    },
+    "tools": { "totalCalls": 3 },
+    "files": { "totalLinesAdded": 7, "totalLinesRemoved": 2 }
+  }
+}"#;
+
+    #[test]
+    fn command_args_pass_session_system_prompt_and_model() {
+        let request = LlmRequest::builder("What is 2+2?")
+            .with_session_id("session-1")
+            .with_system_prompt("Be brief.")
+            .with_model("gemini-2.5-pro")
+            .build();
+
+        assert_eq!(
+            GeminiProvider::new().command_args(&request),
+            [
+                "gemini", "What is 2+2?",
+                "--resume", "session-1",
+                "--system-prompt", "Be brief.",
+                "--model", "gemini-2.5-pro",
+                "--output-format", "json",
+            ]
+        );
+    }
+
+    #[test]
+    fn two_model_stats_are_summed() {
+        let response = GeminiProvider::new()
+            .parse_json_response(TWO_MODEL_OUTPUT.as_bytes())
+            .unwrap();
+
+        assert_eq!(response.primary_model, "gemini-2.5-pro");
+        assert_eq!(response.all_models_used, vec!["gemini-2.5-flash", "gemini-2.5-pro"]);
+
+        let tokens = response.tokens.unwrap();
+        assert_eq!(tokens.input, 1200);
+        assert_eq!(tokens.output, 420);
+        assert_eq!(tokens.total, 1675);
+        assert_eq!(tokens.cache_read, 100);
+        assert_eq!(tokens.extended_thinking, Some(55));
+        assert_eq!(response.api_duration_ms, 2000);
+        assert_eq!(response.metadata.api_errors, Some(1));
+
+        let breakdown = response.model_breakdown.unwrap();
+        assert_eq!(breakdown.len(), 2);
+        assert_eq!(breakdown[1].model, "gemini-2.5-pro");
+        assert_eq!(breakdown[1].output_tokens, 400);
+    }
+
+    #[test]
+    fn clean_stdout_drops_leading_and_trailing_noise_lines() {
+        let stdout = "Loaded cached credentials.\n  (node:1234) DeprecationWarning: punycode\n{\"response\": \"hi\"}\nFlushing log events to Clearcut.\n";
+        assert_eq!(GeminiProvider::new().clean_stdout(stdout), "{\"response\": \"hi\"}");
+    }
+
+    #[test]
+    fn clean_stdout_cuts_banners_around_the_json() {
+        let stdout = "\x1b[1;34mGemini CLI v0.9\x1b[0m\nTips for getting started\n{\n  \"response\": \"{nested}\"\n}\nSee you next time!\n";
+        assert_eq!(
+            GeminiProvider::new().clean_stdout(stdout),
+            "{\n  \"response\": \"{nested}\"\n}"
+        );
+    }
+
+    #[test]
+    fn clean_stdout_honors_custom_noise_prefixes() {
+        let provider = GeminiProvider::new().with_noise_prefix("Using proxy");
+        let stdout = "Using proxy {http://proxy:8080}\n{\"response\": \"hi\"}";
+        assert_eq!(provider.clean_stdout(stdout), "{\"response\": \"hi\"}");
+    }
+
+    #[test]
+    fn clean_stdout_without_json_keeps_the_remaining_text() {
+        let stdout = "Loaded cached credentials.\n\x1b[31mError: not signed in\x1b[0m\n";
+        assert_eq!(GeminiProvider::new().clean_stdout(stdout), "Error: not signed in");
+    }
+}
@@ -7,7 +7,8 @@ use std::collections::HashMap;
 pub struct GeminiJsonResponse {
     pub response: String,
     pub stats: GeminiStats,
-    #[serde(rename = "sessionId")]
+    /// Reported as `session_id` by current CLIs; older builds used `sessionId`
+    #[serde(alias = "sessionId")]
     pub session_id: Option<String>,
     pub uuid: Option<String>,
     #[serde(rename = "numTurns")]
@@ -19,7 +20,6 @@ pub struct GeminiJsonResponse {
 pub struct GeminiStats {
     pub models: HashMap<String, GeminiModelStats>,
     pub tools: GeminiToolStats,
-    #[allow(dead_code)]
     pub files: GeminiFileStats,
 }
 
@@ -45,13 +45,13 @@ pub struct GeminiApiStats {
 /// Token usage stats
 #[derive(Debug, Clone, Deserialize)]
 pub struct GeminiTokenStats {
-    pub prompt: u32,
-    pub candidates: u32,
-    pub total: u32,
-    pub cached: u32,
-    pub thoughts: u32, // Extended thinking
+    pub prompt: u64,
+    pub candidates: u64,
+    pub total: u64,
+    pub cached: u64,
+    pub thoughts: u64, // Extended thinking
     #[allow(dead_code)]
-    pub tool: u32,
+    pub tool: u64,
 }
 
 /// Tool usage stats
@@ -65,9 +65,7 @@ pub struct GeminiToolStats {
 #[derive(Debug, Clone, Deserialize)]
 pub struct GeminiFileStats {
     #[serde(rename = "totalLinesAdded")]
-    #[allow(dead_code)]
     pub total_lines_added: u32,
     #[serde(rename = "totalLinesRemoved")]
-    #[allow(dead_code)]
     pub total_lines_removed: u32,
 }
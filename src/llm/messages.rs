@@ -0,0 +1,98 @@
+//! Chat-style conversations as delegation prompts
+//!
+//! Callers used to chat-completion APIs can describe a request as a list of
+//! system, user, and assistant messages. None of the bundled CLIs accept such a
+//! list, so by default it becomes a system prompt plus one flat prompt with
+//! role markers; a provider with a native format can override
+//! `LlmProvider::render_messages`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::LlmError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+}
+
+/// One message of a conversation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Message {
+    pub role: Role,
+    pub content: String,
+}
+
+/// Check that `messages` reads as a conversation awaiting a reply
+///
+/// System messages may only come first. The rest must start with a user
+/// message, alternate between user and assistant, and end with a user message.
+pub fn validate(messages: &[Message]) -> Result<(), LlmError> {
+    let invalid = |index: usize, reason: &str| LlmError::InvalidRequest {
+        message: format!("messages[{}]: {}", index, reason),
+    };
+
+    let mut previous = None;
+    for (index, message) in messages.iter().enumerate() {
+        if message.content.trim().is_empty() {
+            return Err(invalid(index, "content is empty"));
+        }
+        match (previous, message.role) {
+            (None | Some(Role::System), Role::System) => {}
+            (_, Role::System) => return Err(invalid(index, "system messages must come before the conversation")),
+            (None | Some(Role::System), Role::Assistant) => {
+                return Err(invalid(index, "the conversation must start with a user message"))
+            }
+            (Some(previous), role) if previous == role => {
+                return Err(invalid(index, "user and assistant messages must alternate"))
+            }
+            _ => {}
+        }
+        previous = Some(message.role);
+    }
+
+    match previous {
+        Some(Role::User) => Ok(()),
+        Some(_) => Err(invalid(messages.len() - 1, "the last message must be from the user")),
+        None => Err(LlmError::InvalidRequest {
+            message: "messages must contain at least one user message".to_string(),
+        }),
+    }
+}
+
+/// System prompt and flat prompt for CLIs that take a single prompt
+///
+/// System messages are joined into the system prompt. A lone user message is
+/// the prompt as-is; a longer conversation is written out with `User:` and
+/// `Assistant:` markers, ending with the message to answer.
+pub fn flatten(messages: &[Message]) -> (Option<String>, String) {
+    let system: Vec<&str> = messages
+        .iter()
+        .filter(|m| m.role == Role::System)
+        .map(|m| m.content.as_str())
+        .collect();
+    let system_prompt = (!system.is_empty()).then(|| system.join("\n\n"));
+
+    let turns: Vec<&Message> = messages.iter().filter(|m| m.role != Role::System).collect();
+    let prompt = match turns.as_slice() {
+        [only] => only.content.clone(),
+        _ => {
+            let transcript = turns
+                .iter()
+                .map(|m| match m.role {
+                    Role::Assistant => format!("Assistant: {}", m.content),
+                    _ => format!("User: {}", m.content),
+                })
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            format!(
+                "Continue this conversation by writing the assistant's reply to the last user message.\n\n{}",
+                transcript
+            )
+        }
+    };
+
+    (system_prompt, prompt)
+}
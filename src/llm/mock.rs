@@ -0,0 +1,210 @@
+//! In-process provider for tests and CI
+//!
+//! `MockProvider` never spawns a process. It answers from canned replies
+//! matched against the prompt, so the server's session mapping, error handling
+//! and metrics can be exercised without any CLI installed. Register it with
+//! `PraxioServerBuilder::with_provider` (and usually
+//! `with_default_providers(false)`).
+//!
+//! Only compiled with the `testing` feature (and for the crate's own tests).
+
+use async_trait::async_trait;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::provider::{LlmProvider, ProviderAvailability, ProviderCapabilities};
+use super::types::{CostSource, LlmRequest, LlmResponse, LlmResponseMetadata, TokenUsage};
+use crate::error::LlmError;
+
+type ErrorFn = Arc<dyn Fn() -> LlmError + Send + Sync>;
+
+/// What the mock answers with
+#[derive(Clone)]
+enum Reply {
+    Content(String),
+    Response(Box<LlmResponse>),
+    Error(ErrorFn),
+}
+
+/// Provider that returns configured responses or errors without a CLI
+///
+/// Replies are matched in the order they were added: the first whose pattern
+/// occurs in the prompt wins. A prompt that matches nothing is echoed back.
+pub struct MockProvider {
+    name: String,
+    replies: Vec<(String, Reply)>,
+    latency: Duration,
+    capabilities: ProviderCapabilities,
+    unavailable: Option<String>,
+    calls: AtomicUsize,
+    last_request: Mutex<Option<LlmRequest>>,
+}
+
+impl MockProvider {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            replies: Vec::new(),
+            latency: Duration::ZERO,
+            capabilities: ProviderCapabilities {
+                supports_sessions: true,
+                reports_cost: true,
+                ..Default::default()
+            },
+            unavailable: None,
+            calls: AtomicUsize::new(0),
+            last_request: Mutex::new(None),
+        }
+    }
+
+    /// Answer prompts containing `pattern` with `content`
+    pub fn with_response(mut self, pattern: impl Into<String>, content: impl Into<String>) -> Self {
+        self.replies.push((pattern.into(), Reply::Content(content.into())));
+        self
+    }
+
+    /// Answer prompts containing `pattern` with a complete response, returned as given
+    pub fn with_llm_response(mut self, pattern: impl Into<String>, response: LlmResponse) -> Self {
+        self.replies.push((pattern.into(), Reply::Response(Box::new(response))));
+        self
+    }
+
+    /// Fail prompts containing `pattern` with the error `error` builds
+    ///
+    /// `LlmError` isn't `Clone`, so a fresh one is built for every call.
+    pub fn with_error(
+        mut self,
+        pattern: impl Into<String>,
+        error: impl Fn() -> LlmError + Send + Sync + 'static,
+    ) -> Self {
+        self.replies.push((pattern.into(), Reply::Error(Arc::new(error))));
+        self
+    }
+
+    /// Wait this long before answering
+    ///
+    /// A latency longer than the request's `timeout_seconds` fails with
+    /// `Timeout`, like a CLI that never finishes.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Capabilities to advertise; defaults to sessions and reported cost
+    pub fn with_capabilities(mut self, capabilities: ProviderCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Report the provider as unavailable with `reason`
+    pub fn with_unavailable(mut self, reason: impl Into<String>) -> Self {
+        self.unavailable = Some(reason.into());
+        self
+    }
+
+    /// Number of times `invoke` has been called
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    /// The request `invoke` was last called with
+    pub fn last_request(&self) -> Option<LlmRequest> {
+        self.last_request.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn reply_for(&self, prompt: &str) -> Option<&Reply> {
+        self.replies
+            .iter()
+            .find(|(pattern, _)| prompt.contains(pattern.as_str()))
+            .map(|(_, reply)| reply)
+    }
+
+    /// A plain response carrying `content`, continuing the request's session or starting one
+    fn response(&self, request: &LlmRequest, content: String) -> LlmResponse {
+        let model = request.model.clone().unwrap_or_else(|| "mock".to_string());
+        let input = request.prompt.len() as u64 / 4;
+        let output = content.len() as u64 / 4;
+        let session_id = request
+            .session_id
+            .clone()
+            .unwrap_or_else(|| format!("mock-{}", uuid::Uuid::new_v4()));
+
+        LlmResponse {
+            content,
+            primary_model: model.clone(),
+            all_models_used: vec![model],
+            provider: self.name.clone(),
+            tokens: Some(TokenUsage {
+                input,
+                output,
+                total: input + output,
+                cache_creation: 0,
+                cache_read: 0,
+                extended_thinking: None,
+            }),
+            api_duration_ms: self.latency.as_millis() as u64,
+            total_duration_ms: None,
+            cost_usd: Some(0.0),
+            model_breakdown: None,
+            metadata: LlmResponseMetadata {
+                session_id: self.capabilities.supports_sessions.then_some(session_id),
+                num_turns: Some(1),
+                cost_source: Some(CostSource::Reported),
+                ..Default::default()
+            },
+            raw: None,
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for MockProvider {
+    async fn invoke(&self, request: LlmRequest) -> Result<LlmResponse, LlmError> {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        *self.last_request.lock().unwrap_or_else(|e| e.into_inner()) = Some(request.clone());
+
+        if let Some(seconds) = request.timeout_seconds {
+            if self.latency > Duration::from_secs(seconds) {
+                tokio::time::sleep(Duration::from_secs(seconds)).await;
+                return Err(LlmError::Timeout {
+                    seconds,
+                    partial_content: None,
+                });
+            }
+        }
+        tokio::time::sleep(self.latency).await;
+
+        let mut response = match self.reply_for(&request.prompt) {
+            Some(Reply::Content(content)) => self.response(&request, content.clone()),
+            Some(Reply::Response(response)) => return Ok((**response).clone()),
+            Some(Reply::Error(error)) => return Err(error()),
+            None => self.response(&request, request.prompt.clone()),
+        };
+        if request.return_raw {
+            response.raw = Some(response.content.clone());
+        }
+        Ok(response)
+    }
+
+    fn command_args(&self, request: &LlmRequest) -> Vec<String> {
+        vec![self.name.clone(), request.prompt.clone()]
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.capabilities.clone()
+    }
+
+    async fn check_availability(&self) -> ProviderAvailability {
+        match self.unavailable {
+            Some(ref reason) => ProviderAvailability::Unavailable {
+                reason: reason.clone(),
+            },
+            None => ProviderAvailability::Available { version: None },
+        }
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
@@ -1,9 +1,27 @@
+pub mod attachments;
+pub mod classify;
 pub mod claude;
 pub mod gemini;
+#[cfg(any(test, feature = "testing"))]
+pub mod mock;
+pub mod messages;
+pub mod models;
+pub mod ollama;
+pub mod openai;
+pub mod parse;
+pub mod pool;
+pub mod pricing;
+pub mod process;
 pub mod provider;
+pub mod redact;
 pub mod types;
+pub mod which;
 
 pub use claude::ClaudeProvider;
 pub use gemini::GeminiProvider;
-pub use provider::{LlmProvider, ProviderAvailability};
-pub use types::{LlmRequest, LlmResponse, OutputFormat, TokenUsage, ModelBreakdown};
+#[cfg(any(test, feature = "testing"))]
+pub use mock::MockProvider;
+pub use ollama::OllamaProvider;
+pub use openai::OpenAiProvider;
+pub use provider::{AvailabilityCache, LlmProvider, ProviderAvailability, ProviderCapabilities};
+pub use types::{CostSource, LlmRequest, LlmRequestBuilder, LlmResponse, OutputFormat, StreamEvent, TokenUsage, ModelBreakdown, to_value_compact};
@@ -0,0 +1,200 @@
+//! Friendly model aliases resolved to per-provider model IDs
+//!
+//! Lets callers ask for `"fast"`, `"smart"`, or `"cheap"` instead of tracking
+//! each CLI's model names. The built-in table can be extended or overridden
+//! with `set_alias` or a JSON file passed to `load_aliases`.
+//!
+//! Model names are also checked against the models each bundled CLI is known
+//! to accept, so a typo fails up front with a suggestion instead of deep
+//! inside the CLI.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::{OnceLock, RwLock};
+
+use crate::error::LlmError;
+
+/// Built-in aliases as (provider, alias, model)
+const DEFAULT_ALIASES: &[(&str, &str, &str)] = &[
+    ("claude", "fast", "claude-haiku-4-5"),
+    ("claude", "smart", "claude-opus-4-5"),
+    ("claude", "cheap", "claude-haiku-4-5"),
+    ("gemini", "fast", "gemini-2.5-flash"),
+    ("gemini", "smart", "gemini-2.5-pro"),
+    ("gemini", "cheap", "gemini-2.5-flash-lite"),
+    ("openai", "fast", "gpt-5-mini"),
+    ("openai", "smart", "gpt-5"),
+    ("openai", "cheap", "gpt-5-nano"),
+];
+
+/// Model names the bundled CLIs are known to accept, as (provider, model)
+///
+/// Dated releases (`claude-sonnet-4-5-20250929`) match their base name.
+/// Alias targets count as known too.
+const KNOWN_MODELS: &[(&str, &str)] = &[
+    ("claude", "opus"),
+    ("claude", "sonnet"),
+    ("claude", "haiku"),
+    ("claude", "opusplan"),
+    ("claude", "claude-opus-4-5"),
+    ("claude", "claude-opus-4-1"),
+    ("claude", "claude-opus-4"),
+    ("claude", "claude-sonnet-4-5"),
+    ("claude", "claude-sonnet-4"),
+    ("claude", "claude-haiku-4-5"),
+    ("claude", "claude-3-5-haiku"),
+    ("gemini", "gemini-2.5-pro"),
+    ("gemini", "gemini-2.5-flash"),
+    ("gemini", "gemini-2.5-flash-lite"),
+    ("gemini", "gemini-2.0-flash"),
+    ("openai", "gpt-5"),
+    ("openai", "gpt-5-mini"),
+    ("openai", "gpt-5-nano"),
+    ("openai", "gpt-5-codex"),
+    ("openai", "o3"),
+    ("openai", "o4-mini"),
+];
+
+/// provider -> alias -> model
+type AliasTable = HashMap<String, HashMap<String, String>>;
+
+fn table() -> &'static RwLock<AliasTable> {
+    static TABLE: OnceLock<RwLock<AliasTable>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut aliases = AliasTable::new();
+        for (provider, alias, model) in DEFAULT_ALIASES {
+            aliases
+                .entry(provider.to_string())
+                .or_default()
+                .insert(alias.to_string(), model.to_string());
+        }
+        RwLock::new(aliases)
+    })
+}
+
+/// Add or replace an alias for one provider
+pub fn set_alias(provider: impl Into<String>, alias: impl Into<String>, model: impl Into<String>) {
+    table()
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(provider.into())
+        .or_default()
+        .insert(alias.into(), model.into());
+}
+
+/// Merge aliases from a JSON file over the current table
+///
+/// The file maps provider names to alias tables:
+/// `{ "claude": { "fast": "claude-haiku-4-5" }, "ollama": { "fast": "llama3.2" } }`
+pub fn load_aliases(path: &Path) -> Result<(), LlmError> {
+    let overrides: AliasTable = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+
+    let mut table = table().write().unwrap_or_else(|e| e.into_inner());
+    for (provider, aliases) in overrides {
+        table.entry(provider).or_default().extend(aliases);
+    }
+
+    Ok(())
+}
+
+/// Resolve `model` for `provider`, passing non-alias names through unchanged
+///
+/// A name that is an alias for some provider but not this one is a
+/// `ModelNotAvailable` error rather than being handed to the CLI verbatim.
+pub fn resolve(provider: &str, model: &str) -> Result<String, LlmError> {
+    let table = table().read().unwrap_or_else(|e| e.into_inner());
+
+    if let Some(resolved) = table.get(provider).and_then(|aliases| aliases.get(model)) {
+        return Ok(resolved.clone());
+    }
+
+    if table.values().any(|aliases| aliases.contains_key(model)) {
+        return Err(LlmError::ModelNotAvailable {
+            model: model.to_string(),
+            provider: provider.to_string(),
+            reason: "alias has no model configured for this provider".to_string(),
+        });
+    }
+
+    Ok(model.to_string())
+}
+
+/// The provider's aliases and the models they resolve to
+pub fn aliases(provider: &str) -> BTreeMap<String, String> {
+    let table = table().read().unwrap_or_else(|e| e.into_inner());
+    table
+        .get(provider)
+        .map(|aliases| aliases.iter().map(|(alias, model)| (alias.clone(), model.clone())).collect())
+        .unwrap_or_default()
+}
+
+/// Models known for `provider`: the built-in list plus alias targets
+pub fn known_models(provider: &str) -> BTreeSet<String> {
+    KNOWN_MODELS
+        .iter()
+        .filter(|(p, _)| *p == provider)
+        .map(|(_, model)| model.to_string())
+        .chain(aliases(provider).into_values())
+        .collect()
+}
+
+/// Check a resolved model name against the models known for `provider`
+///
+/// `listed` replaces the built-in list for CLIs that enumerate their models
+/// (Ollama), where `llama3.2` also matches `llama3.2:latest`. Providers with
+/// nothing to check against accept any name. An unknown name is a
+/// `ModelNotAvailable` error suggesting the closest known name.
+pub fn check_known(provider: &str, model: &str, listed: Option<&[String]>) -> Result<(), LlmError> {
+    let known: BTreeSet<String> = match listed {
+        Some(listed) => listed.iter().cloned().collect(),
+        None => known_models(provider),
+    };
+    if known.is_empty() {
+        return Ok(());
+    }
+
+    let matches = |name: &String| {
+        name == model
+            || model.strip_prefix(name.as_str()).is_some_and(|rest| rest.starts_with(['-', ':']))
+            || name.strip_prefix(model).is_some_and(|rest| rest.starts_with(':'))
+    };
+    if known.iter().any(matches) {
+        return Ok(());
+    }
+
+    let closest = known
+        .iter()
+        .map(|name| (edit_distance(model, name), name))
+        .min()
+        .filter(|(distance, _)| *distance <= (model.chars().count() / 3).max(2));
+    let reason = match closest {
+        Some((_, name)) => format!("unknown model; did you mean '{}'?", name),
+        None => format!(
+            "unknown model; known models are {}",
+            known.iter().cloned().collect::<Vec<_>>().join(", ")
+        ),
+    };
+
+    Err(LlmError::ModelNotAvailable {
+        model: model.to_string(),
+        provider: provider.to_string(),
+        reason,
+    })
+}
+
+/// Levenshtein distance between two names, case-insensitively
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
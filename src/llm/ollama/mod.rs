@@ -0,0 +1,410 @@
+//! Ollama provider for local models
+//!
+//! Shells out to `ollama run`, which prints the model's reply on stdout and,
+//! with `--verbose`, timing and token statistics on stderr.
+//!
+//! Session continuity: the Ollama CLI has no equivalent of `--resume`, so every
+//! invocation is independent. Responses carry no `session_id`, and requests that
+//! carry one are rejected rather than silently losing the earlier context.
+//! Attachments are rejected for the same reason: the model only sees the prompt.
+
+use async_trait::async_trait;
+use tokio::process::Command;
+
+use super::classify::{self, ErrorKind, StderrRule};
+use super::process::{
+    apply_env, apply_overrides, decode_output, log_command, max_output_bytes, run_cli, CliExit, RunDir,
+    DEFAULT_MAX_OUTPUT_BYTES,
+};
+use super::provider::{parse_version, LlmProvider, ProviderAvailability, ProviderCapabilities};
+use super::redact::redact;
+use super::which::find_executable;
+use super::types::{LlmRequest, LlmResponse, LlmResponseMetadata, TokenUsage};
+use crate::error::LlmError;
+
+/// How long `ollama list` may take; it only asks the local daemon
+const LIST_MODELS_TIMEOUT_SECS: u64 = 10;
+
+/// Built-in stderr rules
+const DEFAULT_STDERR_RULES: &[(&str, ErrorKind, Option<&str>)] = &[
+    (
+        "could not connect to ollama|connection refused",
+        ErrorKind::ProviderUnavailable,
+        Some("ollama daemon is not running (start it with `ollama serve`)"),
+    ),
+    ("(?s)pull model manifest|model.*not found|not found.*model", ErrorKind::ModelNotAvailable, None),
+];
+
+/// Ollama CLI provider
+pub struct OllamaProvider {
+    timeout_seconds: u64,
+    log_stderr: bool,
+    stderr_rules: Vec<StderrRule>,
+    default_model: String,
+}
+
+impl OllamaProvider {
+    pub fn new() -> Self {
+        // Local models can be slow to load on first use
+        Self {
+            timeout_seconds: 120,
+            log_stderr: true,
+            stderr_rules: classify::defaults(DEFAULT_STDERR_RULES),
+            default_model: "llama3.2".to_string(),
+        }
+    }
+
+    pub fn with_timeout(mut self, seconds: u64) -> Self {
+        self.timeout_seconds = seconds;
+        self
+    }
+
+    /// Toggle live debug logging of the CLI's stderr while it runs
+    pub fn with_stderr_logging(mut self, enabled: bool) -> Self {
+        self.log_stderr = enabled;
+        self
+    }
+
+    /// Check `rule` before the others, so it can override a built-in one
+    pub fn with_stderr_rule(mut self, rule: StderrRule) -> Self {
+        self.stderr_rules.insert(0, rule);
+        self
+    }
+
+    /// Replace the rules mapping stderr to error kinds, dropping the built-in ones
+    pub fn with_stderr_rules(mut self, rules: Vec<StderrRule>) -> Self {
+        self.stderr_rules = rules;
+        self
+    }
+
+    /// Model used when a request doesn't name one (`ollama run` requires a model)
+    pub fn with_default_model(mut self, model: impl Into<String>) -> Self {
+        self.default_model = model.into();
+        self
+    }
+
+    /// Program and arguments for an Ollama CLI invocation
+    pub fn command_args(&self, request: &LlmRequest) -> Vec<String> {
+        let model = request.model.as_deref().unwrap_or(&self.default_model);
+        let mut args = vec!["ollama".to_string(), "run".to_string(), model.to_string()];
+
+        // `ollama run` has no system prompt flag, so fold it into the prompt
+        let prompt = match request.system_prompt {
+            Some(ref sys_prompt) => format!("{}\n\n{}", sys_prompt, request.prompt),
+            None => request.prompt.clone(),
+        };
+        args.push(prompt);
+
+        // Token and timing stats are printed to stderr in verbose mode
+        args.push("--verbose".to_string());
+        args.push("--nowordwrap".to_string());
+
+        apply_overrides(&mut args, request);
+        args
+    }
+
+    /// Build command for Ollama CLI invocation
+    fn build_command(&self, request: &LlmRequest) -> Command {
+        let args = self.command_args(request);
+        log_command(&args, request, "ollama");
+        let mut cmd = Command::new(&args[0]);
+        cmd.args(&args[1..]);
+        apply_env(&mut cmd, request);
+        cmd
+    }
+
+    /// Build a response from stdout (content) and stderr (verbose stats)
+    fn parse_response(&self, model: &str, stdout: &str, stderr: &str, wall_ms: u64) -> LlmResponse {
+        let stats = VerboseStats::parse(stderr);
+
+        let tokens = match (stats.prompt_tokens, stats.output_tokens) {
+            (None, None) => None,
+            (input, output) => {
+                let input = input.unwrap_or(0);
+                let output = output.unwrap_or(0);
+                Some(TokenUsage {
+                    input,
+                    output,
+                    total: input.saturating_add(output),
+                    cache_creation: 0, // Not applicable to local models
+                    cache_read: 0,
+                    extended_thinking: None,
+                })
+            }
+        };
+
+        LlmResponse {
+            content: stdout.trim_end().to_string(),
+            primary_model: model.to_string(),
+            all_models_used: vec![model.to_string()],
+            provider: "ollama".to_string(),
+            tokens,
+            api_duration_ms: stats.total_duration_ms.unwrap_or(wall_ms),
+            total_duration_ms: None,
+            cost_usd: None, // Local inference has no per-token cost
+            model_breakdown: None,
+            metadata: LlmResponseMetadata::default(),
+            raw: None,
+        }
+    }
+
+    /// Classify error from stderr
+    fn classify_error(&self, stderr: &str, exit: CliExit) -> LlmError {
+        // The message is returned to the client, so mask secrets first
+        let stderr = &*redact(stderr);
+
+        if let Some(error) = classify::classify(&self.stderr_rules, "ollama", stderr) {
+            error
+        } else if exit.code == 127 {
+            LlmError::ProviderUnavailable {
+                provider: "ollama".to_string(),
+                reason: "CLI not found in PATH".to_string(),
+            }
+        } else {
+            LlmError::CliExecutionFailed {
+                command: "ollama".to_string(),
+                stderr: stderr.to_string(),
+                exit_code: exit.code,
+                signal: exit.signal,
+            }
+        }
+    }
+}
+
+impl Default for OllamaProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Statistics printed by `ollama run --verbose`
+#[derive(Debug, Default)]
+struct VerboseStats {
+    total_duration_ms: Option<u64>,
+    prompt_tokens: Option<u64>,
+    output_tokens: Option<u64>,
+}
+
+impl VerboseStats {
+    fn parse(stderr: &str) -> Self {
+        let mut stats = Self::default();
+
+        for line in stderr.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+
+            match key.trim() {
+                "total duration" => stats.total_duration_ms = parse_go_duration_ms(value),
+                "prompt eval count" => stats.prompt_tokens = parse_token_count(value),
+                "eval count" => stats.output_tokens = parse_token_count(value),
+                _ => {}
+            }
+        }
+
+        stats
+    }
+}
+
+/// Parse "26 token(s)" into 26
+fn parse_token_count(value: &str) -> Option<u64> {
+    value.split_whitespace().next()?.parse().ok()
+}
+
+/// Parse a Go duration string ("1.5s", "850.2ms", "1m2.5s") into milliseconds
+fn parse_go_duration_ms(value: &str) -> Option<u64> {
+    let mut total_ms = 0.0;
+    let mut number = String::new();
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() || c == '.' {
+            number.push(c);
+            continue;
+        }
+
+        let mut unit = c.to_string();
+        while let Some(&next) = chars.peek() {
+            if next.is_ascii_digit() || next == '.' {
+                break;
+            }
+            unit.push(next);
+            chars.next();
+        }
+
+        let n: f64 = number.parse().ok()?;
+        number.clear();
+        total_ms += match unit.as_str() {
+            "h" => n * 3_600_000.0,
+            "m" => n * 60_000.0,
+            "s" => n * 1_000.0,
+            "ms" => n,
+            "µs" | "us" => n / 1_000.0,
+            "ns" => n / 1_000_000.0,
+            _ => return None,
+        };
+    }
+
+    Some(total_ms.round() as u64)
+}
+
+#[async_trait]
+impl LlmProvider for OllamaProvider {
+    async fn invoke(&self, request: LlmRequest) -> Result<LlmResponse, LlmError> {
+        if request.session_id.is_some() {
+            return Err(LlmError::InvalidRequest {
+                message: "ollama does not support resuming sessions; omit session_id".to_string(),
+            });
+        }
+
+        if request.attachments.as_ref().is_some_and(|a| !a.is_empty()) {
+            return Err(LlmError::InvalidRequest {
+                message: "ollama cannot read attachments; include the content in the prompt".to_string(),
+            });
+        }
+
+        // Run in the caller's working directory, or the session temp directory
+        // (managed by server) so each session stays isolated
+        let run_dir = RunDir::prepare(&request, "praxio-ollama-")?;
+
+        let mut cmd = self.build_command(&request);
+        cmd.current_dir(run_dir.path());
+
+        // Use timeout from request or provider default
+        let timeout_secs = request.timeout_seconds.unwrap_or(self.timeout_seconds);
+
+        // Execute with timeout, streaming stderr to tracing as it arrives
+        let start = std::time::Instant::now();
+        let output = run_cli(
+            cmd,
+            timeout_secs,
+            "ollama",
+            self.log_stderr,
+            request.stdin_context.as_deref(),
+            max_output_bytes(&request),
+        )
+        .await?;
+        let wall_ms = start.elapsed().as_millis() as u64;
+
+        // Remove the temp directory if this run created it
+        drop(run_dir);
+
+        let stderr = decode_output(&output.stderr, "ollama", "stderr");
+
+        // Check exit status
+        if !output.status.success() {
+            let exit = CliExit::from_status(&output.status);
+            return Err(self.classify_error(&stderr, exit));
+        }
+
+        let stdout = decode_output(&output.stdout, "ollama", "stdout");
+        let model = request.model.as_deref().unwrap_or(&self.default_model);
+        let mut response = self.parse_response(model, &stdout, &stderr, wall_ms);
+        if request.return_raw {
+            response.raw = Some(stdout);
+        }
+        Ok(response)
+    }
+
+    fn command_args(&self, request: &LlmRequest) -> Vec<String> {
+        OllamaProvider::command_args(self, request)
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        // Context length depends on which local model is loaded
+        ProviderCapabilities {
+            supports_sessions: false,
+            supports_fallback: false,
+            supports_attachments: false,
+            supports_temperature: false,
+            supports_thinking_budget: false,
+            reports_cost: false,
+            supports_process_pool: false,
+            max_context: None,
+        }
+    }
+
+    fn default_timeout_seconds(&self) -> Option<u64> {
+        Some(self.timeout_seconds)
+    }
+
+    async fn check_availability(&self) -> ProviderAvailability {
+        // 1. Check if CLI exists
+        let Some(cli_path) = find_executable("ollama") else {
+            return ProviderAvailability::Unavailable {
+                reason: "ollama CLI not found in PATH".to_string(),
+            };
+        };
+
+        // 2. Check the daemon responds (`ollama list` talks to the local server)
+        let daemon_check = Command::new(&cli_path).arg("list").output().await;
+
+        match daemon_check {
+            Ok(output) if output.status.success() => {
+                // `ollama --version` prints `ollama version is 0.5.7`
+                let version = match Command::new(&cli_path).arg("--version").output().await {
+                    Ok(output) if output.status.success() => parse_version(&output.stdout),
+                    _ => None,
+                };
+                ProviderAvailability::Available { version }
+            }
+            Ok(_) => ProviderAvailability::Unavailable {
+                reason: "ollama CLI found but daemon is not responding (run `ollama serve`)"
+                    .to_string(),
+            },
+            Err(e) => ProviderAvailability::Unavailable {
+                reason: format!("ollama CLI error: {}", e),
+            },
+        }
+    }
+
+    async fn list_models(&self) -> Result<Option<Vec<String>>, LlmError> {
+        let mut cmd = Command::new("ollama");
+        cmd.arg("list");
+        let output = run_cli(cmd, LIST_MODELS_TIMEOUT_SECS, "ollama", false, None, DEFAULT_MAX_OUTPUT_BYTES).await?;
+
+        if !output.status.success() {
+            let stderr = decode_output(&output.stderr, "ollama", "stderr");
+            return Err(self.classify_error(&stderr, CliExit::from_status(&output.status)));
+        }
+
+        // A header row, then one installed model per line: NAME ID SIZE MODIFIED
+        let stdout = decode_output(&output.stdout, "ollama", "stdout");
+        let models = stdout
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split_whitespace().next())
+            .map(String::from)
+            .collect();
+        Ok(Some(models))
+    }
+
+    fn name(&self) -> &str {
+        "ollama"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_args_use_the_default_model_unless_one_is_named() {
+        let provider = OllamaProvider::new().with_default_model("qwen2.5");
+
+        assert_eq!(
+            provider.command_args(&LlmRequest::builder("What is 2+2?").build()),
+            ["ollama", "run", "qwen2.5", "What is 2+2?", "--verbose", "--nowordwrap"]
+        );
+
+        let request = LlmRequest::builder("What is 2+2?")
+            .with_model("llama3.2")
+            .with_system_prompt("Be brief.")
+            .build();
+        assert_eq!(
+            provider.command_args(&request),
+            ["ollama", "run", "llama3.2", "Be brief.\n\nWhat is 2+2?", "--verbose", "--nowordwrap"]
+        );
+    }
+}
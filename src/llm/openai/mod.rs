@@ -0,0 +1,392 @@
+mod types;
+
+use async_trait::async_trait;
+use tokio::process::Command;
+
+use super::attachments;
+use super::classify::{self, ErrorKind, StderrRule};
+use super::process::{apply_env, apply_overrides, decode_output, log_command, max_output_bytes, run_cli, CliExit, RunDir};
+use super::provider::{parse_version, LlmProvider, ProviderAvailability, ProviderCapabilities};
+use super::redact::redact;
+use super::which::find_executable;
+use super::pricing;
+use super::types::{
+    CostSource, LlmRequest, LlmResponse, LlmResponseMetadata, ModelBreakdown, TokenUsage,
+};
+use crate::error::LlmError;
+use types::CodexEvent;
+
+/// Built-in stderr rules, matched against stderr and stdout together
+const DEFAULT_STDERR_RULES: &[(&str, ErrorKind, Option<&str>)] = &[
+    ("(?i)not logged in|401|unauthorized|invalid api key", ErrorKind::AuthenticationFailed, None),
+    ("(?i)insufficient_quota|quota exceeded", ErrorKind::RateLimited, Some("quota exhausted: {stderr}")),
+    ("(?i)rate limit|429", ErrorKind::RateLimited, None),
+];
+
+/// OpenAI Codex CLI provider
+pub struct OpenAiProvider {
+    timeout_seconds: u64,
+    log_stderr: bool,
+    stderr_rules: Vec<StderrRule>,
+}
+
+impl OpenAiProvider {
+    pub fn new() -> Self {
+        // Codex runs an agent loop, so give it as long as Gemini
+        Self {
+            timeout_seconds: 60,
+            log_stderr: true,
+            stderr_rules: classify::defaults(DEFAULT_STDERR_RULES),
+        }
+    }
+
+    pub fn with_timeout(mut self, seconds: u64) -> Self {
+        self.timeout_seconds = seconds;
+        self
+    }
+
+    /// Toggle live debug logging of the CLI's stderr while it runs
+    pub fn with_stderr_logging(mut self, enabled: bool) -> Self {
+        self.log_stderr = enabled;
+        self
+    }
+
+    /// Check `rule` before the others, so it can override a built-in one
+    pub fn with_stderr_rule(mut self, rule: StderrRule) -> Self {
+        self.stderr_rules.insert(0, rule);
+        self
+    }
+
+    /// Replace the rules mapping stderr to error kinds, dropping the built-in ones
+    pub fn with_stderr_rules(mut self, rules: Vec<StderrRule>) -> Self {
+        self.stderr_rules = rules;
+        self
+    }
+
+    /// Program and arguments for a Codex CLI invocation
+    pub fn command_args(&self, request: &LlmRequest) -> Vec<String> {
+        let mut args = vec!["codex".to_string(), "exec".to_string()];
+
+        // JSONL event stream on stdout carries the thread id and token usage
+        args.push("--json".to_string());
+
+        // Session temp dirs are not git repositories
+        args.push("--skip-git-repo-check".to_string());
+
+        // Non-interactive: auto-approve actions inside the workspace sandbox
+        args.push("--full-auto".to_string());
+
+        if let Some(ref model) = request.model {
+            args.extend(["--model".to_string(), model.clone()]);
+        }
+
+        // Images also go through --image so vision models see them directly
+        let images = request.attachments.iter().flatten().filter(|path| attachments::is_image(path));
+        for image in images {
+            args.extend(["--image".to_string(), image.display().to_string()]);
+        }
+
+        // Session management: `codex exec resume <id>` continues a thread
+        if let Some(ref session_id) = request.session_id {
+            args.extend(["resume".to_string(), session_id.clone()]);
+        }
+
+        // Codex has no system prompt flag, so fold it into the prompt
+        let mut prompt = match request.system_prompt {
+            Some(ref sys_prompt) => format!("{}\n\n{}", sys_prompt, request.prompt),
+            None => request.prompt.clone(),
+        };
+        let attachments = attachments::references(request);
+        if !attachments.is_empty() {
+            prompt = format!("{}\n\nAttached files: {}", prompt, attachments.join(", "));
+        }
+        args.push(prompt);
+
+        apply_overrides(&mut args, request);
+        args
+    }
+
+    /// Build command for Codex CLI invocation
+    fn build_command(&self, request: &LlmRequest) -> Command {
+        let args = self.command_args(request);
+        log_command(&args, request, "openai");
+        let mut cmd = Command::new(&args[0]);
+        cmd.args(&args[1..]);
+        apply_env(&mut cmd, request);
+        cmd
+    }
+
+    /// Parse the JSONL event stream from Codex
+    fn parse_json_response(&self, stdout: &[u8], model: Option<&str>) -> Result<LlmResponse, LlmError> {
+        let mut thread_id = None;
+        let mut content = None;
+        let mut usage = TokenUsage {
+            input: 0,
+            output: 0,
+            total: 0,
+            cache_creation: 0,
+            cache_read: 0,
+            extended_thinking: None,
+        };
+        let mut num_turns = 0;
+        let mut tool_calls = 0;
+        let mut errors = Vec::new();
+        let mut last_turn_failed = false;
+
+        // Codex may print non-JSON log lines; only JSON objects are events
+        for line in stdout
+            .split(|&byte| byte == b'\n')
+            .filter(|l| l.trim_ascii_start().starts_with(b"{"))
+        {
+            let event: CodexEvent = serde_json::from_slice(line).map_err(|e| LlmError::ParseError {
+                format: "jsonl".to_string(),
+                source: Box::new(e),
+                output: None,
+            })?;
+
+            match event {
+                CodexEvent::ThreadStarted { thread_id: id } => thread_id = Some(id),
+                CodexEvent::TurnCompleted { usage: turn } => {
+                    num_turns += 1;
+                    last_turn_failed = false;
+                    usage.input = usage.input.saturating_add(turn.input_tokens);
+                    usage.output = usage.output.saturating_add(turn.output_tokens);
+                    usage.cache_read = usage.cache_read.saturating_add(turn.cached_input_tokens);
+                }
+                CodexEvent::ItemCompleted { item } => {
+                    if item.is_tool_call() {
+                        tool_calls += 1;
+                    } else if item.item_type == "agent_message" {
+                        // The last agent message is the final answer
+                        content = item.text;
+                    }
+                }
+                CodexEvent::TurnFailed { error } => {
+                    last_turn_failed = true;
+                    errors.push(error.message);
+                }
+                CodexEvent::Error { message } => errors.push(message),
+                CodexEvent::Other => {}
+            }
+        }
+
+        let Some(content) = content else {
+            if !errors.is_empty() {
+                return Err(self.classify_error(&errors.join("\n"), CliExit::code(1)));
+            }
+            return Err(LlmError::ParseError {
+                format: "jsonl".to_string(),
+                source: "No agent message found in Codex output".into(),
+                output: None,
+            });
+        };
+
+        usage.total = usage.input.saturating_add(usage.output);
+
+        // Codex doesn't report which model served the thread, so echo the request
+        let model = model.unwrap_or("default").to_string();
+
+        // OpenAI's input count includes cached tokens; bill them at the cache rate
+        let billable = TokenUsage {
+            input: usage.input.saturating_sub(usage.cache_read),
+            ..usage.clone()
+        };
+        let cost_usd = pricing::estimate_cost(&model, &billable);
+
+        Ok(LlmResponse {
+            content,
+            primary_model: model.clone(),
+            all_models_used: vec![model.clone()],
+            provider: "openai".to_string(),
+            api_duration_ms: 0, // Filled in by invoke() from wall-clock time
+            total_duration_ms: None,
+            cost_usd, // Not reported by Codex CLI, estimated from tokens
+            model_breakdown: Some(vec![ModelBreakdown {
+                model,
+                input_tokens: usage.input,
+                output_tokens: usage.output,
+                cache_read_tokens: usage.cache_read,
+                cache_creation_tokens: 0,
+                cost_usd: cost_usd.unwrap_or(0.0),
+                context_window: 0, // Not reported by Codex CLI
+            }]),
+            tokens: Some(usage),
+            metadata: LlmResponseMetadata {
+                session_id: thread_id,
+                uuid: None,
+                num_turns: Some(num_turns),
+                service_tier: None,
+                api_errors: Some(errors.len() as u32),
+                tool_calls: Some(tool_calls),
+                cost_source: cost_usd.map(|_| CostSource::Estimated),
+                command: None,
+                budget_remaining_usd: None,
+                cached: None,
+                deduplicated: None,
+                cost_warning: None,
+                // A failed final turn means the last agent message may be partial
+                finish_reason: Some(if last_turn_failed { "failed" } else { "completed" }.to_string()),
+                truncated: last_turn_failed,
+                request_id: None,
+                previous_session_id: None,
+                permission_denials: None,
+                lines_added: None,
+                lines_removed: None,
+                provider_version: None,
+            },
+            raw: None,
+        })
+    }
+
+    /// Classify error from stderr
+    fn classify_error(&self, stderr: &str, exit: CliExit) -> LlmError {
+        // The message is returned to the client, so mask secrets first
+        let stderr = &*redact(stderr);
+
+        if let Some(error) = classify::classify(&self.stderr_rules, "openai", stderr) {
+            error
+        } else if exit.code == 127 {
+            LlmError::ProviderUnavailable {
+                provider: "openai".to_string(),
+                reason: "CLI not found in PATH".to_string(),
+            }
+        } else {
+            LlmError::CliExecutionFailed {
+                command: "codex".to_string(),
+                stderr: stderr.to_string(),
+                exit_code: exit.code,
+                signal: exit.signal,
+            }
+        }
+    }
+}
+
+impl Default for OpenAiProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OpenAiProvider {
+    async fn invoke(&self, request: LlmRequest) -> Result<LlmResponse, LlmError> {
+        // Run in the caller's working directory, or the session temp directory
+        // (managed by server) so each session stays isolated
+        let run_dir = RunDir::prepare(&request, "praxio-openai-")?;
+
+        let mut cmd = self.build_command(&request);
+        cmd.current_dir(run_dir.path());
+
+        // Use timeout from request or provider default
+        let timeout_secs = request.timeout_seconds.unwrap_or(self.timeout_seconds);
+
+        // Execute with timeout, streaming stderr to tracing as it arrives
+        let start = std::time::Instant::now();
+        let output = run_cli(
+            cmd,
+            timeout_secs,
+            "openai",
+            self.log_stderr,
+            request.stdin_context.as_deref(),
+            max_output_bytes(&request),
+        )
+        .await
+        // Cut-off JSON is no use to the caller
+        .map_err(|e| e.log_partial_content("openai"))?;
+        let wall_ms = start.elapsed().as_millis() as u64;
+
+        // Remove the temp directory if this run created it
+        drop(run_dir);
+
+        let stdout = decode_output(&output.stdout, "openai", "stdout");
+
+        // Check exit status; Codex reports API failures as events on stdout
+        if !output.status.success() {
+            let stderr = decode_output(&output.stderr, "openai", "stderr");
+            let exit = CliExit::from_status(&output.status);
+            return Err(self.classify_error(&format!("{}\n{}", stderr, stdout), exit));
+        }
+
+        let mut response = self
+            .parse_json_response(&output.stdout, request.model.as_deref())
+            .map_err(|e| e.with_raw_output(&stdout))?;
+        response.api_duration_ms = wall_ms;
+        if request.return_raw {
+            response.raw = Some(stdout);
+        }
+        Ok(response)
+    }
+
+    fn command_args(&self, request: &LlmRequest) -> Vec<String> {
+        OpenAiProvider::command_args(self, request)
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            supports_sessions: true,
+            supports_fallback: false,
+            supports_attachments: true,
+            supports_temperature: false,
+            supports_thinking_budget: false,
+            reports_cost: false,
+            supports_process_pool: false,
+            max_context: Some(400_000),
+        }
+    }
+
+    fn default_timeout_seconds(&self) -> Option<u64> {
+        Some(self.timeout_seconds)
+    }
+
+    async fn check_availability(&self) -> ProviderAvailability {
+        // Check if CLI exists
+        let Some(cli_path) = find_executable("codex") else {
+            return ProviderAvailability::Unavailable {
+                reason: "codex CLI not found in PATH".to_string(),
+            };
+        };
+
+        // CLI exists, try to get version
+        let version_check = Command::new(&cli_path).arg("--version").output().await;
+
+        match version_check {
+            Ok(output) if output.status.success() => ProviderAvailability::Available {
+                version: parse_version(&output.stdout),
+            },
+            Ok(_) => ProviderAvailability::Unavailable {
+                reason: "codex CLI found but not responding correctly".to_string(),
+            },
+            Err(e) => ProviderAvailability::Unavailable {
+                reason: format!("codex CLI error: {}", e),
+            },
+        }
+    }
+
+    fn name(&self) -> &str {
+        "openai"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_args_resume_a_thread_with_the_system_prompt_folded_in() {
+        let request = LlmRequest::builder("What is 2+2?")
+            .with_model("gpt-5-codex")
+            .with_session_id("thread-1")
+            .with_system_prompt("Be brief.")
+            .build();
+
+        assert_eq!(
+            OpenAiProvider::new().command_args(&request),
+            [
+                "codex", "exec", "--json", "--skip-git-repo-check", "--full-auto",
+                "--model", "gpt-5-codex",
+                "resume", "thread-1",
+                "Be brief.\n\nWhat is 2+2?",
+            ]
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use serde::Deserialize;
+
+/// One line of `codex exec --json` output
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type")]
+pub enum CodexEvent {
+    #[serde(rename = "thread.started")]
+    ThreadStarted { thread_id: String },
+    #[serde(rename = "turn.completed")]
+    TurnCompleted { usage: CodexUsage },
+    #[serde(rename = "turn.failed")]
+    TurnFailed { error: CodexErrorDetail },
+    #[serde(rename = "item.completed")]
+    ItemCompleted { item: CodexItem },
+    #[serde(rename = "error")]
+    Error { message: String },
+    /// Events we don't need (turn.started, item.started, item.updated, ...)
+    #[serde(other)]
+    Other,
+}
+
+/// Token usage reported at the end of each turn
+#[derive(Debug, Clone, Deserialize)]
+pub struct CodexUsage {
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub cached_input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+/// A completed thread item (agent message, reasoning, command, ...)
+#[derive(Debug, Clone, Deserialize)]
+pub struct CodexItem {
+    #[serde(rename = "type")]
+    pub item_type: String,
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+impl CodexItem {
+    /// Whether this item represents the agent acting through a tool
+    pub fn is_tool_call(&self) -> bool {
+        matches!(
+            self.item_type.as_str(),
+            "command_execution" | "file_change" | "mcp_tool_call" | "web_search"
+        )
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CodexErrorDetail {
+    pub message: String,
+}
@@ -0,0 +1,188 @@
+//! Recovery for slightly malformed CLI JSON
+//!
+//! CLIs occasionally print a log line after their JSON, or are cut off before
+//! the closing braces. `from_slice_lenient` parses output strictly first and
+//! only falls back to `repair_json` on a syntax error.
+
+use serde::de::{DeserializeOwned, IgnoredAny};
+
+/// Candidate starting points tried before giving up
+const MAX_REPAIR_ATTEMPTS: usize = 8;
+
+/// Deserialize CLI output, repairing it first if it isn't well-formed JSON
+///
+/// On failure the original parse error is returned, not the repaired one, so
+/// the caller reports what the CLI actually printed.
+pub fn from_slice_lenient<T: DeserializeOwned>(json: &[u8], provider: &str) -> Result<T, serde_json::Error> {
+    let error = match serde_json::from_slice(json) {
+        Ok(value) => return Ok(value),
+        Err(e) => e,
+    };
+    // Well-formed JSON of the wrong shape can't be repaired
+    if !error.is_syntax() && !error.is_eof() {
+        return Err(error);
+    }
+
+    let Some(repaired) = std::str::from_utf8(json).ok().and_then(repair_json) else {
+        return Err(error);
+    };
+    match serde_json::from_str(&repaired) {
+        Ok(value) => {
+            tracing::warn!("⚠️  Repaired malformed {} JSON output ({})", provider, error);
+            Ok(value)
+        }
+        Err(_) => Err(error),
+    }
+}
+
+/// Best-effort repair of malformed JSON text
+///
+/// Drops text before the value (a `{` or `[` at the start of a line) and after
+/// it, and closes strings, objects, and arrays left open by truncated output.
+/// Returns `None` unless the result parses as JSON; a truncated value may be
+/// missing its last fields.
+pub fn repair_json(input: &str) -> Option<String> {
+    input
+        .split('\n')
+        .scan(0, |offset, line| {
+            let start = *offset;
+            *offset += line.len() + 1;
+            let indent = line.len() - line.trim_start().len();
+            Some(line[indent..].starts_with(['{', '[']).then_some(start + indent))
+        })
+        .flatten()
+        .take(MAX_REPAIR_ATTEMPTS)
+        .find_map(|start| {
+            let repaired = repair_from(&input[start..])?;
+            serde_json::from_str::<IgnoredAny>(&repaired)
+                .is_ok()
+                .then_some(repaired)
+        })
+}
+
+/// Cut `text` after the value it starts with, or close it if it is truncated
+fn repair_from(text: &str) -> Option<String> {
+    let mut closers = Vec::new();
+    let mut in_string = false;
+    let mut string_is_key = false;
+    let mut escaped = false;
+    let mut last_token = ' ';
+
+    for (i, c) in text.char_indices() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+                last_token = '"';
+            }
+            continue;
+        }
+
+        match c {
+            '"' => {
+                in_string = true;
+                string_is_key = closers.last() == Some(&'}') && matches!(last_token, '{' | ',');
+            }
+            '{' => closers.push('}'),
+            '[' => closers.push(']'),
+            '}' | ']' => {
+                if closers.pop() != Some(c) {
+                    return None;
+                }
+                if closers.is_empty() {
+                    // Anything after the value is a trailing log line
+                    return Some(text[..=i].to_string());
+                }
+            }
+            _ => {}
+        }
+        if !c.is_whitespace() {
+            last_token = c;
+        }
+    }
+
+    // Truncated: close whatever is still open
+    let mut repaired = text.to_string();
+    if in_string {
+        if escaped {
+            repaired.pop();
+        }
+        repaired.push('"');
+        if string_is_key {
+            repaired.push_str(":null");
+        }
+    } else {
+        repaired.truncate(repaired.trim_end_matches(|c: char| c.is_whitespace() || c == ',').len());
+        if repaired.ends_with(':') {
+            repaired.push_str("null");
+        } else if string_is_key && repaired.ends_with('"') {
+            repaired.push_str(":null");
+        }
+    }
+    repaired.extend(closers.iter().rev());
+    Some(repaired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn repaired(input: &str) -> Value {
+        let text = repair_json(input).unwrap_or_else(|| panic!("could not repair {:?}", input));
+        serde_json::from_str(&text).unwrap()
+    }
+
+    #[test]
+    fn repairs_recoverable_output() {
+        let cases = [
+            // Log lines around the value
+            ("Loaded cached credentials.\n{\"result\": \"hi\"}", json!({"result": "hi"})),
+            ("{\"result\": \"hi\"}\nFlushing log events", json!({"result": "hi"})),
+            // Cut off mid-value, mid-key, after a colon, and after a comma
+            ("{\"result\": \"half an ans", json!({"result": "half an ans"})),
+            ("{\"result\": \"hi\", \"sess", json!({"result": "hi", "sess": null})),
+            ("{\"result\": \"hi\", \"usage\":", json!({"result": "hi", "usage": null})),
+            ("{\"models\": [1, 2,", json!({"models": [1, 2]})),
+            // Cut off inside an escape, and with nested objects left open
+            ("{\"result\": \"a \\", json!({"result": "a "})),
+            ("{\"stats\": {\"models\": {\"pro\": {\"tokens\": 5", json!({"stats": {"models": {"pro": {"tokens": 5}}}})),
+            // Braces inside strings don't count
+            ("{\"result\": \"} { ]\"}\ntrailer", json!({"result": "} { ]"})),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(repaired(input), expected, "input: {:?}", input);
+        }
+    }
+
+    #[test]
+    fn skips_a_log_line_that_looks_like_json() {
+        assert_eq!(
+            repaired("[warn] cache miss\n{\"result\": \"hi\"}"),
+            json!({"result": "hi"})
+        );
+    }
+
+    #[test]
+    fn leaves_unrecoverable_output_alone() {
+        assert_eq!(repair_json("Error: not signed in"), None);
+        assert_eq!(repair_json("{\"result\": [1, 2}"), None);
+    }
+
+    #[test]
+    fn lenient_parse_reports_the_original_error() {
+        let error = from_slice_lenient::<Value>(b"Error: not signed in", "test").unwrap_err();
+        assert!(error.is_syntax());
+
+        // Well-formed JSON of the wrong shape is not repaired
+        let error = from_slice_lenient::<Vec<u32>>(b"{\"result\": 1}", "test").unwrap_err();
+        assert!(error.is_data());
+
+        let value: Value = from_slice_lenient(b"{\"result\": \"hi\"}\ndone", "test").unwrap();
+        assert_eq!(value, json!({"result": "hi"}));
+    }
+}
@@ -0,0 +1,128 @@
+//! Warm CLI processes spawned ahead of the requests they serve
+//!
+//! None of the bundled CLIs can answer several independent requests from one
+//! process, but a CLI that reads its prompt from stdin can be started early:
+//! it loads its runtime and configuration, then waits. A pool keeps a few such
+//! processes per exact command line and working directory, each serving one
+//! request, and replaces every one it hands out. Processes idle for longer than
+//! the idle timeout are killed. Only providers reporting
+//! `supports_process_pool` use a pool.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use tokio::process::Child;
+
+/// Default time a warm process may wait for a request before it is killed
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// A started CLI waiting on stdin
+#[derive(Debug)]
+pub struct WarmProcess {
+    pub child: Child,
+    spawned_at: Instant,
+}
+
+impl WarmProcess {
+    pub fn new(child: Child) -> Self {
+        Self {
+            child,
+            spawned_at: Instant::now(),
+        }
+    }
+
+    /// Still waiting, rather than exited or past `idle_timeout`
+    fn is_usable(&mut self, idle_timeout: Duration) -> bool {
+        self.spawned_at.elapsed() < idle_timeout && matches!(self.child.try_wait(), Ok(None))
+    }
+}
+
+/// Idle warm processes, keyed by everything that went into spawning them
+#[derive(Debug)]
+pub struct ProcessPool {
+    size: usize,
+    idle_timeout: Duration,
+    idle: Mutex<HashMap<String, Vec<WarmProcess>>>,
+}
+
+impl ProcessPool {
+    pub fn new(size: usize, idle_timeout: Duration) -> Arc<Self> {
+        Arc::new(Self {
+            size: size.max(1),
+            idle_timeout,
+            idle: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    /// Take a usable warm process for `key`, killing any that went stale
+    pub fn take(&self, key: &str) -> Option<WarmProcess> {
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        let processes = idle.get_mut(key)?;
+        // Dropping a `WarmProcess` kills its child (kill_on_drop)
+        while let Some(mut process) = processes.pop() {
+            if process.is_usable(self.idle_timeout) {
+                return Some(process);
+            }
+        }
+        idle.remove(key);
+        None
+    }
+
+    /// Whether `key` has fewer than `size` warm processes
+    pub fn has_room(&self, key: &str) -> bool {
+        let idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        idle.get(key).is_none_or(|processes| processes.len() < self.size)
+    }
+
+    /// Keep `process` for a later request with the same `key`
+    ///
+    /// Dropped, killing it, when the pool for `key` is already full.
+    pub fn put(self: &Arc<Self>, key: String, process: WarmProcess) {
+        {
+            let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+            let processes = idle.entry(key).or_default();
+            if processes.len() >= self.size {
+                return;
+            }
+            processes.push(process);
+        }
+
+        // Kill it if nothing takes it in time
+        let pool: Weak<Self> = Arc::downgrade(self);
+        let idle_timeout = self.idle_timeout;
+        tokio::spawn(async move {
+            tokio::time::sleep(idle_timeout).await;
+            if let Some(pool) = pool.upgrade() {
+                pool.reap();
+            }
+        });
+    }
+
+    /// Kill every warm process past the idle timeout or no longer running
+    pub fn reap(&self) {
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        for processes in idle.values_mut() {
+            processes.retain_mut(|process| process.is_usable(self.idle_timeout));
+        }
+        idle.retain(|_, processes| !processes.is_empty());
+    }
+
+    /// Warm processes currently waiting
+    pub fn idle_count(&self) -> usize {
+        self.idle
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .map(Vec::len)
+            .sum()
+    }
+}
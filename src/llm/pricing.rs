@@ -0,0 +1,102 @@
+//! Model pricing table for estimating costs the CLIs don't report
+//!
+//! Rates are USD per million tokens. The built-in table covers common models
+//! at list price; use `set_price` to plug in negotiated rates or new models.
+
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+use super::types::TokenUsage;
+
+/// Per-million-token rates for a model
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelPrice {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+    pub cache_read_per_million: f64,
+    pub cache_write_per_million: f64,
+}
+
+impl ModelPrice {
+    pub const fn new(input: f64, output: f64, cache_read: f64, cache_write: f64) -> Self {
+        Self {
+            input_per_million: input,
+            output_per_million: output,
+            cache_read_per_million: cache_read,
+            cache_write_per_million: cache_write,
+        }
+    }
+}
+
+/// List prices keyed by model name prefix
+const DEFAULT_PRICES: &[(&str, ModelPrice)] = &[
+    // Claude
+    ("claude-opus-4-5", ModelPrice::new(5.0, 25.0, 0.50, 6.25)),
+    ("claude-opus-4", ModelPrice::new(15.0, 75.0, 1.50, 18.75)),
+    ("claude-sonnet-4", ModelPrice::new(3.0, 15.0, 0.30, 3.75)),
+    ("claude-haiku-4", ModelPrice::new(1.0, 5.0, 0.10, 1.25)),
+    ("claude-3-5-haiku", ModelPrice::new(0.80, 4.0, 0.08, 1.0)),
+    // Gemini (prompts up to 200k tokens)
+    ("gemini-2.5-pro", ModelPrice::new(1.25, 10.0, 0.31, 0.0)),
+    ("gemini-2.5-flash-lite", ModelPrice::new(0.10, 0.40, 0.025, 0.0)),
+    ("gemini-2.5-flash", ModelPrice::new(0.30, 2.50, 0.075, 0.0)),
+    ("gemini-2.0-flash", ModelPrice::new(0.10, 0.40, 0.025, 0.0)),
+    // OpenAI
+    ("gpt-5-mini", ModelPrice::new(0.25, 2.0, 0.025, 0.0)),
+    ("gpt-5-nano", ModelPrice::new(0.05, 0.40, 0.005, 0.0)),
+    ("gpt-5", ModelPrice::new(1.25, 10.0, 0.125, 0.0)),
+];
+
+fn table() -> &'static RwLock<HashMap<String, ModelPrice>> {
+    static TABLE: OnceLock<RwLock<HashMap<String, ModelPrice>>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let prices = DEFAULT_PRICES
+            .iter()
+            .map(|(model, price)| (model.to_string(), *price))
+            .collect();
+        RwLock::new(prices)
+    })
+}
+
+/// Add or replace the price for a model (or model name prefix)
+pub fn set_price(model: impl Into<String>, price: ModelPrice) {
+    table()
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(model.into(), price);
+}
+
+/// Look up the price for a model
+///
+/// An exact match wins; otherwise the longest matching prefix is used, so
+/// dated names like `claude-sonnet-4-5-20250929` resolve to `claude-sonnet-4`.
+pub fn price_for(model: &str) -> Option<ModelPrice> {
+    let table = table().read().unwrap_or_else(|e| e.into_inner());
+
+    if let Some(price) = table.get(model) {
+        return Some(*price);
+    }
+
+    table
+        .iter()
+        .filter(|(prefix, _)| model.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, price)| *price)
+}
+
+/// Estimate the USD cost of a response from its token usage
+///
+/// `usage.input` must exclude cache reads (callers whose CLI reports an
+/// inclusive prompt count should subtract `cache_read` first). Thinking
+/// tokens are billed at the output rate. Returns `None` for unknown models.
+pub fn estimate_cost(model: &str, usage: &TokenUsage) -> Option<f64> {
+    let price = price_for(model)?;
+    let output = usage.output.saturating_add(usage.extended_thinking.unwrap_or(0));
+
+    let cost = usage.input as f64 * price.input_per_million
+        + output as f64 * price.output_per_million
+        + usage.cache_read as f64 * price.cache_read_per_million
+        + usage.cache_creation as f64 * price.cache_write_per_million;
+
+    Some(cost / 1_000_000.0)
+}
@@ -0,0 +1,575 @@
+//! Shared subprocess runner for CLI-backed providers
+
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Output, Stdio};
+use std::sync::OnceLock;
+use tempfile::TempDir;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{timeout, Duration};
+
+use super::attachments;
+use super::types::LlmRequest;
+use super::redact::redact;
+use crate::error::LlmError;
+
+/// Directory a CLI runs in for one request
+///
+/// A caller-supplied `working_dir` is used as-is. The request's `temp_dir` is
+/// created if missing and left in place, since whoever chose it (the server,
+/// for sessions) owns its lifetime. Without either, a fresh directory named
+/// after `temp_prefix` is created under the system temp dir and removed when
+/// the `RunDir` is dropped, error paths included. Attachments are copied into
+/// temp directories.
+#[derive(Debug)]
+pub struct RunDir {
+    path: PathBuf,
+    /// Removes the directory on drop when this run created it
+    _temp: Option<TempDir>,
+}
+
+impl RunDir {
+    pub fn prepare(request: &LlmRequest, temp_prefix: &str) -> Result<Self, LlmError> {
+        if let Some(ref dir) = request.working_dir {
+            return Ok(Self {
+                path: dir.clone(),
+                _temp: None,
+            });
+        }
+
+        let run_dir = match request.temp_dir {
+            Some(ref dir) => {
+                std::fs::create_dir_all(dir).map_err(|source| LlmError::TempDirFailed {
+                    operation: "create temp directory".to_string(),
+                    path: dir.clone(),
+                    source,
+                })?;
+                Self {
+                    path: dir.clone(),
+                    _temp: None,
+                }
+            }
+            None => {
+                let temp = tempfile::Builder::new()
+                    .prefix(temp_prefix)
+                    .tempdir()
+                    .map_err(|source| LlmError::TempDirFailed {
+                        operation: "create temp directory in".to_string(),
+                        path: std::env::temp_dir(),
+                        source,
+                    })?;
+                Self {
+                    path: temp.path().to_path_buf(),
+                    _temp: Some(temp),
+                }
+            }
+        };
+        attachments::copy_into(request.attachments.as_deref().unwrap_or(&[]), &run_dir.path)?;
+
+        Ok(run_dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Default cap on what one CLI run may write to stdout: 32 MiB
+pub const DEFAULT_MAX_OUTPUT_BYTES: usize = 32 * 1024 * 1024;
+
+/// Stdout cap for the request's CLI: its `max_output_bytes`, or the default
+///
+/// Guards against a runaway CLI exhausting memory; stderr is truncated at the
+/// same size instead.
+pub fn max_output_bytes(request: &LlmRequest) -> usize {
+    request.max_output_bytes.unwrap_or(DEFAULT_MAX_OUTPUT_BYTES).max(1)
+}
+
+fn output_too_large(provider: &str, limit: usize) -> LlmError {
+    LlmError::OutputTooLarge {
+        provider: provider.to_string(),
+        limit_bytes: limit,
+    }
+}
+
+/// Set once by `abort_all`; every running CLI watches it
+fn abort_signal() -> &'static watch::Sender<bool> {
+    static ABORT: OnceLock<watch::Sender<bool>> = OnceLock::new();
+    ABORT.get_or_init(|| watch::channel(false).0)
+}
+
+/// Kill every running CLI subprocess and refuse to start new ones
+///
+/// Used during shutdown once in-flight delegations have had their grace period.
+/// Affected calls fail with `ProviderUnavailable`.
+pub fn abort_all() {
+    abort_signal().send_replace(true);
+}
+
+fn aborted_error(provider: &str) -> LlmError {
+    LlmError::ProviderUnavailable {
+        provider: provider.to_string(),
+        reason: "server is shutting down".to_string(),
+    }
+}
+
+/// Run a CLI command to completion with a timeout
+///
+/// Stdin gets `stdin` (or is closed) and stdout/stderr are captured. Stderr is read line by line
+/// as it arrives and, when `log_stderr` is set, each line is emitted at debug
+/// level so operators can watch long delegations progress. The full stderr is
+/// still returned for error classification. On timeout or `abort_all` the
+/// child is killed, as it is once stdout passes `max_output` bytes.
+pub async fn run_cli(
+    cmd: Command,
+    timeout_secs: u64,
+    provider: &str,
+    log_stderr: bool,
+    stdin: Option<&str>,
+    max_output: usize,
+) -> Result<Output, LlmError> {
+    let child = spawn_cli(cmd, provider, stdin.is_some())?;
+    run_child(child, timeout_secs, provider, log_stderr, stdin, max_output).await
+}
+
+/// Spawn a CLI with its output piped, for `run_child` to finish later
+///
+/// With `pipe_stdin` the child waits for input, which is how pooled processes
+/// start up ahead of the request they serve. The child is killed if dropped.
+pub fn spawn_cli(mut cmd: Command, provider: &str, pipe_stdin: bool) -> Result<Child, LlmError> {
+    configure_stdio(&mut cmd, pipe_stdin);
+
+    if *abort_signal().subscribe().borrow() {
+        return Err(aborted_error(provider));
+    }
+
+    cmd.spawn().map_err(LlmError::Io)
+}
+
+/// Feed `stdin` to a child from `spawn_cli` and collect its output, as `run_cli` does
+pub async fn run_child(
+    mut child: Child,
+    timeout_secs: u64,
+    provider: &str,
+    log_stderr: bool,
+    stdin: Option<&str>,
+    max_output: usize,
+) -> Result<Output, LlmError> {
+    let mut aborted = abort_signal().subscribe();
+    if *aborted.borrow() {
+        return Err(aborted_error(provider));
+    }
+
+    let child_stdin = child.stdin.take();
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+
+    // Outlives `run` so whatever arrived before a timeout can be reported
+    let mut stdout_buf = Vec::new();
+    let limit = max_output;
+
+    let run = async {
+        // `read_buf` keeps every completed read in the buffer even if this is cancelled.
+        // Reading one byte past the limit is enough to know it was passed.
+        let read_stdout = async {
+            let mut bounded = (&mut stdout).take(limit as u64 + 1);
+            while bounded.read_buf(&mut stdout_buf).await? > 0 {}
+            if stdout_buf.len() > limit {
+                // Ends the run, killing the child; reported as `OutputTooLarge` below
+                return Err(std::io::Error::other("output limit exceeded"));
+            }
+            Ok::<_, std::io::Error>(())
+        };
+
+        // Feed stdin and drain both output pipes concurrently so none can fill up and block the child
+        let (_, (), stderr) = tokio::try_join!(
+            write_stdin(child_stdin, stdin.unwrap_or_default()),
+            read_stdout,
+            read_stderr(stderr, provider, log_stderr, limit)
+        )?;
+        let status = child.wait().await?;
+
+        Ok::<_, std::io::Error>((status, stderr))
+    };
+
+    // Dropping the child on timeout or abort kills it (kill_on_drop)
+    let result = tokio::select! {
+        result = timeout(Duration::from_secs(timeout_secs), run) => result,
+        _ = aborted.wait_for(|aborted| *aborted) => return Err(aborted_error(provider)),
+    };
+
+    match result {
+        Ok(Ok((status, stderr))) => Ok(Output {
+            status,
+            stdout: stdout_buf,
+            stderr,
+        }),
+        Ok(Err(_)) if stdout_buf.len() > limit => Err(output_too_large(provider, limit)),
+        Ok(Err(e)) => Err(LlmError::Io(e)),
+        Err(_) => Err(LlmError::Timeout {
+            seconds: timeout_secs,
+            partial_content: (!stdout_buf.is_empty()).then(|| decode_output(&stdout_buf, provider, "partial stdout")),
+        }),
+    }
+}
+
+/// A running CLI whose stdout is consumed one line at a time
+///
+/// Used for streaming output modes. Stderr is drained on a background task
+/// (logged the same way as `run_cli`). The child is killed if this is dropped
+/// before `finish`, on `abort_all`, or once its stdout passes `max_output` bytes.
+pub struct CliLines {
+    child: Child,
+    stdout: BufReader<ChildStdout>,
+    stderr: JoinHandle<std::io::Result<Vec<u8>>>,
+    provider: String,
+    aborted: watch::Receiver<bool>,
+    /// Stdout bytes read so far, checked against `limit`
+    read_bytes: usize,
+    limit: usize,
+}
+
+impl CliLines {
+    /// Spawn the command with stdout available for line-by-line reads
+    ///
+    /// `stdin` is written on a background task while the caller reads stdout.
+    pub fn spawn(
+        mut cmd: Command,
+        provider: &str,
+        log_stderr: bool,
+        stdin: Option<String>,
+        max_output: usize,
+    ) -> Result<Self, LlmError> {
+        configure_stdio(&mut cmd, stdin.is_some());
+
+        let aborted = abort_signal().subscribe();
+        if *aborted.borrow() {
+            return Err(aborted_error(provider));
+        }
+
+        let mut child = cmd.spawn().map_err(LlmError::Io)?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+
+        if let Some(input) = stdin {
+            let child_stdin = child.stdin.take();
+            let provider = provider.to_string();
+            tokio::spawn(async move {
+                if let Err(e) = write_stdin(child_stdin, &input).await {
+                    tracing::warn!(provider, "⚠️  Failed to write stdin context: {}", e);
+                }
+            });
+        }
+
+        let stderr = tokio::spawn({
+            let provider = provider.to_string();
+            async move { read_stderr(stderr, &provider, log_stderr, max_output).await }
+        });
+
+        Ok(Self {
+            child,
+            stdout: BufReader::new(stdout),
+            stderr,
+            provider: provider.to_string(),
+            aborted,
+            read_bytes: 0,
+            limit: max_output,
+        })
+    }
+
+    /// Read the next stdout line, or `None` at end of output
+    ///
+    /// Never buffers more than the remaining limit, so a CLI writing without
+    /// newlines is stopped as soon as it passes the cap.
+    pub async fn next_line(&mut self) -> Result<Option<String>, LlmError> {
+        let mut line = Vec::new();
+        let remaining = self.limit.saturating_sub(self.read_bytes);
+        let mut bounded = (&mut self.stdout).take(remaining as u64 + 1);
+        let read = tokio::select! {
+            read = bounded.read_until(b'\n', &mut line) => read.map_err(LlmError::Io)?,
+            _ = self.aborted.wait_for(|aborted| *aborted) => {
+                let _ = self.child.start_kill();
+                return Err(aborted_error(&self.provider));
+            }
+        };
+
+        self.read_bytes += read;
+        if self.read_bytes > self.limit {
+            let _ = self.child.start_kill();
+            return Err(output_too_large(&self.provider, self.limit));
+        }
+        if read == 0 {
+            return Ok(None);
+        }
+
+        if line.ends_with(b"\n") {
+            line.pop();
+            if line.ends_with(b"\r") {
+                line.pop();
+            }
+        }
+        String::from_utf8(line)
+            .map(Some)
+            .map_err(|e| LlmError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))
+    }
+
+    /// Wait for the process to exit and collect its stderr
+    pub async fn finish(mut self) -> Result<(ExitStatus, Vec<u8>), LlmError> {
+        let status = self.child.wait().await.map_err(LlmError::Io)?;
+        let stderr = self
+            .stderr
+            .await
+            .map_err(|e| LlmError::Io(std::io::Error::other(e)))?
+            .map_err(LlmError::Io)?;
+        Ok((status, stderr))
+    }
+}
+
+/// Decode CLI output, keeping it exact when it is valid UTF-8
+///
+/// Invalid bytes are replaced with U+FFFD only as a last resort, with a
+/// warning, so a confusing parse failure can be traced back to the encoding.
+pub fn decode_output(bytes: &[u8], provider: &str, stream: &str) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(e) => {
+            tracing::warn!(
+                provider,
+                "⚠️  {} is not valid UTF-8 (first bad byte at offset {}); invalid bytes replaced",
+                stream,
+                e.valid_up_to()
+            );
+            String::from_utf8_lossy(bytes).into_owned()
+        }
+    }
+}
+
+/// How a failed CLI ended: its exit code, or the signal that killed it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CliExit {
+    /// Exit code, or -1 when the process was killed by a signal
+    pub code: i32,
+    /// Signal that terminated the process (Unix only)
+    pub signal: Option<i32>,
+}
+
+impl CliExit {
+    pub fn from_status(status: &ExitStatus) -> Self {
+        #[cfg(unix)]
+        let signal = std::os::unix::process::ExitStatusExt::signal(status);
+        #[cfg(not(unix))]
+        let signal = None;
+
+        Self {
+            code: status.code().unwrap_or(-1),
+            signal,
+        }
+    }
+
+    /// A failure the CLI reported with `code` rather than through its exit status
+    pub fn code(code: i32) -> Self {
+        Self { code, signal: None }
+    }
+}
+
+/// Parent variables kept even in a cleared environment; no CLI starts without them
+const ALWAYS_INHERITED_ENV: &[&str] = &["PATH", "HOME"];
+
+/// Apply the request's environment: clear it down to the allowlist if one is
+/// set, then add the request's own variables
+pub fn apply_env(cmd: &mut Command, request: &LlmRequest) {
+    if let Some(ref allowlist) = request.env_allowlist {
+        cmd.env_clear();
+        let names = ALWAYS_INHERITED_ENV.iter().copied().chain(allowlist.iter().map(String::as_str));
+        for name in names {
+            if let Some(value) = std::env::var_os(name) {
+                cmd.env(name, value);
+            }
+        }
+    }
+
+    if let Some(ref env) = request.env {
+        cmd.envs(env);
+    }
+}
+
+/// Apply a request's `binary` override and append its `extra_args`
+///
+/// Providers call this last when building their argument vector, so dry runs
+/// show the same command line that runs.
+pub fn apply_overrides(args: &mut Vec<String>, request: &LlmRequest) {
+    if let Some(ref binary) = request.binary {
+        args[0] = binary.display().to_string();
+    }
+    if let Some(ref extra) = request.extra_args {
+        args.extend(extra.iter().cloned());
+    }
+}
+
+/// Log the argument vector a CLI is about to run with, at debug level
+///
+/// The prompt and system prompts are replaced with their length wherever
+/// they appear, so flag combinations can be diagnosed without leaking
+/// prompt contents; anything left still goes through `redact`.
+pub fn log_command(args: &[String], request: &LlmRequest, provider: &str) {
+    if !tracing::enabled!(tracing::Level::DEBUG) {
+        return;
+    }
+
+    let hidden = [
+        ("prompt", Some(request.prompt.as_str())),
+        ("system prompt", request.system_prompt.as_deref()),
+        ("appended system prompt", request.append_system_prompt.as_deref()),
+    ];
+    let shown: Vec<String> = args
+        .iter()
+        .map(|arg| {
+            let masked = hidden
+                .iter()
+                .filter_map(|(label, text)| text.filter(|text| !text.is_empty()).map(|text| (label, text)))
+                .fold(arg.clone(), |arg, (label, text)| {
+                    arg.replace(text, &format!("<{}: {} chars>", label, text.chars().count()))
+                });
+            redact(&masked).into_owned()
+        })
+        .collect();
+
+    tracing::debug!(provider, "🔧 Running {:?}", shown);
+}
+
+/// Pipe stdin if there is input for it (closing it otherwise), capture
+/// stdout/stderr, and make sure the child never outlives us
+fn configure_stdio(cmd: &mut Command, pipe_stdin: bool) {
+    cmd.stdin(if pipe_stdin { Stdio::piped() } else { Stdio::null() });
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+    cmd.kill_on_drop(true);
+}
+
+/// Write `input` to the child's stdin, then close it so the child sees end of input
+///
+/// Must run alongside the output readers: a child that writes a lot before
+/// reading all of its input would otherwise block on a full stdout while we
+/// block on a full stdin. A child that exits without reading everything is
+/// not an error here; its exit status says whether it failed.
+async fn write_stdin(stdin: Option<ChildStdin>, input: &str) -> std::io::Result<()> {
+    let Some(mut stdin) = stdin else {
+        return Ok(());
+    };
+
+    match stdin.write_all(input.as_bytes()).await {
+        Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Read stderr to the end, optionally logging each line as it arrives
+///
+/// Only the first `limit` bytes are kept, and a logged line is cut off at the
+/// same size; the rest is still drained, a chunk at a time, so the child never
+/// blocks on a full pipe.
+async fn read_stderr(
+    stderr: impl AsyncRead + Unpin,
+    provider: &str,
+    log_stderr: bool,
+    limit: usize,
+) -> std::io::Result<Vec<u8>> {
+    let log_line = |line: &[u8]| {
+        tracing::debug!(
+            provider,
+            "stderr: {}",
+            redact(String::from_utf8_lossy(line).trim_end())
+        );
+    };
+
+    let mut reader = BufReader::new(stderr);
+    let mut buf = Vec::new();
+    let mut line = Vec::new();
+
+    loop {
+        let chunk = reader.fill_buf().await?;
+        if chunk.is_empty() {
+            break;
+        }
+
+        let room = limit.saturating_sub(buf.len());
+        buf.extend_from_slice(&chunk[..chunk.len().min(room)]);
+        if log_stderr {
+            for piece in chunk.split_inclusive(|&b| b == b'\n') {
+                let room = limit.saturating_sub(line.len());
+                line.extend_from_slice(&piece[..piece.len().min(room)]);
+                if piece.ends_with(b"\n") {
+                    log_line(&line);
+                    line.clear();
+                }
+            }
+        }
+
+        let consumed = chunk.len();
+        reader.consume(consumed);
+    }
+    if !line.is_empty() {
+        log_line(&line);
+    }
+
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_output_keeps_valid_utf8_exact() {
+        assert_eq!(decode_output("naïve \u{1F600}".as_bytes(), "test", "stdout"), "naïve \u{1F600}");
+    }
+
+    #[test]
+    fn decode_output_replaces_only_invalid_bytes() {
+        assert_eq!(decode_output(b"ok \xff\xfe done", "test", "stdout"), "ok \u{FFFD}\u{FFFD} done");
+    }
+
+    /// A CLI writing endless output with no newline in it
+    #[cfg(unix)]
+    fn endless_output() -> Command {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "exec cat /dev/zero"]);
+        cmd
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn run_cli_stops_output_past_the_limit() {
+        let result = run_cli(endless_output(), 10, "test", false, None, 4096).await;
+        assert!(
+            matches!(result, Err(LlmError::OutputTooLarge { limit_bytes: 4096, .. })),
+            "{:?}",
+            result.map(|output| output.stdout.len())
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn cli_lines_stops_a_line_past_the_limit() {
+        let mut lines = CliLines::spawn(endless_output(), "test", false, None, 4096).unwrap();
+        let result = timeout(Duration::from_secs(10), lines.next_line()).await.expect("read is bounded");
+        assert!(
+            matches!(result, Err(LlmError::OutputTooLarge { limit_bytes: 4096, .. })),
+            "{:?}",
+            result
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn cli_lines_counts_every_line_against_the_limit() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "printf 'first\\r\\nsecond\\n'"]);
+
+        let mut lines = CliLines::spawn(cmd, "test", false, None, 12).unwrap();
+        assert_eq!(lines.next_line().await.unwrap().as_deref(), Some("first"));
+        assert!(matches!(lines.next_line().await, Err(LlmError::OutputTooLarge { .. })));
+    }
+}
@@ -1,24 +1,163 @@
 use async_trait::async_trait;
+use futures::stream::{self, BoxStream, StreamExt};
+use regex::Regex;
+use serde::Serialize;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
 
-use super::types::{LlmRequest, LlmResponse};
+use super::messages::{self, Message};
+use super::types::{LlmRequest, LlmResponse, StreamEvent};
 use crate::error::LlmError;
 
 /// Provider availability status
 #[derive(Debug, Clone)]
 pub enum ProviderAvailability {
-    Available,
+    /// `version` is what the CLI's `--version` reported, if it could be read
+    Available { version: Option<String> },
+    /// Reachable, but failing often enough that a healthy provider should be preferred
+    Degraded { reason: String },
     Unavailable { reason: String },
 }
 
+/// The version number in a CLI's `--version` output, e.g. `2.0.14` from `2.0.14 (Claude Code)`
+pub fn parse_version(output: &[u8]) -> Option<String> {
+    static VERSION: OnceLock<Regex> = OnceLock::new();
+
+    let pattern = VERSION.get_or_init(|| {
+        Regex::new(r"\d+\.\d+(?:\.\d+)?(?:-[0-9A-Za-z.\-]+)?").expect("version pattern is valid")
+    });
+    pattern
+        .find(&String::from_utf8_lossy(output))
+        .map(|m| m.as_str().to_string())
+}
+
+/// Optional features a provider's CLI supports
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProviderCapabilities {
+    /// Resumes earlier conversations by `session_id`
+    pub supports_sessions: bool,
+    /// Honors `fallback_model` when the primary model is overloaded
+    pub supports_fallback: bool,
+    /// Can read `attachments`
+    pub supports_attachments: bool,
+    /// Accepts a sampling `temperature`
+    pub supports_temperature: bool,
+    /// Accepts an extended-thinking `thinking_budget`
+    pub supports_thinking_budget: bool,
+    /// Reports the actual cost of a call rather than leaving Praxio to estimate it
+    pub reports_cost: bool,
+    /// Can start CLIs ahead of requests and keep them waiting (see `pool`)
+    pub supports_process_pool: bool,
+    /// Context window of the default model, in tokens, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_context: Option<u64>,
+}
+
 /// Core abstraction for LLM providers
 #[async_trait]
 pub trait LlmProvider: Send + Sync {
     /// Invoke the LLM with a request
     async fn invoke(&self, request: LlmRequest) -> Result<LlmResponse, LlmError>;
 
+    /// Invoke the LLM and stream events as output arrives
+    ///
+    /// Providers whose CLI can't stream fall back to this default, which runs
+    /// `invoke` and yields a single `Completed` event.
+    fn invoke_stream(&self, request: LlmRequest) -> BoxStream<'_, Result<StreamEvent, LlmError>> {
+        stream::once(async move {
+            self.invoke(request)
+                .await
+                .map(|response| StreamEvent::Completed {
+                    response: Box::new(response),
+                })
+        })
+        .boxed()
+    }
+
+    /// Program and arguments this provider would run for the request
+    ///
+    /// Used for dry runs; CLI providers build their `Command` from the same list.
+    fn command_args(&self, request: &LlmRequest) -> Vec<String>;
+
+    /// System prompt and prompt carrying a validated conversation
+    ///
+    /// The bundled CLIs take one prompt, so the default writes earlier turns
+    /// out with role markers (`messages::flatten`).
+    fn render_messages(&self, messages: &[Message]) -> (Option<String>, String) {
+        messages::flatten(messages)
+    }
+
+    /// What the CLI supports
+    ///
+    /// The server rejects requests that use an unsupported option rather than
+    /// silently ignoring it. Defaults to no optional features.
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities::default()
+    }
+
+    /// Timeout applied to requests that don't set one, in seconds, if the provider has one
+    fn default_timeout_seconds(&self) -> Option<u64> {
+        None
+    }
+
     /// Check if this provider is available and ready to use
     async fn check_availability(&self) -> ProviderAvailability;
 
+    /// Models the CLI reports, or `None` if it has no way to enumerate them
+    async fn list_models(&self) -> Result<Option<Vec<String>>, LlmError> {
+        Ok(None)
+    }
+
     /// Get the provider name
     fn name(&self) -> &str;
 }
+
+/// Caches the result of `check_availability` so polling it stays cheap
+#[derive(Debug, Default)]
+pub struct AvailabilityCache {
+    entry: RwLock<Option<(ProviderAvailability, Instant)>>,
+}
+
+impl AvailabilityCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the cached availability if younger than `ttl`, otherwise re-check
+    ///
+    /// `force` bypasses the cache and always runs a fresh check.
+    pub async fn get_or_check(
+        &self,
+        provider: &dyn LlmProvider,
+        ttl: Duration,
+        force: bool,
+    ) -> ProviderAvailability {
+        if !force {
+            if let Some((ref availability, checked_at)) = *self.entry.read().await {
+                if checked_at.elapsed() < ttl {
+                    return availability.clone();
+                }
+            }
+        }
+
+        let availability = provider.check_availability().await;
+        *self.entry.write().await = Some((availability.clone(), Instant::now()));
+        availability
+    }
+
+    /// Whether the last check found the provider available, without re-checking
+    ///
+    /// `false` if it has never been checked.
+    pub async fn last_available(&self) -> bool {
+        matches!(*self.entry.read().await, Some((ProviderAvailability::Available { .. }, _)))
+    }
+
+    /// CLI version found by the last check, without re-checking
+    pub async fn version(&self) -> Option<String> {
+        match *self.entry.read().await {
+            Some((ProviderAvailability::Available { ref version }, _)) => version.clone(),
+            _ => None,
+        }
+    }
+}
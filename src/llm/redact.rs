@@ -0,0 +1,110 @@
+//! Masking of secrets in CLI output before it reaches logs or clients
+//!
+//! CLI stderr can echo API keys, bearer tokens, or environment assignments.
+//! Error messages built from it flow back to the MCP client, so every pattern
+//! match is replaced with `[REDACTED]`. The built-in patterns can be extended
+//! with `add_pattern` or a file passed to `load_patterns`.
+
+use std::borrow::Cow;
+use std::path::Path;
+use std::sync::{OnceLock, RwLock};
+
+use regex::Regex;
+
+use crate::error::LlmError;
+
+/// Replacement text for a masked secret
+const MASK: &str = "[REDACTED]";
+
+/// Built-in secret patterns
+///
+/// A pattern with a capture group named `secret` masks only that group, so
+/// `GEMINI_API_KEY=...` keeps its variable name.
+const DEFAULT_PATTERNS: &[&str] = &[
+    // OpenAI / Anthropic style keys (sk-..., sk-ant-...)
+    r"\bsk-[A-Za-z0-9_\-]{16,}",
+    // Google API keys
+    r"\bAIza[0-9A-Za-z_\-]{35}",
+    // Authorization headers
+    r"(?i)\bbearer\s+(?P<secret>[A-Za-z0-9._~+/\-]+=*)",
+    // KEY=value / TOKEN: value assignments
+    r#"(?i)\b[A-Z0-9_]*(?:API_KEY|TOKEN|SECRET|PASSWORD)\b["']?\s*[=:]\s*["']?(?P<secret>[^\s"']+)"#,
+];
+
+fn patterns() -> &'static RwLock<Vec<Regex>> {
+    static PATTERNS: OnceLock<RwLock<Vec<Regex>>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        RwLock::new(
+            DEFAULT_PATTERNS
+                .iter()
+                .map(|pattern| Regex::new(pattern).expect("built-in redaction pattern is valid"))
+                .collect(),
+        )
+    })
+}
+
+fn compile(pattern: &str) -> Result<Regex, LlmError> {
+    Regex::new(pattern).map_err(|e| LlmError::InvalidRequest {
+        message: format!("invalid redaction pattern '{}': {}", pattern, e),
+    })
+}
+
+/// Add a regex to the patterns applied by `redact`
+pub fn add_pattern(pattern: &str) -> Result<(), LlmError> {
+    let regex = compile(pattern)?;
+    patterns().write().unwrap_or_else(|e| e.into_inner()).push(regex);
+    Ok(())
+}
+
+/// Replace the pattern list, dropping the built-in patterns
+pub fn set_patterns<S: AsRef<str>>(list: &[S]) -> Result<(), LlmError> {
+    let compiled = list
+        .iter()
+        .map(|pattern| compile(pattern.as_ref()))
+        .collect::<Result<Vec<_>, _>>()?;
+    *patterns().write().unwrap_or_else(|e| e.into_inner()) = compiled;
+    Ok(())
+}
+
+/// Add the patterns in a file, one regex per line, to the built-in ones
+///
+/// Blank lines and lines starting with `#` are ignored. Nothing is added if
+/// any pattern fails to compile.
+pub fn load_patterns(path: &Path) -> Result<usize, LlmError> {
+    let compiled = std::fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(compile)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let count = compiled.len();
+    patterns().write().unwrap_or_else(|e| e.into_inner()).extend(compiled);
+    Ok(count)
+}
+
+/// Mask every secret in `text`
+pub fn redact(text: &str) -> Cow<'_, str> {
+    let patterns = patterns().read().unwrap_or_else(|e| e.into_inner());
+    let mut out = Cow::Borrowed(text);
+
+    for regex in patterns.iter() {
+        if !regex.is_match(&out) {
+            continue;
+        }
+        let replaced = regex
+            .replace_all(&out, |caps: &regex::Captures| match caps.name("secret") {
+                Some(secret) => {
+                    let whole = caps.get(0).expect("group 0 always matches");
+                    let start = secret.start() - whole.start();
+                    let end = secret.end() - whole.start();
+                    format!("{}{}{}", &whole.as_str()[..start], MASK, &whole.as_str()[end..])
+                }
+                None => MASK.to_string(),
+            })
+            .into_owned();
+        out = Cow::Owned(replaced);
+    }
+
+    out
+}
@@ -1,11 +1,67 @@
 use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::HashMap;
 use std::path::PathBuf;
 
+use super::attachments;
+use crate::error::LlmError;
+
+/// Characters rejected in `extra_args` and `binary`
+const SHELL_METACHARACTERS: &[char] = &[';', '&', '|', '$', '`', '<', '>', '\n', '\r', '\0'];
+
+thread_local! {
+    /// Set while `to_value_compact` serializes a compact response
+    static COMPACT: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Serialize a response, leaving out its heavy detail when `compact` is set
+///
+/// Compact output drops `all_models_used`, `model_breakdown`, the cache token
+/// counts, and the `uuid` and `service_tier` metadata; everything else is kept.
+pub fn to_value_compact<T: Serialize>(value: &T, compact: bool) -> serde_json::Result<serde_json::Value> {
+    let previous = COMPACT.with(|flag| flag.replace(compact));
+    let result = serde_json::to_value(value);
+    COMPACT.with(|flag| flag.set(previous));
+    result
+}
+
+/// `skip_serializing_if` for the fields compact output leaves out
+fn is_compact<T>(_: &T) -> bool {
+    COMPACT.with(Cell::get)
+}
+
+/// `skip_serializing_if` for optional fields compact output leaves out
+fn is_none_or_compact<T>(value: &Option<T>) -> bool {
+    value.is_none() || is_compact(value)
+}
+
+/// Write `api_duration_ms` under both its name and its former one, `duration_ms`
+fn serialize_api_duration<S: serde::Serializer>(ms: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+    use serde::ser::SerializeMap;
+
+    let mut map = serializer.serialize_map(Some(2))?;
+    map.serialize_entry("api_duration_ms", ms)?;
+    map.serialize_entry("duration_ms", ms)?;
+    map.end()
+}
+
 /// Request to invoke an LLM
 #[derive(Debug, Clone)]
 pub struct LlmRequest {
     pub prompt: String,
     pub system_prompt: Option<String>,
+
+    /// File whose contents become the system prompt, read at invoke time
+    /// Mutually exclusive with `system_prompt`
+    pub system_prompt_file: Option<PathBuf>,
+
+    /// Instructions added on top of the CLI's default system prompt (Claude only)
+    /// Unlike `system_prompt`, which replaces the default; both may be set
+    pub append_system_prompt: Option<String>,
+
+    /// Leave out the server's base system prompt (the shared preamble) for this request
+    pub skip_base_system_prompt: bool,
+
     pub model: Option<String>,
     pub output_format: OutputFormat,
     pub max_tokens: Option<u32>,
@@ -14,16 +70,453 @@ pub struct LlmRequest {
     /// When provided, the LLM will have context from previous calls in that session
     pub session_id: Option<String>,
 
+    /// Continue the most recent conversation in the run directory (Claude `--continue`)
+    /// Ignored when `session_id` is set
+    pub continue_last: bool,
+
     /// Temp directory where Claude CLI should execute
     /// Used for session isolation - each session has its own directory
     pub temp_dir: Option<PathBuf>,
 
+    /// Directory the CLI should work in instead of `temp_dir`
+    /// Lets the delegate see a real project; unlike `temp_dir` it is never deleted
+    pub working_dir: Option<PathBuf>,
+
     /// Fallback model if primary is overloaded (Claude only)
     pub fallback_model: Option<String>,
 
     /// Timeout in seconds for this specific request
     /// Overrides provider default if specified
     pub timeout_seconds: Option<u64>,
+
+    /// Auto-approve every action the delegated agent takes (Claude only)
+    ///
+    /// Passes `--dangerously-skip-permissions`, so file edits and shell commands
+    /// run without any permission check. That's convenient inside a throwaway
+    /// temp dir but risky with `working_dir` pointing at a real project. When
+    /// off, the CLI's normal permission rules apply; `--print` mode can't prompt,
+    /// so actions needing approval are refused unless `allowed_tools` covers them.
+    pub skip_permissions: bool,
+
+    /// Tools the delegated agent may use, e.g. `Read` or `Bash(git log:*)` (Claude only)
+    /// Setting this also stops Praxio from skipping permission checks
+    pub allowed_tools: Option<Vec<String>>,
+
+    /// Tools the delegated agent may not use (Claude only)
+    pub disallowed_tools: Option<Vec<String>>,
+
+    /// Extended-thinking token budget (Claude only)
+    /// Passed to the CLI as `MAX_THINKING_TOKENS`
+    pub thinking_budget: Option<u32>,
+
+    /// Sampling temperature (0.0 to 2.0)
+    /// Only honored by providers whose CLI exposes it; others reject the request
+    pub temperature: Option<f32>,
+
+    /// Files to make available to the model
+    /// Copied into the working directory and referenced from the prompt
+    pub attachments: Option<Vec<PathBuf>>,
+
+    /// Extra context written to the CLI's stdin, e.g. a log file or a diff
+    /// The prompt is still passed as an argument and should say how to use it
+    pub stdin_context: Option<String>,
+
+    /// Include the CLI's unparsed stdout in the response, for debugging
+    pub return_raw: bool,
+
+    /// Return the command line the provider would run instead of running it
+    pub dry_run: bool,
+
+    /// JSON Schema the response `content` must conform to
+    /// Adds answer-format instructions to the system prompt; a reply that
+    /// doesn't validate is a `ParseError`
+    pub response_schema: Option<serde_json::Value>,
+
+    /// Send the prompt even if its estimated size exceeds the model's context window
+    pub skip_context_check: bool,
+
+    /// Correlation ID for logs and `metadata.request_id`; the server generates one if unset
+    pub request_id: Option<String>,
+
+    /// Extra environment variables for the CLI, e.g. `ANTHROPIC_BASE_URL`
+    /// Applied on top of the inherited (or allowlisted) environment
+    pub env: Option<HashMap<String, String>>,
+
+    /// When set, the CLI starts with a cleared environment holding only these
+    /// parent variables (plus `PATH` and `HOME`, which every CLI needs) and `env`
+    pub env_allowlist: Option<Vec<String>>,
+
+    /// Arguments appended verbatim after the structured flags
+    ///
+    /// An escape hatch for CLI options Praxio doesn't model. Nothing checks
+    /// them against the flags Praxio sets, so a conflicting or duplicated flag
+    /// is up to the CLI to resolve.
+    pub extra_args: Option<Vec<String>>,
+
+    /// Program to run instead of the provider's usual CLI
+    /// Refused unless the server allows binary overrides
+    pub binary: Option<PathBuf>,
+
+    /// Stop the CLI and fail with `OutputTooLarge` once its stdout passes this many bytes
+    /// The server fills in its configured limit when unset
+    pub max_output_bytes: Option<usize>,
+}
+
+impl LlmRequest {
+    /// Start building a request for `prompt`; the recommended way to make one
+    pub fn builder(prompt: impl Into<String>) -> LlmRequestBuilder {
+        LlmRequestBuilder {
+            request: LlmRequest {
+                prompt: prompt.into(),
+                system_prompt: None,
+                system_prompt_file: None,
+                append_system_prompt: None,
+                skip_base_system_prompt: false,
+                model: None,
+                output_format: OutputFormat::Json,
+                max_tokens: None,
+                session_id: None,
+                continue_last: false,
+                temp_dir: None,
+                working_dir: None,
+                fallback_model: None,
+                timeout_seconds: None,
+                skip_permissions: false,
+                allowed_tools: None,
+                disallowed_tools: None,
+                thinking_budget: None,
+                temperature: None,
+                attachments: None,
+                stdin_context: None,
+                return_raw: false,
+                dry_run: false,
+                response_schema: None,
+                skip_context_check: false,
+                request_id: None,
+                env: None,
+                env_allowlist: None,
+                extra_args: None,
+                binary: None,
+                max_output_bytes: None,
+            },
+        }
+    }
+
+    /// Replace `system_prompt_file` with the file's contents in `system_prompt`
+    pub fn load_system_prompt_file(&mut self) -> Result<(), LlmError> {
+        let Some(path) = self.system_prompt_file.take() else {
+            return Ok(());
+        };
+
+        if self.system_prompt.is_some() {
+            return Err(LlmError::InvalidRequest {
+                message: "set either system_prompt or system_prompt_file, not both".to_string(),
+            });
+        }
+
+        self.system_prompt = Some(std::fs::read_to_string(&path)?);
+        Ok(())
+    }
+
+    /// Check request parameters that don't depend on the provider
+    pub fn validate(&self) -> Result<(), LlmError> {
+        if self.prompt.trim().is_empty() {
+            return Err(LlmError::InvalidRequest {
+                message: "prompt must not be empty".to_string(),
+            });
+        }
+
+        if let Some(temperature) = self.temperature {
+            if !(0.0..=2.0).contains(&temperature) {
+                return Err(LlmError::InvalidRequest {
+                    message: format!("temperature must be between 0.0 and 2.0, got {}", temperature),
+                });
+            }
+        }
+
+        if let Some(ref dir) = self.working_dir {
+            if !dir.is_dir() {
+                return Err(LlmError::InvalidRequest {
+                    message: format!("working_dir {} is not an existing directory", dir.display()),
+                });
+            }
+        }
+
+        if let Some(ref attachments) = self.attachments {
+            attachments::validate(attachments)?;
+        }
+
+        // Model names become a single CLI argument; one starting with '-' would be read as a flag
+        for model in self.model.iter().chain(&self.fallback_model) {
+            if model.is_empty()
+                || model.starts_with('-')
+                || model.contains(SHELL_METACHARACTERS)
+                || model.chars().any(|c| c.is_whitespace() || c.is_control())
+            {
+                return Err(LlmError::InvalidRequest {
+                    message: format!("'{}' is not a valid model name", model),
+                });
+            }
+        }
+
+        // CLIs are spawned without a shell, so these are only suspicious, not
+        // dangerous; they still almost always mean a command line pasted whole
+        let binary = self.binary.as_ref().map(|path| path.to_string_lossy());
+        let extra_args = self.extra_args.iter().flatten().map(|arg| std::borrow::Cow::from(arg.as_str()));
+        if let Some(arg) = binary.into_iter().chain(extra_args).find(|arg| arg.contains(SHELL_METACHARACTERS)) {
+            return Err(LlmError::InvalidRequest {
+                message: format!(
+                    "'{}' contains a shell metacharacter; extra_args and binary are passed as-is without a shell",
+                    arg
+                ),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Rough token count of the prompt, system prompts, and stdin context
+    ///
+    /// Uses ~4 characters per token, which is close for English prose and
+    /// tends to undercount code; good enough to catch prompts that can't fit.
+    pub fn estimated_prompt_tokens(&self) -> u64 {
+        let chars: usize = [
+            Some(self.prompt.as_str()),
+            self.system_prompt.as_deref(),
+            self.append_system_prompt.as_deref(),
+            self.stdin_context.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|text| text.chars().count())
+        .sum();
+        (chars as u64).div_ceil(4)
+    }
+
+    /// Reject prompts longer than `max_chars` characters
+    ///
+    /// Only the prompt counts: it is passed as a command-line argument, while
+    /// `stdin_context` has no such limit.
+    pub fn check_prompt_length(&self, max_chars: usize) -> Result<(), LlmError> {
+        let chars = self.prompt.chars().count();
+        if chars > max_chars {
+            return Err(LlmError::InvalidRequest {
+                message: format!(
+                    "prompt is {} characters, over the limit of {}",
+                    chars, max_chars
+                ),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds an `LlmRequest` without naming every field
+///
+/// Unset options keep their defaults: no system prompt, session, or
+/// overrides, JSON output, and permission checks left on. Fields added to
+/// `LlmRequest` later get a default here too, so code using the builder keeps
+/// compiling.
+#[derive(Debug, Clone)]
+pub struct LlmRequestBuilder {
+    request: LlmRequest,
+}
+
+impl LlmRequestBuilder {
+    /// Apply `set` to `value` if there is one, for options the caller may leave out
+    ///
+    /// `builder.with_optional(model, LlmRequestBuilder::with_model)`
+    pub fn with_optional<T>(self, value: Option<T>, set: impl FnOnce(Self, T) -> Self) -> Self {
+        match value {
+            Some(value) => set(self, value),
+            None => self,
+        }
+    }
+
+    /// Replace the CLI's default system prompt
+    pub fn with_system_prompt(mut self, value: impl Into<String>) -> Self {
+        self.request.system_prompt = Some(value.into());
+        self
+    }
+
+    /// Read the system prompt from a file at invoke time
+    pub fn with_system_prompt_file(mut self, value: impl Into<PathBuf>) -> Self {
+        self.request.system_prompt_file = Some(value.into());
+        self
+    }
+
+    /// Add instructions on top of the CLI's default system prompt (Claude only)
+    pub fn with_append_system_prompt(mut self, value: impl Into<String>) -> Self {
+        self.request.append_system_prompt = Some(value.into());
+        self
+    }
+
+    /// Leave out the server's base system prompt
+    pub fn with_skip_base_system_prompt(mut self, value: bool) -> Self {
+        self.request.skip_base_system_prompt = value;
+        self
+    }
+
+    /// Model name or alias
+    pub fn with_model(mut self, value: impl Into<String>) -> Self {
+        self.request.model = Some(value.into());
+        self
+    }
+
+    /// Output format (default `Json`)
+    pub fn with_output_format(mut self, value: OutputFormat) -> Self {
+        self.request.output_format = value;
+        self
+    }
+
+    /// Cap on output tokens
+    pub fn with_max_tokens(mut self, value: u32) -> Self {
+        self.request.max_tokens = Some(value);
+        self
+    }
+
+    /// Resume this session
+    pub fn with_session_id(mut self, value: impl Into<String>) -> Self {
+        self.request.session_id = Some(value.into());
+        self
+    }
+
+    /// Continue the most recent conversation in the run directory (Claude only)
+    pub fn with_continue_last(mut self, value: bool) -> Self {
+        self.request.continue_last = value;
+        self
+    }
+
+    /// Run in this directory, created if missing and never deleted
+    pub fn with_temp_dir(mut self, value: impl Into<PathBuf>) -> Self {
+        self.request.temp_dir = Some(value.into());
+        self
+    }
+
+    /// Run in an existing project directory
+    pub fn with_working_dir(mut self, value: impl Into<PathBuf>) -> Self {
+        self.request.working_dir = Some(value.into());
+        self
+    }
+
+    /// Model to fall back to when the primary is overloaded (Claude only)
+    pub fn with_fallback_model(mut self, value: impl Into<String>) -> Self {
+        self.request.fallback_model = Some(value.into());
+        self
+    }
+
+    /// Timeout for this request, in place of the provider's default
+    pub fn with_timeout_seconds(mut self, value: u64) -> Self {
+        self.request.timeout_seconds = Some(value);
+        self
+    }
+
+    /// Auto-approve every action the delegate takes (default `false`); see `LlmRequest::skip_permissions`
+    pub fn with_skip_permissions(mut self, value: bool) -> Self {
+        self.request.skip_permissions = value;
+        self
+    }
+
+    /// Tools the delegate may use (Claude only)
+    pub fn with_allowed_tools(mut self, value: Vec<String>) -> Self {
+        self.request.allowed_tools = Some(value);
+        self
+    }
+
+    /// Tools the delegate may not use (Claude only)
+    pub fn with_disallowed_tools(mut self, value: Vec<String>) -> Self {
+        self.request.disallowed_tools = Some(value);
+        self
+    }
+
+    /// Extended-thinking token budget (Claude only)
+    pub fn with_thinking_budget(mut self, value: u32) -> Self {
+        self.request.thinking_budget = Some(value);
+        self
+    }
+
+    /// Sampling temperature (0.0 to 2.0)
+    pub fn with_temperature(mut self, value: f32) -> Self {
+        self.request.temperature = Some(value);
+        self
+    }
+
+    /// Files to make available to the model
+    pub fn with_attachments(mut self, value: Vec<PathBuf>) -> Self {
+        self.request.attachments = Some(value);
+        self
+    }
+
+    /// Extra context written to the CLI's stdin
+    pub fn with_stdin_context(mut self, value: impl Into<String>) -> Self {
+        self.request.stdin_context = Some(value.into());
+        self
+    }
+
+    /// Include the CLI's unparsed stdout in the response
+    pub fn with_return_raw(mut self, value: bool) -> Self {
+        self.request.return_raw = value;
+        self
+    }
+
+    /// Return the command line instead of running it
+    pub fn with_dry_run(mut self, value: bool) -> Self {
+        self.request.dry_run = value;
+        self
+    }
+
+    /// JSON Schema the response content must conform to
+    pub fn with_response_schema(mut self, value: serde_json::Value) -> Self {
+        self.request.response_schema = Some(value);
+        self
+    }
+
+    /// Send the prompt even if it looks too big for the context window
+    pub fn with_skip_context_check(mut self, value: bool) -> Self {
+        self.request.skip_context_check = value;
+        self
+    }
+
+    /// Correlation ID for logs and `metadata.request_id`
+    pub fn with_request_id(mut self, value: impl Into<String>) -> Self {
+        self.request.request_id = Some(value.into());
+        self
+    }
+
+    /// Extra environment variables for the CLI
+    pub fn with_env(mut self, value: HashMap<String, String>) -> Self {
+        self.request.env = Some(value);
+        self
+    }
+
+    /// Start the CLI with a cleared environment holding only these parent variables
+    pub fn with_env_allowlist(mut self, value: Vec<String>) -> Self {
+        self.request.env_allowlist = Some(value);
+        self
+    }
+
+    /// Arguments appended verbatim after the structured flags
+    pub fn with_extra_args(mut self, value: Vec<String>) -> Self {
+        self.request.extra_args = Some(value);
+        self
+    }
+
+    /// Program to run instead of the provider's usual CLI
+    pub fn with_binary(mut self, value: impl Into<PathBuf>) -> Self {
+        self.request.binary = Some(value.into());
+        self
+    }
+
+    /// Cap on the CLI's stdout, in bytes
+    pub fn with_max_output_bytes(mut self, value: usize) -> Self {
+        self.request.max_output_bytes = Some(value);
+        self
+    }
+
+    pub fn build(self) -> LlmRequest {
+        self.request
+    }
 }
 
 /// Output format for LLM response
@@ -31,6 +524,22 @@ pub struct LlmRequest {
 pub enum OutputFormat {
     Text,
     Json,
+    /// Newline-delimited JSON events emitted while the model generates
+    StreamJson,
+}
+
+/// Incremental event from a streaming invocation
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    /// The CLI started; carries the session ID when the provider reports it early
+    Started { session_id: Option<String> },
+
+    /// A chunk of generated content
+    Delta { text: String },
+
+    /// Final response with full token and cost metadata
+    Completed { response: Box<LlmResponse> },
 }
 
 /// Unified response from any LLM provider
@@ -43,6 +552,7 @@ pub struct LlmResponse {
     pub primary_model: String,
 
     /// All models involved in generating the response
+    #[serde(skip_serializing_if = "is_compact")]
     pub all_models_used: Vec<String>,
 
     /// Provider name (claude, gemini)
@@ -51,41 +561,88 @@ pub struct LlmResponse {
     /// Token usage breakdown
     pub tokens: Option<TokenUsage>,
 
-    /// Duration in milliseconds
-    pub duration_ms: u64,
+    /// Duration in milliseconds as reported by the provider (API time where
+    /// the CLI reports it, otherwise the CLI's own run time). Also serialized
+    /// under its former name, `duration_ms`, for existing callers.
+    #[serde(flatten, serialize_with = "serialize_api_duration")]
+    pub api_duration_ms: u64,
+
+    /// Wall-clock milliseconds around the whole delegation, including CLI
+    /// startup and retries; set by the tool handlers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_duration_ms: Option<u64>,
 
-    /// Cost in USD (only available from Claude)
+    /// Cost in USD (reported by Claude, estimated for other providers)
     pub cost_usd: Option<f64>,
 
     /// Per-model breakdown (only from Claude)
+    #[serde(skip_serializing_if = "is_compact")]
     pub model_breakdown: Option<Vec<ModelBreakdown>>,
 
     /// Provider-specific metadata
     pub metadata: LlmResponseMetadata,
+
+    /// Unparsed CLI output, when the request set `return_raw`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw: Option<String>,
+}
+
+impl LlmResponse {
+    /// Response carrying just `content`, for custom providers to fill in further
+    ///
+    /// Models, tokens, cost, and metadata start out empty.
+    pub fn new(provider: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            content: content.into(),
+            primary_model: String::new(),
+            all_models_used: Vec::new(),
+            provider: provider.into(),
+            tokens: None,
+            api_duration_ms: 0,
+            total_duration_ms: None,
+            cost_usd: None,
+            model_breakdown: None,
+            metadata: LlmResponseMetadata::default(),
+            raw: None,
+        }
+    }
+
+    /// Response for a dry run: the would-be command line instead of model output
+    pub fn dry_run(provider: &str, command: Vec<String>) -> Self {
+        Self {
+            metadata: LlmResponseMetadata {
+                command: Some(command.clone()),
+                ..Default::default()
+            },
+            ..Self::new(provider, command.join(" "))
+        }
+    }
 }
 
 /// Token usage statistics
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TokenUsage {
-    pub input: u32,
-    pub output: u32,
-    pub total: u32,
-    pub cache_creation: u32,
-    pub cache_read: u32,
+    pub input: u64,
+    pub output: u64,
+    pub total: u64,
+    #[serde(default, skip_serializing_if = "is_compact")]
+    pub cache_creation: u64,
+    #[serde(default, skip_serializing_if = "is_compact")]
+    pub cache_read: u64,
 
-    /// Extended thinking tokens (Gemini only)
+    /// Extended thinking tokens, when the provider reports them separately
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub extended_thinking: Option<u32>,
+    pub extended_thinking: Option<u64>,
 }
 
 /// Per-model token and cost breakdown (Claude only)
 #[derive(Debug, Clone, Serialize)]
 pub struct ModelBreakdown {
     pub model: String,
-    pub input_tokens: u32,
-    pub output_tokens: u32,
-    pub cache_read_tokens: u32,
-    pub cache_creation_tokens: u32,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_creation_tokens: u64,
     pub cost_usd: f64,
     pub context_window: u32,
 }
@@ -98,7 +655,7 @@ pub struct LlmResponseMetadata {
     pub session_id: Option<String>,
 
     /// UUID (Claude)
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "is_none_or_compact")]
     pub uuid: Option<String>,
 
     /// Number of turns (Claude)
@@ -106,7 +663,7 @@ pub struct LlmResponseMetadata {
     pub num_turns: Option<u32>,
 
     /// Service tier (Claude)
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "is_none_or_compact")]
     pub service_tier: Option<String>,
 
     /// API errors count (Gemini)
@@ -116,4 +673,108 @@ pub struct LlmResponseMetadata {
     /// Total tool calls (Gemini)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_calls: Option<u32>,
+
+    /// Whether `cost_usd` was reported by the CLI or estimated from token counts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_source: Option<CostSource>,
+
+    /// Program and arguments that would have run (dry runs only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<Vec<String>>,
+
+    /// Budget left in the session after this call (sessions created with a budget)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget_remaining_usd: Option<f64>,
+
+    /// Served from the response cache rather than a fresh CLI run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cached: Option<bool>,
+
+    /// Shared from an identical request that was already running rather than a run of its own
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deduplicated: Option<bool>,
+
+    /// The call cost more than the server's cost warning threshold
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_warning: Option<bool>,
+
+    /// Why generation stopped, as reported by the CLI (Claude, OpenAI)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<String>,
+
+    /// The content was cut off (token or turn limit, failed turn); resume the
+    /// session to get the rest
+    pub truncated: bool,
+
+    /// Correlation ID of the tool call, also attached to its log lines
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+
+    /// Session whose history was summarized into this one (`max_history_turns`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_session_id: Option<String>,
+
+    /// Actions the CLI refused because permission checks were on, e.g.
+    /// `Bash(git push)` (Claude); explains a task left unfinished
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub permission_denials: Option<Vec<String>>,
+
+    /// Lines the model added to files during the call (Gemini)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lines_added: Option<u32>,
+
+    /// Lines the model removed from files during the call (Gemini)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lines_removed: Option<u32>,
+
+    /// Version of the CLI that produced the response, from its last availability check
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider_version: Option<String>,
+}
+
+/// Origin of a response's `cost_usd`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CostSource {
+    /// Reported by the provider CLI (Claude)
+    Reported,
+    /// Computed from token usage and the pricing table
+    Estimated,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn response_serializes_duration_under_both_names() {
+        let mut response = LlmResponse::new("claude", "hi");
+        response.api_duration_ms = 1234;
+
+        for compact in [false, true] {
+            let value = to_value_compact(&response, compact).unwrap();
+            assert_eq!(value["api_duration_ms"], 1234);
+            assert_eq!(value["duration_ms"], 1234);
+        }
+    }
+
+    fn invalid_request_message(request: &LlmRequest) -> String {
+        match request.validate() {
+            Err(LlmError::InvalidRequest { message }) => message,
+            other => panic!("expected InvalidRequest, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn validate_rejects_empty_and_whitespace_prompts() {
+        for prompt in ["", " ", "\n\t  \r\n"] {
+            let request = LlmRequest::builder(prompt).build();
+            assert_eq!(invalid_request_message(&request), "prompt must not be empty", "prompt: {:?}", prompt);
+        }
+    }
+
+    #[test]
+    fn validate_accepts_a_prompt_with_surrounding_whitespace() {
+        assert!(LlmRequest::builder("  What is 2+2?\n").build().validate().is_ok());
+    }
 }
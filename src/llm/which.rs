@@ -0,0 +1,117 @@
+//! Cross-platform executable lookup
+//!
+//! A portable replacement for shelling out to `which`, which doesn't exist on
+//! Windows. Scans `PATH` and, on Windows, tries each `PATHEXT` extension so that
+//! npm-style shims like `claude.cmd` are found.
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+/// Find an executable by name on the current `PATH`
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+
+    if cfg!(windows) {
+        let pathext = std::env::var_os("PATHEXT")
+            .unwrap_or_else(|| ".COM;.EXE;.BAT;.CMD".into());
+        find_executable_in(name, &path, Some(&pathext))
+    } else {
+        find_executable_in(name, &path, None)
+    }
+}
+
+/// Find an executable by name in an explicit `PATH`-style list
+///
+/// When `pathext` is given (Windows), each `;`-separated extension is tried in
+/// turn and the bare name is not considered executable.
+pub fn find_executable_in(name: &str, path: &OsStr, pathext: Option<&OsStr>) -> Option<PathBuf> {
+    let extensions: Vec<String> = match pathext {
+        Some(exts) => exts
+            .to_string_lossy()
+            .split(';')
+            .filter(|ext| !ext.is_empty())
+            .map(str::to_string)
+            .collect(),
+        None => vec![String::new()],
+    };
+
+    std::env::split_paths(path).find_map(|dir| {
+        extensions
+            .iter()
+            .map(|ext| dir.join(format!("{}{}", name, ext)))
+            .find(|candidate| is_executable(candidate))
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch(dir: &Path, name: &str, mode: u32) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, "#!/bin/sh\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+        path
+    }
+
+    fn search_path(dirs: &[&Path]) -> std::ffi::OsString {
+        std::env::join_paths(dirs).unwrap()
+    }
+
+    #[test]
+    fn finds_the_first_match_on_path() {
+        let (first, second) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let expected = touch(first.path(), "fake-cli", 0o755);
+        touch(second.path(), "fake-cli", 0o755);
+
+        let path = search_path(&[first.path(), second.path()]);
+        assert_eq!(find_executable_in("fake-cli", &path, None), Some(expected));
+        assert_eq!(find_executable_in("other-cli", &path, None), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn skips_files_without_the_executable_bit() {
+        let (first, second) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        touch(first.path(), "fake-cli", 0o644);
+        let expected = touch(second.path(), "fake-cli", 0o755);
+
+        let path = search_path(&[first.path(), second.path()]);
+        assert_eq!(find_executable_in("fake-cli", &path, None), Some(expected));
+    }
+
+    #[test]
+    fn tries_each_pathext_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "fake-cli", 0o755);
+        let expected = touch(dir.path(), "fake-cli.cmd", 0o755);
+
+        let path = search_path(&[dir.path()]);
+        // The bare name doesn't count once extensions are in play
+        assert_eq!(
+            find_executable_in("fake-cli", &path, Some(OsStr::new(".EXE;;.cmd"))),
+            Some(expected)
+        );
+        assert_eq!(find_executable_in("fake-cli", &path, Some(OsStr::new(".EXE;.BAT"))), None);
+    }
+}
@@ -1,24 +1,182 @@
-use praxio::PraxioServer;
+use clap::Parser;
+use praxio::PraxioServerBuilder;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+/// Default listen address for the http transport
+const DEFAULT_BIND: &str = "127.0.0.1:8080";
+
+/// A running transport; resolves when the client disconnects or the listener fails
+type ServeFuture = Pin<Box<dyn Future<Output = Result<(), Box<dyn std::error::Error>>>>>;
+
+/// Default time in-flight delegations get to finish after SIGTERM/SIGINT
+const DEFAULT_SHUTDOWN_GRACE_SECONDS: u64 = 30;
+
+/// Command-line options
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// Transport to serve MCP over (http needs a build with `--features http`)
+    #[arg(long, value_enum, default_value_t = Transport::Stdio)]
+    transport: Transport,
+
+    /// Address the http transport listens on
+    #[arg(long, value_name = "ADDR", default_value = DEFAULT_BIND)]
+    bind: String,
+
+    /// Serve GET /healthz and /readyz probes on this address
+    #[arg(long, value_name = "ADDR")]
+    health_bind: Option<std::net::SocketAddr>,
+
+    /// Default timeout for Claude requests
+    #[arg(long, value_name = "SECONDS", env = "PRAXIO_CLAUDE_TIMEOUT")]
+    claude_timeout: Option<u64>,
+
+    /// Default timeout for Gemini requests
+    #[arg(long, value_name = "SECONDS", env = "PRAXIO_GEMINI_TIMEOUT")]
+    gemini_timeout: Option<u64>,
+
+    /// Max CLI subprocesses running at once
+    #[arg(long, value_name = "N", env = "PRAXIO_MAX_CONCURRENCY")]
+    max_concurrency: Option<usize>,
+
+    /// error, warn, info, debug, or trace (overrides RUST_LOG) [default: info]
+    #[arg(long, value_name = "LEVEL")]
+    log_level: Option<tracing::Level>,
+
+    /// Time running delegations get to finish on SIGTERM/SIGINT
+    #[arg(long, value_name = "SECONDS", default_value_t = DEFAULT_SHUTDOWN_GRACE_SECONDS)]
+    shutdown_grace_seconds: u64,
+}
+
+/// Transports Praxio can serve MCP over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Transport {
+    Stdio,
+    #[cfg(feature = "http")]
+    Http,
+}
+
+/// Resolve when the process is asked to stop, naming the signal
+async fn shutdown_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        let mut sigterm = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(sigterm) => sigterm,
+            Err(e) => {
+                tracing::warn!("Cannot listen for SIGTERM: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+                return "SIGINT";
+            }
+        };
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => "SIGINT",
+            _ = sigterm.recv() => "SIGTERM",
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        "Ctrl-C"
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
     // Initialize tracing - write to STDERR for MCP compliance (stdout is for JSON-RPC)
+    let filter = match args.log_level {
+        Some(level) => tracing_subscriber::EnvFilter::new(level.to_string()),
+        None => tracing_subscriber::EnvFilter::from_default_env()
+            .add_directive(tracing::Level::INFO.into()),
+    };
     tracing_subscriber::fmt()
         .with_writer(std::io::stderr)  // Critical: logs to stderr, not stdout
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::from_default_env()
-                .add_directive(tracing::Level::INFO.into()),
-        )
+        .with_env_filter(filter)
         .init();
 
     tracing::info!("🚀 Starting Praxio MCP server");
 
     // Create server
-    let server = PraxioServer::new().await;
+    let mut builder = PraxioServerBuilder::new();
+    if let Some(seconds) = args.claude_timeout {
+        builder = builder.with_provider_timeout("claude", Duration::from_secs(seconds));
+    }
+    if let Some(seconds) = args.gemini_timeout {
+        builder = builder.with_provider_timeout("gemini", Duration::from_secs(seconds));
+    }
+    if let Some(max) = args.max_concurrency {
+        builder = builder.with_max_concurrency(max);
+    }
+    let server = builder.build().await;
+
+    // Optional Prometheus endpoint
+    #[cfg(feature = "metrics")]
+    let server = match std::env::var("PRAXIO_METRICS_PORT").ok().and_then(|p| p.parse::<u16>().ok()) {
+        Some(port) => {
+            let metrics = std::sync::Arc::new(praxio::metrics::Metrics::new());
+            let addr = std::net::SocketAddr::from(([127, 0, 0, 1], port));
+            tokio::spawn({
+                let metrics = metrics.clone();
+                async move {
+                    if let Err(e) = metrics.serve(addr).await {
+                        tracing::error!("Metrics endpoint failed: {}", e);
+                    }
+                }
+            });
+            server.with_metrics(metrics)
+        }
+        None => server,
+    };
+
+    // Optional liveness/readiness probes for orchestrators
+    if let Some(addr) = args.health_bind {
+        tokio::spawn({
+            let server = server.clone();
+            async move {
+                if let Err(e) = praxio::health::serve(server, addr).await {
+                    tracing::error!("Health endpoint failed: {}", e);
+                }
+            }
+        });
+    }
+
+    let shutdown_handle = server.clone();
+
+    // Logs stay on stderr for every transport, never in protocol responses
+    let mut run: ServeFuture = match args.transport {
+        #[cfg(feature = "http")]
+        Transport::Http => {
+            tracing::info!("📡 Running on HTTP transport at http://{}/mcp", args.bind);
+            Box::pin(server.serve_http(args.bind.clone()))
+        }
+        Transport::Stdio => {
+            tracing::info!("📡 Running on STDIO transport");
+            Box::pin(server.serve_stdio())
+        }
+    };
+
+    let signal = tokio::select! {
+        result = &mut run => return result,
+        signal = shutdown_signal() => signal,
+    };
 
-    // Run with STDIO transport
-    tracing::info!("📡 Running on STDIO transport");
-    server.run_stdio().await?;
+    // Keep serving in-flight calls while they drain; new ones are refused
+    tracing::info!(
+        "🛑 Received {}, waiting up to {}s for running delegations",
+        signal,
+        args.shutdown_grace_seconds
+    );
+    let drain = shutdown_handle.shutdown(Duration::from_secs(args.shutdown_grace_seconds));
+    tokio::pin!(drain);
+    tokio::select! {
+        _ = &mut drain => {}
+        _ = &mut run => drain.await,
+    }
 
-    Ok(())
+    // The STDIO reader blocks runtime teardown, so exit directly; every child is gone by now
+    std::process::exit(0);
 }
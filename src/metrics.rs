@@ -0,0 +1,196 @@
+//! Prometheus metrics exporter (`metrics` feature)
+//!
+//! Keeps per-provider counters and a duration histogram in memory and serves
+//! them in the Prometheus text exposition format from a minimal HTTP listener,
+//! so the default build doesn't pull in an HTTP stack.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::error::LlmError;
+use crate::llm::LlmResponse;
+
+/// Upper bounds (seconds) of the invocation duration histogram buckets
+const DURATION_BUCKETS: &[f64] = &[0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0];
+
+/// Counters for one provider
+#[derive(Debug, Default)]
+struct ProviderMetrics {
+    invocations: u64,
+    failures: BTreeMap<&'static str, u64>,
+    input_tokens: u64,
+    output_tokens: u64,
+    cost_usd: f64,
+    duration_buckets: Vec<u64>,
+    duration_sum_seconds: f64,
+    duration_count: u64,
+}
+
+/// In-memory metric registry shared by the server and the exporter
+#[derive(Debug, Default)]
+pub struct Metrics {
+    providers: Mutex<BTreeMap<String, ProviderMetrics>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a successful invocation
+    pub fn record_success(&self, provider: &str, response: &LlmResponse) {
+        self.with_provider(provider, |m| {
+            m.invocations += 1;
+            if let Some(ref tokens) = response.tokens {
+                m.input_tokens = m.input_tokens.saturating_add(tokens.input);
+                m.output_tokens = m.output_tokens.saturating_add(tokens.output);
+            }
+            m.cost_usd += response.cost_usd.unwrap_or(0.0);
+
+            let seconds = response.api_duration_ms as f64 / 1000.0;
+            for (bucket, bound) in m.duration_buckets.iter_mut().zip(DURATION_BUCKETS) {
+                if seconds <= *bound {
+                    *bucket += 1;
+                }
+            }
+            m.duration_sum_seconds += seconds;
+            m.duration_count += 1;
+        });
+    }
+
+    /// Record a failed invocation, labelled by error kind
+    pub fn record_failure(&self, provider: &str, error: &LlmError) {
+        self.with_provider(provider, |m| {
+            m.invocations += 1;
+            *m.failures.entry(error.kind()).or_default() += 1;
+        });
+    }
+
+    fn with_provider(&self, provider: &str, update: impl FnOnce(&mut ProviderMetrics)) {
+        let mut providers = self.providers.lock().unwrap_or_else(|e| e.into_inner());
+        let metrics = providers
+            .entry(provider.to_string())
+            .or_insert_with(|| ProviderMetrics {
+                duration_buckets: vec![0; DURATION_BUCKETS.len()],
+                ..Default::default()
+            });
+        update(metrics);
+    }
+
+    /// Render all metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let providers = self.providers.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out = String::new();
+
+        out.push_str("# HELP praxio_invocations_total Delegations attempted, including failures\n");
+        out.push_str("# TYPE praxio_invocations_total counter\n");
+        for (name, m) in providers.iter() {
+            let _ = writeln!(out, "praxio_invocations_total{{provider=\"{}\"}} {}", name, m.invocations);
+        }
+
+        out.push_str("# HELP praxio_failures_total Failed delegations by error kind\n");
+        out.push_str("# TYPE praxio_failures_total counter\n");
+        for (name, m) in providers.iter() {
+            for (kind, count) in &m.failures {
+                let _ = writeln!(
+                    out,
+                    "praxio_failures_total{{provider=\"{}\",error=\"{}\"}} {}",
+                    name, kind, count
+                );
+            }
+        }
+
+        out.push_str("# HELP praxio_tokens_total Tokens consumed by successful delegations\n");
+        out.push_str("# TYPE praxio_tokens_total counter\n");
+        for (name, m) in providers.iter() {
+            let _ = writeln!(
+                out,
+                "praxio_tokens_total{{provider=\"{}\",direction=\"input\"}} {}",
+                name, m.input_tokens
+            );
+            let _ = writeln!(
+                out,
+                "praxio_tokens_total{{provider=\"{}\",direction=\"output\"}} {}",
+                name, m.output_tokens
+            );
+        }
+
+        out.push_str("# HELP praxio_cost_usd_total Reported or estimated cost of successful delegations\n");
+        out.push_str("# TYPE praxio_cost_usd_total counter\n");
+        for (name, m) in providers.iter() {
+            let _ = writeln!(out, "praxio_cost_usd_total{{provider=\"{}\"}} {}", name, m.cost_usd);
+        }
+
+        out.push_str("# HELP praxio_invocation_duration_seconds Duration of successful delegations\n");
+        out.push_str("# TYPE praxio_invocation_duration_seconds histogram\n");
+        for (name, m) in providers.iter() {
+            for (count, bound) in m.duration_buckets.iter().zip(DURATION_BUCKETS) {
+                let _ = writeln!(
+                    out,
+                    "praxio_invocation_duration_seconds_bucket{{provider=\"{}\",le=\"{}\"}} {}",
+                    name, bound, count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "praxio_invocation_duration_seconds_bucket{{provider=\"{}\",le=\"+Inf\"}} {}",
+                name, m.duration_count
+            );
+            let _ = writeln!(
+                out,
+                "praxio_invocation_duration_seconds_sum{{provider=\"{}\"}} {}",
+                name, m.duration_sum_seconds
+            );
+            let _ = writeln!(
+                out,
+                "praxio_invocation_duration_seconds_count{{provider=\"{}\"}} {}",
+                name, m.duration_count
+            );
+        }
+
+        out
+    }
+
+    /// Serve `GET /metrics` on `addr` until the task is dropped
+    pub async fn serve(self: Arc<Self>, addr: SocketAddr) -> std::io::Result<()> {
+        let listener = TcpListener::bind(addr).await?;
+        tracing::info!("📈 Serving metrics on http://{}/metrics", listener.local_addr()?);
+
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let metrics = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = metrics.respond(stream).await {
+                    tracing::debug!("metrics connection error: {}", e);
+                }
+            });
+        }
+    }
+
+    async fn respond(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        // Only the request line matters; scrapers send small GET requests
+        let mut buf = [0u8; 1024];
+        let n = stream.read(&mut buf).await?;
+        let request = String::from_utf8_lossy(&buf[..n]);
+        let path = request.split_whitespace().nth(1).unwrap_or("");
+
+        let (status, body) = if request.starts_with("GET ") && path == "/metrics" {
+            ("200 OK", self.render())
+        } else {
+            ("404 Not Found", "not found\n".to_string())
+        };
+
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await
+    }
+}
@@ -0,0 +1,95 @@
+//! Per-provider request rate limit
+//!
+//! A token bucket holding up to a minute's worth of requests, refilled
+//! continuously. Each delegation takes a token before its CLI starts; when the
+//! bucket is empty it waits its turn for the next one, up to the server's queue
+//! timeout, and then fails with `RateLimited`. Waiters are served in arrival
+//! order. This bounds the request rate, where the concurrency limits bound how
+//! many CLIs run at once.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::error::LlmError;
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+#[derive(Debug)]
+pub struct RateLimiter {
+    provider: String,
+    per_minute: u32,
+    bucket: Mutex<Bucket>,
+    /// Held while waiting for a token, so waiters take turns
+    queue: tokio::sync::Mutex<()>,
+}
+
+impl RateLimiter {
+    /// Allow `per_minute` requests a minute, all of which may come at once
+    pub fn new(provider: impl Into<String>, per_minute: u32) -> Self {
+        let per_minute = per_minute.max(1);
+        Self {
+            provider: provider.into(),
+            per_minute,
+            bucket: Mutex::new(Bucket {
+                tokens: per_minute as f64,
+                refilled_at: Instant::now(),
+            }),
+            queue: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn per_minute(&self) -> u32 {
+        self.per_minute
+    }
+
+    /// Requests that could start right now without waiting
+    pub fn available(&self) -> f64 {
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        self.refill(&mut bucket);
+        bucket.tokens
+    }
+
+    /// Take a token, waiting for one until `deadline`
+    pub async fn acquire(&self, deadline: Instant) -> Result<(), LlmError> {
+        let _turn = tokio::time::timeout_at(deadline, self.queue.lock())
+            .await
+            .map_err(|_| self.exhausted())?;
+
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+                self.refill(&mut bucket);
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return Ok(());
+                }
+                Duration::from_secs_f64((1.0 - bucket.tokens) * 60.0 / self.per_minute as f64)
+            };
+
+            if Instant::now() + wait > deadline {
+                return Err(self.exhausted());
+            }
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    fn refill(&self, bucket: &mut Bucket) {
+        let now = Instant::now();
+        let earned = now.duration_since(bucket.refilled_at).as_secs_f64() * self.per_minute as f64 / 60.0;
+        bucket.tokens = (bucket.tokens + earned).min(self.per_minute as f64);
+        bucket.refilled_at = now;
+    }
+
+    fn exhausted(&self) -> LlmError {
+        LlmError::RateLimited {
+            provider: self.provider.clone(),
+            message: format!("over Praxio's limit of {} requests per minute", self.per_minute),
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! JSON-schema-constrained responses
+//!
+//! A request with a `response_schema` gets instructions to answer with JSON
+//! matching it, and the answer is validated before it is returned.
+
+use jsonschema::Validator;
+
+use crate::error::LlmError;
+
+/// `ParseError::format` of a reply that isn't JSON or doesn't match the schema
+pub const FORMAT: &str = "schema-constrained json";
+
+/// Compile `schema`, rejecting one that isn't a valid JSON Schema
+pub fn compile(schema: &serde_json::Value) -> Result<Validator, LlmError> {
+    jsonschema::validator_for(schema).map_err(|e| LlmError::InvalidRequest {
+        message: format!("response_schema is not a valid JSON Schema: {}", e),
+    })
+}
+
+/// System prompt text telling the model to answer with JSON matching `schema`
+pub fn instructions(schema: &serde_json::Value) -> String {
+    format!(
+        "Respond with a single JSON value that conforms to this JSON Schema, and nothing else: \
+         no prose and no Markdown code fences.\n\nSchema:\n{}",
+        serde_json::to_string_pretty(schema).unwrap_or_else(|_| schema.to_string())
+    )
+}
+
+/// The JSON in `content`, ignoring surrounding whitespace and a Markdown code fence
+pub fn extract_json(content: &str) -> &str {
+    let trimmed = content.trim();
+    let Some(fenced) = trimmed.strip_prefix("```") else {
+        return trimmed;
+    };
+
+    // Drop the info string (```json) and the closing fence
+    let body = fenced.split_once('\n').map_or("", |(_, body)| body);
+    body.trim_end().strip_suffix("```").unwrap_or(body).trim()
+}
+
+/// Whether `error` came from `validate`, as opposed to the CLI's own output failing to parse
+pub fn is_mismatch(error: &LlmError) -> bool {
+    matches!(error.root(), LlmError::ParseError { format, .. } if format == FORMAT)
+}
+
+/// Parse `content` as JSON and check it against `validator`
+///
+/// Either failure is a `ParseError` listing what didn't match.
+pub fn validate(validator: &Validator, content: &str) -> Result<serde_json::Value, LlmError> {
+    let value: serde_json::Value =
+        serde_json::from_str(extract_json(content)).map_err(|e| LlmError::ParseError {
+            format: FORMAT.to_string(),
+            source: Box::new(e),
+            output: None,
+        })?;
+
+    let errors: Vec<String> = validator
+        .iter_errors(&value)
+        .map(|error| {
+            let path = error.instance_path.to_string();
+            if path.is_empty() {
+                error.to_string()
+            } else {
+                format!("{}: {}", path, error)
+            }
+        })
+        .collect();
+    if !errors.is_empty() {
+        return Err(LlmError::ParseError {
+            format: FORMAT.to_string(),
+            source: format!("response does not match the schema: {}", errors.join("; ")).into(),
+            output: None,
+        });
+    }
+
+    Ok(value)
+}
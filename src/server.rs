@@ -1,225 +1,3743 @@
 use turbomcp::prelude::*;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::collections::HashMap;
 use std::path::PathBuf;
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::{RwLock, Semaphore};
+use tempfile::TempDir;
+use tokio_util::sync::CancellationToken;
+use futures::StreamExt;
+use tracing::Instrument;
 
-use crate::llm::{ClaudeProvider, GeminiProvider, LlmProvider, LlmRequest, OutputFormat, ProviderAvailability};
+use crate::audit::AuditLog;
+use crate::cache::ResponseCache;
+use crate::circuit::{self, CircuitBreaker};
+use crate::dedup::{self, Dedup, Flight};
+use crate::error::LlmError;
+use crate::health::HealthReport;
+#[cfg(feature = "metrics")]
+use crate::metrics::Metrics;
+use crate::ratelimit::RateLimiter;
+use crate::schema;
+use crate::template;
+use crate::usage::{UsageCounters, UsageSnapshot};
+use crate::llm::classify;
+use crate::llm::messages::{self, Message};
+use crate::llm::models;
+use crate::llm::pool;
+use crate::llm::process;
+use crate::llm::redact;
+use crate::llm::{AvailabilityCache, ClaudeProvider, GeminiProvider, LlmProvider, LlmResponse, OllamaProvider, OpenAiProvider, LlmRequest, LlmRequestBuilder, OutputFormat, ProviderAvailability, ProviderCapabilities, TokenUsage, to_value_compact};
+
+/// Default maximum number of CLI subprocesses running at once
+const DEFAULT_MAX_CONCURRENCY: usize = 4;
+
+/// Default lifetime of a cached availability check
+const DEFAULT_AVAILABILITY_TTL: Duration = Duration::from_secs(60);
+
+/// How long a model list reported by a CLI is reused
+const MODEL_LIST_TTL: Duration = Duration::from_secs(60);
+
+/// How long killed subprocesses get to release their slots during shutdown
+const SHUTDOWN_KILL_WAIT: Duration = Duration::from_secs(5);
+
+/// Default longest prompt accepted, in characters
+const DEFAULT_MAX_PROMPT_CHARS: usize = 1_000_000;
+
+/// Share of a context window a prompt may fill, leaving room for estimation error
+const DEFAULT_CONTEXT_MARGIN: f64 = 0.9;
+
+/// Default time a request waits for a free slot before being rejected
+const DEFAULT_QUEUE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Asks a session for the summary that seeds its successor once it passes `max_history_turns`
+const HISTORY_SUMMARY_PROMPT: &str = "Summarize our conversation so far for a colleague who will continue it: \
+     the task, decisions made, facts established, and any open questions. \
+     Reply with the summary only.";
+
+/// Prompt `warmup` sends through each provider
+const WARMUP_PROMPT: &str = "Reply with the single word: pong";
+
+/// Default time one warmup call gets, in seconds
+const DEFAULT_WARMUP_TIMEOUT_SECONDS: u64 = 30;
+
+/// Prompt `invoke_until` resumes with when the caller doesn't give one
+const DEFAULT_CONTINUE_PROMPT: &str = "Continue.";
+
+/// Default turn limit for `invoke_until`
+const DEFAULT_UNTIL_MAX_TURNS: u32 = 10;
+
+/// Models reported by a CLI and when they were listed
+type ModelList = (Vec<String>, Instant);
+
+/// A registered provider and the server-side state tracked for it
+#[derive(Clone)]
+struct ProviderEntry {
+    provider: Arc<dyn LlmProvider>,
+    availability: Arc<AvailabilityCache>,
+    usage: Arc<UsageCounters>,
+    circuit: Arc<CircuitBreaker>,
+    /// This provider's own cap on running CLIs, taken before a server-wide permit
+    concurrency: Option<Arc<Semaphore>>,
+    /// This provider's cap on requests per minute, taken before any concurrency permit
+    rate_limit: Option<Arc<RateLimiter>>,
+    /// Last model list the CLI reported, and when
+    models: Arc<RwLock<Option<ModelList>>>,
+}
+
+/// Cancellation tokens of running delegations, by request ID
+///
+/// Calls sharing a request ID (as in `compare_providers`) share a token and
+/// are cancelled together.
+#[derive(Debug, Default)]
+struct InFlight {
+    tokens: std::sync::Mutex<HashMap<String, (CancellationToken, usize)>>,
+}
+
+impl InFlight {
+    /// Track a call until the returned guard is dropped
+    fn register(self: &Arc<Self>, request_id: &str) -> InFlightGuard {
+        let mut tokens = self.tokens.lock().unwrap_or_else(|e| e.into_inner());
+        let (token, calls) = tokens.entry(request_id.to_string()).or_default();
+        *calls += 1;
+        InFlightGuard {
+            in_flight: self.clone(),
+            request_id: request_id.to_string(),
+            token: token.clone(),
+        }
+    }
+
+    /// Cancel every running call with this ID, returning whether there was one
+    fn cancel(&self, request_id: &str) -> bool {
+        let tokens = self.tokens.lock().unwrap_or_else(|e| e.into_inner());
+        match tokens.get(request_id) {
+            Some((token, _)) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+struct InFlightGuard {
+    in_flight: Arc<InFlight>,
+    request_id: String,
+    token: CancellationToken,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        let mut tokens = self.in_flight.tokens.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, calls)) = tokens.get_mut(&self.request_id) {
+            *calls -= 1;
+            if *calls == 0 {
+                tokens.remove(&self.request_id);
+            }
+        }
+    }
+}
+
+/// One prompt and its answer, as kept in a session's transcript
+#[derive(Debug, Clone, serde::Serialize)]
+struct TranscriptTurn {
+    /// When the answer arrived (RFC 3339)
+    at: String,
+    provider: String,
+    model: String,
+    prompt: String,
+    response: String,
+    tokens: Option<TokenUsage>,
+    cost_usd: Option<f64>,
+}
+
+/// Directories a session's CLI runs in, reused when the session is resumed,
+/// plus running totals reported by `session_info`
+#[derive(Debug, Clone)]
+struct SessionEntry {
+    /// Provider the session belongs to
+    provider: String,
+    /// Praxio-managed temp directory, removed once the session is dropped
+    /// and no call is using it
+    temp_dir: Arc<TempDir>,
+    /// Caller's working directory, which takes the place of `temp_dir` when set
+    working_dir: Option<PathBuf>,
+    /// Spending ceiling for the whole session, if any
+    budget_usd: Option<f64>,
+    /// Cost of the session's calls so far
+    spent_usd: f64,
+    /// Number of completed calls, used to estimate the next one
+    calls: u32,
+    /// Conversation turns across all calls, as reported by the CLI
+    turn_count: u32,
+    /// Turns in the CLI's own history, restarted when the history is summarized away
+    history_turns: u32,
+    /// Input tokens across all calls
+    input_tokens: u64,
+    /// Output tokens across all calls
+    output_tokens: u64,
+    /// When the session was created
+    created_at: chrono::DateTime<chrono::Utc>,
+    /// When the session was last created or resumed, for display
+    last_used_at: chrono::DateTime<chrono::Utc>,
+    /// When the session was last created or resumed, for expiry
+    last_used: Instant,
+    /// Every prompt and answer so far, when transcripts are enabled; shared by
+    /// clones of the entry
+    transcript: Option<Arc<std::sync::Mutex<Vec<TranscriptTurn>>>>,
+}
+
+impl SessionEntry {
+    fn new(
+        provider: &str,
+        temp_dir: Arc<TempDir>,
+        working_dir: Option<PathBuf>,
+        budget_usd: Option<f64>,
+    ) -> Self {
+        let now = chrono::Utc::now();
+        Self {
+            provider: provider.to_string(),
+            temp_dir,
+            working_dir,
+            budget_usd,
+            spent_usd: 0.0,
+            calls: 0,
+            turn_count: 0,
+            history_turns: 0,
+            input_tokens: 0,
+            output_tokens: 0,
+            created_at: now,
+            last_used_at: now,
+            last_used: Instant::now(),
+            transcript: None,
+        }
+    }
+
+    /// Entry for a fresh session that carries on from this one
+    ///
+    /// Keeps the running totals, budget and a copy of the transcript, but none
+    /// of the CLI history.
+    fn continued(&self) -> Self {
+        let now = chrono::Utc::now();
+        Self {
+            history_turns: 0,
+            created_at: now,
+            last_used_at: now,
+            last_used: Instant::now(),
+            transcript: self.transcript.as_ref().map(|transcript| {
+                Arc::new(std::sync::Mutex::new(
+                    transcript.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+                ))
+            }),
+            ..self.clone()
+        }
+    }
+
+    /// What `session_info` and the sessions resource report about the session
+    fn report(&self, session_id: &str) -> serde_json::Value {
+        serde_json::json!({
+            "session_id": session_id,
+            "provider": self.provider,
+            "created_at": self.created_at.to_rfc3339(),
+            "last_used_at": self.last_used_at.to_rfc3339(),
+            "calls": self.calls,
+            "turn_count": self.turn_count,
+            "history_turns": self.history_turns,
+            "input_tokens": self.input_tokens,
+            "output_tokens": self.output_tokens,
+            "cost_usd": self.spent_usd,
+            "budget_usd": self.budget_usd,
+            "working_dir": self.working_dir,
+        })
+    }
+
+    /// Refuse a call that is expected to take the session past its budget
+    ///
+    /// The pending call is estimated at the session's average cost so far.
+    fn check_budget(&self) -> Result<(), LlmError> {
+        let Some(budget) = self.budget_usd else {
+            return Ok(());
+        };
+
+        let estimate = if self.calls > 0 {
+            self.spent_usd / self.calls as f64
+        } else {
+            0.0
+        };
+
+        if self.spent_usd + estimate > budget {
+            return Err(LlmError::InvalidRequest {
+                message: format!(
+                    "session budget exhausted: spent ${:.4} of ${:.4}, next call estimated at ${:.4}",
+                    self.spent_usd, budget, estimate
+                ),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Timeout that ends a request by `deadline_unix_ms`, capped at `timeout_seconds`
+///
+/// Fails with `Timeout` when less than a second remains, so no CLI is spawned
+/// for a call that can't finish in time.
+fn timeout_for_deadline(
+    deadline_unix_ms: Option<u64>,
+    timeout_seconds: Option<u64>,
+) -> Result<Option<u64>, LlmError> {
+    let Some(deadline) = deadline_unix_ms else {
+        return Ok(timeout_seconds);
+    };
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    let remaining = deadline.saturating_sub(now) / 1000;
+    if remaining == 0 {
+        return Err(LlmError::Timeout {
+            seconds: 0,
+            partial_content: None,
+        });
+    }
+
+    Ok(Some(timeout_seconds.map_or(remaining, |t| t.min(remaining))))
+}
+
+/// Reject a prompt whose estimated size won't fit the provider's context window
+///
+/// Only `margin` of the window is usable, to allow for the estimate running low
+/// and for the CLI's own system prompt and tool definitions.
+fn check_context_window(
+    name: &str,
+    caps: &ProviderCapabilities,
+    request: &LlmRequest,
+    margin: f64,
+) -> Result<(), LlmError> {
+    let Some(max_context) = caps.max_context else {
+        return Ok(());
+    };
+    if request.skip_context_check {
+        return Ok(());
+    }
+
+    let estimate = request.estimated_prompt_tokens();
+    let limit = (max_context as f64 * margin) as u64;
+    tracing::debug!("Prompt estimated at {} tokens; {} limit is {}", estimate, name, limit);
+
+    if estimate > limit {
+        return Err(LlmError::InvalidRequest {
+            message: format!(
+                "prompt is an estimated {} tokens, over the {} token limit for {} ({:.0}% of its {} token context window); \
+                 shorten it or set skip_context_check",
+                estimate,
+                limit,
+                name,
+                margin * 100.0,
+                max_context
+            ),
+        });
+    }
+
+    Ok(())
+}
+
+/// Apply a bundled provider's configured stderr rules and default timeout
+///
+/// The rules go ahead of the built-in ones and keep the file's order, so the
+/// first rule listed is checked first. `timeout` is in seconds.
+fn configure_bundled<P>(
+    provider: P,
+    rules: Vec<classify::StderrRule>,
+    with_rule: fn(P, classify::StderrRule) -> P,
+    timeout: Option<u64>,
+    with_timeout: fn(P, u64) -> P,
+) -> P {
+    let provider = rules.into_iter().rev().fold(provider, with_rule);
+    match timeout {
+        Some(secs) => with_timeout(provider, secs),
+        None => provider,
+    }
+}
+
+/// Whether a boolean environment variable is set to 1, true, or yes
+fn env_flag(name: &str) -> bool {
+    std::env::var(name).is_ok_and(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+}
+
+/// Create a configured temp root, falling back to the system temp dir if that fails
+fn prepare_temp_root(root: PathBuf) -> PathBuf {
+    match std::fs::create_dir_all(&root) {
+        Ok(()) => root,
+        Err(e) => {
+            let fallback = std::env::temp_dir();
+            tracing::warn!(
+                "⚠️  Cannot create temp root {}: {}; using {}",
+                root.display(),
+                e,
+                fallback.display()
+            );
+            fallback
+        }
+    }
+}
+
+/// Prompt for the first call of a session that replaces one summarized by `compact_history`
+fn seed_with_summary(summary: &str, prompt: &str) -> String {
+    format!(
+        "Summary of our conversation so far:\n\n{}\n\nContinuing from there:\n\n{}",
+        summary.trim(),
+        prompt
+    )
+}
+
+/// Fresh correlation ID for a tool call that didn't bring its own
+fn new_request_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// Reject request options the provider's CLI would otherwise silently ignore
+fn check_capabilities(name: &str, caps: &ProviderCapabilities, request: &LlmRequest) -> Result<(), LlmError> {
+    let unsupported = [
+        (request.temperature.is_some() && !caps.supports_temperature, "temperature"),
+        (request.thinking_budget.is_some() && !caps.supports_thinking_budget, "thinking_budget"),
+        (request.fallback_model.is_some() && !caps.supports_fallback, "fallback_model"),
+        (
+            (request.session_id.is_some() || request.continue_last) && !caps.supports_sessions,
+            "session_id",
+        ),
+        (
+            request.attachments.as_ref().is_some_and(|a| !a.is_empty()) && !caps.supports_attachments,
+            "attachments",
+        ),
+    ];
+
+    match unsupported.iter().find(|(rejected, _)| *rejected) {
+        Some((_, option)) => Err(LlmError::InvalidRequest {
+            message: format!("{} does not support {}; omit it", name, option),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Put the server's shared preamble in front of the request's system prompt
+///
+/// Goes before `append_system_prompt` instead when only that is set, so a
+/// CLI's own default prompt isn't replaced.
+fn apply_base_system_prompt(request: &mut LlmRequest, preamble: &str) {
+    let target = match (&request.system_prompt, &request.append_system_prompt) {
+        (None, Some(_)) => &mut request.append_system_prompt,
+        _ => &mut request.system_prompt,
+    };
+    *target = Some(match target.take() {
+        Some(prompt) => format!("{}\n\n{}", preamble, prompt),
+        None => preamble.to_string(),
+    });
+}
+
+/// Provider-specific options accepted by the generic `invoke` tool
+///
+/// Options covered by the provider's `capabilities` are rejected when it lacks
+/// them; the rest are ignored by providers that don't use them.
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InvokeExtra {
+    append_system_prompt: Option<String>,
+    skip_base_system_prompt: Option<bool>,
+    fallback_model: Option<String>,
+    temperature: Option<f32>,
+    skip_permissions: Option<bool>,
+    allowed_tools: Option<Vec<String>>,
+    disallowed_tools: Option<Vec<String>>,
+    return_raw: Option<bool>,
+    env: Option<HashMap<String, String>>,
+    thinking_budget: Option<u32>,
+    skip_context_check: Option<bool>,
+    stdin_context: Option<String>,
+    extra_args: Option<Vec<String>>,
+    binary: Option<String>,
+}
 
 #[derive(Clone)]
 pub struct PraxioServer {
-    claude: Arc<ClaudeProvider>,
-    gemini: Arc<GeminiProvider>,
-    sessions: Arc<RwLock<HashMap<String, PathBuf>>>,  // session_id -> temp_dir
+    providers: HashMap<String, ProviderEntry>,  // provider name -> provider
+    sessions: Arc<RwLock<HashMap<String, SessionEntry>>>,  // session_id -> directories
+    session_resources: Arc<std::sync::OnceLock<Arc<turbomcp_server::HandlerRegistry>>>,  // running MCP server's resources, once serving
+    last_sessions: Arc<RwLock<HashMap<String, String>>>,  // provider -> most recently created session_id
+    concurrency: Arc<Semaphore>,  // one permit per running CLI subprocess
+    in_flight: Arc<InFlight>,  // running delegations, for `cancel`
+    shutting_down: Arc<AtomicBool>,  // set by `shutdown`; new delegations are refused
+    started_at: Instant,
+    max_concurrency: usize,
+    provider_concurrency: HashMap<String, usize>,  // per-provider caps applied when providers are registered
+    provider_rate_limits: HashMap<String, u32>,  // per-provider requests per minute, applied the same way
+    queue_timeout: Duration,
+    availability_ttl: Duration,
+    skip_permissions: bool,  // default for requests that don't say
+    allow_binary_override: bool,  // whether requests may name the program to run
+    max_prompt_chars: usize,
+    max_output_bytes: usize,  // stdout cap for requests that don't set their own
+    context_margin: f64,  // share of a context window a prompt may fill
+    default_timeout: Option<Duration>,  // overrides provider defaults when set
+    session_ttl: Option<Duration>,  // idle time after which a session is forgotten
+    audit_log: Option<Arc<AuditLog>>,
+    audit_full_prompts: bool,
+    response_cache: Option<Arc<ResponseCache>>,
+    dedup: Option<Arc<Dedup>>,  // joins identical concurrent stateless requests into one run
+    template_dir: Option<PathBuf>,  // where invoke_claude_template looks up templates
+    base_system_prompt: Option<String>,  // shared preamble put in front of every request's system prompt
+    temp_root: PathBuf,  // parent of session and per-call temp directories
+    env_allowlist: Option<Vec<String>>,  // when set, CLIs get a cleared environment plus these
+    circuit_breaker: (u32, Duration),  // consecutive failures and cooldown for new providers' breakers
+    cost_warning_usd: Option<f64>,  // responses costing more are flagged and logged
+    parse_retry: bool,  // re-run a fresh request once if its output can't be parsed
+    session_transcripts: bool,  // keep each session's prompts and answers for `export_session`
+    compact_responses: bool,  // leave heavy detail out of responses unless a request sets `verbose`
+    strict_models: bool,  // reject model names not known for the provider instead of passing them through
+    process_pool: Option<(usize, Duration)>,  // warm CLIs per configuration and their idle timeout
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<Metrics>>,
+}
+
+/// Configures and builds a `PraxioServer`
+///
+/// `PraxioServer::new()` is the same as `PraxioServerBuilder::new().build()`.
+/// Settings not covered here are available as `with_*` methods on the built server.
+pub struct PraxioServerBuilder {
+    providers: Vec<(String, Arc<dyn LlmProvider>)>,
+    default_providers: bool,
+    default_timeout: Option<Duration>,
+    provider_timeouts: HashMap<String, Duration>,
+    session_ttl: Option<Duration>,
+    max_concurrency: Option<usize>,
+    provider_concurrency: HashMap<String, usize>,
+    provider_rate_limits: HashMap<String, u32>,
+    circuit_breaker: (u32, Duration),
+    cost_warning_usd: Option<f64>,
+    base_system_prompt: Option<String>,
+    max_output_bytes: Option<usize>,
+    process_pool: Option<(usize, Duration)>,
+}
+
+impl Default for PraxioServerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PraxioServerBuilder {
+    pub fn new() -> Self {
+        Self {
+            providers: Vec::new(),
+            default_providers: true,
+            default_timeout: None,
+            provider_timeouts: HashMap::new(),
+            session_ttl: None,
+            max_concurrency: None,
+            provider_concurrency: HashMap::new(),
+            provider_rate_limits: HashMap::new(),
+            circuit_breaker: (circuit::DEFAULT_FAILURE_THRESHOLD, circuit::DEFAULT_COOLDOWN),
+            cost_warning_usd: None,
+            base_system_prompt: None,
+            max_output_bytes: None,
+            process_pool: None,
+        }
+    }
+
+    /// Register a provider, replacing a built-in one of the same name
+    pub fn with_provider(mut self, name: impl Into<String>, provider: Arc<dyn LlmProvider>) -> Self {
+        self.providers.push((name.into(), provider));
+        self
+    }
+
+    /// Set whether the bundled Claude, Gemini, Ollama, and OpenAI providers are registered
+    ///
+    /// Defaults to `true`.
+    pub fn with_default_providers(mut self, enabled: bool) -> Self {
+        self.default_providers = enabled;
+        self
+    }
+
+    /// Timeout for requests that don't set one, in place of each provider's default
+    pub fn with_default_timeout(mut self, timeout: Duration) -> Self {
+        self.default_timeout = Some(timeout);
+        self
+    }
+
+    /// Default timeout of one bundled provider (`claude`, `gemini`, `ollama`, `openai`)
+    ///
+    /// Replaces that provider's built-in default and overrides
+    /// `PRAXIO_<NAME>_TIMEOUT` (e.g. `PRAXIO_CLAUDE_TIMEOUT`, in seconds). A
+    /// request's `timeout_seconds` and `with_default_timeout` still take precedence.
+    pub fn with_provider_timeout(mut self, name: impl Into<String>, timeout: Duration) -> Self {
+        self.provider_timeouts.insert(name.into(), timeout);
+        self
+    }
+
+    /// Forget sessions that haven't been used for `ttl`
+    ///
+    /// Sessions never expire by default.
+    pub fn with_session_ttl(mut self, ttl: Duration) -> Self {
+        self.session_ttl = Some(ttl);
+        self
+    }
+
+    /// Set the maximum number of provider subprocesses that may run at once
+    ///
+    /// Overrides `PRAXIO_MAX_CONCURRENCY`.
+    pub fn with_max_concurrency(mut self, max: usize) -> Self {
+        self.max_concurrency = Some(max.max(1));
+        self
+    }
+
+    /// Cap how many of one provider's CLIs may run at once
+    ///
+    /// Counted within `with_max_concurrency`, so a burst of slow requests to
+    /// one provider queues on its own limit instead of taking every server-wide
+    /// permit and starving the others. Overrides `PRAXIO_<NAME>_MAX_CONCURRENCY`
+    /// (e.g. `PRAXIO_CLAUDE_MAX_CONCURRENCY`). Unlimited by default.
+    pub fn with_provider_concurrency(mut self, name: impl Into<String>, max: usize) -> Self {
+        self.provider_concurrency.insert(name.into(), max.max(1));
+        self
+    }
+
+    /// Cap how many requests per minute one provider may start
+    ///
+    /// A token bucket that lets a full minute's worth through at once, then
+    /// paces the rest. A request finding it empty waits for a token up to the
+    /// queue timeout, then fails with `RateLimited`. Overrides
+    /// `PRAXIO_<NAME>_REQUESTS_PER_MINUTE` (e.g. `PRAXIO_GEMINI_REQUESTS_PER_MINUTE`).
+    /// Unlimited by default.
+    pub fn with_provider_rate_limit(mut self, name: impl Into<String>, per_minute: u32) -> Self {
+        self.provider_rate_limits.insert(name.into(), per_minute.max(1));
+        self
+    }
+
+    /// Fail fast for `cooldown` once a provider has failed `failures` times in a row
+    ///
+    /// Defaults to 5 failures and 60 seconds. After the cooldown one probe call
+    /// is let through to test recovery. Pass `failures = 0` to disable.
+    pub fn with_circuit_breaker(mut self, failures: u32, cooldown: Duration) -> Self {
+        self.circuit_breaker = (failures, cooldown);
+        self
+    }
+
+    /// Log a warning and set `metadata.cost_warning` when one response costs more than `usd`
+    ///
+    /// Purely informational; the call still succeeds. Overrides `PRAXIO_COST_WARNING_USD`.
+    pub fn with_cost_warning(mut self, usd: f64) -> Self {
+        self.cost_warning_usd = Some(usd);
+        self
+    }
+
+    /// Put a shared preamble in front of every request's system prompt
+    ///
+    /// Joined to the request's `system_prompt` with a blank line. A request
+    /// without one gets it in front of its `append_system_prompt`, keeping the
+    /// CLI's default prompt, or as its whole system prompt when it sets
+    /// neither. Requests opt out with `skip_base_system_prompt`. Overrides
+    /// `PRAXIO_BASE_SYSTEM_PROMPT_FILE`.
+    pub fn with_base_system_prompt(mut self, preamble: impl Into<String>) -> Self {
+        self.base_system_prompt = Some(preamble.into());
+        self
+    }
+
+    /// Stop a CLI and fail the call with `OutputTooLarge` once its stdout passes `bytes`
+    ///
+    /// Defaults to 32 MiB, which no real answer comes near, so a runaway CLI
+    /// can't exhaust the server's memory. Overrides `PRAXIO_MAX_OUTPUT_BYTES`.
+    pub fn with_max_output_bytes(mut self, bytes: usize) -> Self {
+        self.max_output_bytes = Some(bytes);
+        self
+    }
+
+    /// Start bundled CLIs ahead of requests, keeping up to `size` waiting per configuration
+    ///
+    /// Applies to providers that support it (Claude), for requests with a
+    /// `working_dir` and no session to resume; others spawn a CLI per call as
+    /// before. A warm CLI unused for `idle_timeout` is killed. Off by default;
+    /// overrides `PRAXIO_POOL_SIZE` and `PRAXIO_POOL_IDLE_SECONDS`.
+    pub fn with_process_pool(mut self, size: usize, idle_timeout: Duration) -> Self {
+        self.process_pool = Some((size.max(1), idle_timeout));
+        self
+    }
+
+    /// Build the server, checking every provider's availability
+    pub async fn build(self) -> PraxioServer {
+        let max_concurrency = self
+            .max_concurrency
+            .or_else(|| {
+                std::env::var("PRAXIO_MAX_CONCURRENCY")
+                    .ok()
+                    .and_then(|v| v.parse::<usize>().ok())
+            })
+            .filter(|&n| n > 0)
+            .unwrap_or(DEFAULT_MAX_CONCURRENCY);
+        let max_prompt_chars = std::env::var("PRAXIO_MAX_PROMPT_CHARS")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|&n| n > 0)
+            .unwrap_or(DEFAULT_MAX_PROMPT_CHARS);
+        let template_dir = std::env::var_os("PRAXIO_TEMPLATE_DIR").map(PathBuf::from);
+        let max_output_bytes = self
+            .max_output_bytes
+            .or_else(|| {
+                std::env::var("PRAXIO_MAX_OUTPUT_BYTES")
+                    .ok()
+                    .and_then(|v| v.parse::<usize>().ok())
+            })
+            .filter(|&n| n > 0)
+            .unwrap_or(process::DEFAULT_MAX_OUTPUT_BYTES);
+        let base_system_prompt = self.base_system_prompt.or_else(|| {
+            let path = PathBuf::from(std::env::var_os("PRAXIO_BASE_SYSTEM_PROMPT_FILE")?);
+            match std::fs::read_to_string(&path) {
+                Ok(preamble) => Some(preamble),
+                Err(e) => {
+                    tracing::error!("❌ Cannot read base system prompt {}: {}", path.display(), e);
+                    None
+                }
+            }
+        });
+        let process_pool = self.process_pool.or_else(|| {
+            let size = std::env::var("PRAXIO_POOL_SIZE")
+                .ok()
+                .and_then(|v| v.parse::<usize>().ok())
+                .filter(|&n| n > 0)?;
+            let idle_timeout = std::env::var("PRAXIO_POOL_IDLE_SECONDS")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|&secs| secs > 0)
+                .map_or(pool::DEFAULT_IDLE_TIMEOUT, Duration::from_secs);
+            Some((size, idle_timeout))
+        });
+        let cost_warning_usd = self
+            .cost_warning_usd
+            .or_else(|| {
+                std::env::var("PRAXIO_COST_WARNING_USD")
+                    .ok()
+                    .and_then(|v| v.parse::<f64>().ok())
+            })
+            .filter(|usd| usd.is_finite() && *usd >= 0.0);
+        let session_transcripts = env_flag("PRAXIO_SESSION_TRANSCRIPTS");
+        let compact_responses = env_flag("PRAXIO_COMPACT_RESPONSES");
+        let strict_models = env_flag("PRAXIO_STRICT_MODELS");
+        let temp_root = std::env::var_os("PRAXIO_TEMP_ROOT")
+            .map(PathBuf::from)
+            .map_or_else(std::env::temp_dir, prepare_temp_root);
+        let env_allowlist = std::env::var("PRAXIO_ENV_ALLOWLIST").ok().map(|list| {
+            list.split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(String::from)
+                .collect()
+        });
+
+        if let Some(path) = std::env::var_os("PRAXIO_MODEL_ALIASES").map(PathBuf::from) {
+            match models::load_aliases(&path) {
+                Ok(()) => tracing::info!("Loaded model aliases from {}", path.display()),
+                Err(e) => tracing::warn!("⚠️  Ignoring model aliases in {}: {}", path.display(), e),
+            }
+        }
+
+        if let Some(path) = std::env::var_os("PRAXIO_REDACT_PATTERNS").map(PathBuf::from) {
+            match redact::load_patterns(&path) {
+                Ok(count) => tracing::info!("Loaded {} redaction pattern(s) from {}", count, path.display()),
+                Err(e) => tracing::warn!("⚠️  Ignoring redaction patterns in {}: {}", path.display(), e),
+            }
+        }
+
+        let mut provider_concurrency = self.provider_concurrency;
+        let mut provider_rate_limits = self.provider_rate_limits;
+        let names = ["claude", "gemini", "ollama", "openai"]
+            .into_iter()
+            .chain(self.providers.iter().map(|(name, _)| name.as_str()));
+        for name in names {
+            if !provider_concurrency.contains_key(name) {
+                if let Some(max) = std::env::var(format!("PRAXIO_{}_MAX_CONCURRENCY", name.to_uppercase()))
+                    .ok()
+                    .and_then(|v| v.parse::<usize>().ok())
+                    .filter(|&n| n > 0)
+                {
+                    provider_concurrency.insert(name.to_string(), max);
+                }
+            }
+            if !provider_rate_limits.contains_key(name) {
+                if let Some(per_minute) = std::env::var(format!("PRAXIO_{}_REQUESTS_PER_MINUTE", name.to_uppercase()))
+                    .ok()
+                    .and_then(|v| v.parse::<u32>().ok())
+                    .filter(|&n| n > 0)
+                {
+                    provider_rate_limits.insert(name.to_string(), per_minute);
+                }
+            }
+        }
+
+        let mut server = PraxioServer {
+            providers: HashMap::new(),
+            sessions: Arc::new(RwLock::new(HashMap::new())),
+            session_resources: Arc::new(std::sync::OnceLock::new()),
+            last_sessions: Arc::new(RwLock::new(HashMap::new())),
+            concurrency: Arc::new(Semaphore::new(max_concurrency)),
+            in_flight: Arc::new(InFlight::default()),
+            shutting_down: Arc::new(AtomicBool::new(false)),
+            started_at: Instant::now(),
+            max_concurrency,
+            provider_concurrency,
+            provider_rate_limits,
+            queue_timeout: DEFAULT_QUEUE_TIMEOUT,
+            availability_ttl: DEFAULT_AVAILABILITY_TTL,
+            skip_permissions: true,
+            allow_binary_override: false,
+            max_prompt_chars,
+            max_output_bytes,
+            context_margin: DEFAULT_CONTEXT_MARGIN,
+            default_timeout: self.default_timeout,
+            session_ttl: self.session_ttl,
+            audit_log: None,
+            audit_full_prompts: false,
+            response_cache: None,
+            dedup: Some(Arc::new(Dedup::new())),
+            template_dir,
+            base_system_prompt: base_system_prompt.filter(|preamble| !preamble.trim().is_empty()),
+            temp_root,
+            env_allowlist,
+            circuit_breaker: self.circuit_breaker,
+            cost_warning_usd,
+            parse_retry: false,
+            session_transcripts,
+            compact_responses,
+            strict_models,
+            process_pool,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+        };
+
+        let mut stderr_rules = match std::env::var_os("PRAXIO_STDERR_RULES").map(PathBuf::from) {
+            Some(path) => match classify::load_rules(&path) {
+                Ok(rules) => {
+                    tracing::info!("Loaded stderr rules from {}", path.display());
+                    rules
+                }
+                Err(e) => {
+                    tracing::warn!("⚠️  Ignoring stderr rules in {}: {}", path.display(), e);
+                    HashMap::new()
+                }
+            },
+            None => HashMap::new(),
+        };
+
+        if self.default_providers {
+            let timeout = |name: &str| {
+                self.provider_timeouts
+                    .get(name)
+                    .map(|timeout| timeout.as_secs().max(1))
+                    .or_else(|| {
+                        std::env::var(format!("PRAXIO_{}_TIMEOUT", name.to_uppercase()))
+                            .ok()
+                            .and_then(|v| v.parse::<u64>().ok())
+                            .filter(|&secs| secs > 0)
+                    })
+            };
+
+            let claude = configure_bundled(
+                ClaudeProvider::new(),
+                stderr_rules.remove("claude").unwrap_or_default(),
+                ClaudeProvider::with_stderr_rule,
+                timeout("claude"),
+                ClaudeProvider::with_timeout,
+            );
+            let gemini = configure_bundled(
+                GeminiProvider::new(),
+                stderr_rules.remove("gemini").unwrap_or_default(),
+                GeminiProvider::with_stderr_rule,
+                timeout("gemini"),
+                GeminiProvider::with_timeout,
+            );
+            let ollama = configure_bundled(
+                OllamaProvider::new(),
+                stderr_rules.remove("ollama").unwrap_or_default(),
+                OllamaProvider::with_stderr_rule,
+                timeout("ollama"),
+                OllamaProvider::with_timeout,
+            );
+            let openai = configure_bundled(
+                OpenAiProvider::new(),
+                stderr_rules.remove("openai").unwrap_or_default(),
+                OpenAiProvider::with_stderr_rule,
+                timeout("openai"),
+                OpenAiProvider::with_timeout,
+            );
+            let claude = match process_pool {
+                Some((size, idle_timeout)) => claude.with_process_pool(size, idle_timeout),
+                None => claude,
+            };
+            server.register_provider("claude", Arc::new(claude));
+            server.register_provider("gemini", Arc::new(gemini));
+            server.register_provider("ollama", Arc::new(ollama));
+            server.register_provider("openai", Arc::new(openai));
+        }
+        for name in stderr_rules.keys() {
+            tracing::warn!("⚠️  Ignoring stderr rules for '{}': not a bundled provider", name);
+        }
+        for (name, provider) in self.providers {
+            server.register_provider(name, provider);
+        }
+
+        // Check provider availability concurrently (also primes the availability cache)
+        let checks = server.providers.iter().map(|(name, entry)| async move {
+            let availability = entry
+                .availability
+                .get_or_check(entry.provider.as_ref(), server.availability_ttl, true)
+                .await;
+            (name, availability)
+        });
+
+        for (name, availability) in futures::future::join_all(checks).await {
+            match availability {
+                ProviderAvailability::Available { version: Some(version) } => {
+                    tracing::info!("✅ {} provider available (version {})", name, version);
+                }
+                ProviderAvailability::Available { version: None } => {
+                    tracing::info!("✅ {} provider available", name);
+                }
+                ProviderAvailability::Degraded { reason } => {
+                    tracing::warn!("⚠️  {} provider degraded: {}", name, reason);
+                }
+                ProviderAvailability::Unavailable { reason } => {
+                    tracing::warn!("⚠️  {} provider unavailable: {}", name, reason);
+                }
+            }
+        }
+
+        server
+    }
 }
 
 impl PraxioServer {
     pub async fn new() -> Self {
-        let claude = Arc::new(ClaudeProvider::new());
-        let gemini = Arc::new(GeminiProvider::new());
+        PraxioServerBuilder::new().build().await
+    }
+
+    /// Register a provider under the given name, replacing any existing one
+    pub fn register_provider(&mut self, name: impl Into<String>, provider: Arc<dyn LlmProvider>) {
+        let name = name.into();
+        let (failures, cooldown) = self.circuit_breaker;
+        let entry = ProviderEntry {
+            provider,
+            availability: Arc::new(AvailabilityCache::new()),
+            usage: Arc::new(UsageCounters::new()),
+            circuit: Arc::new(CircuitBreaker::new(name.clone(), failures, cooldown)),
+            concurrency: self
+                .provider_concurrency
+                .get(&name)
+                .map(|&max| Arc::new(Semaphore::new(max))),
+            rate_limit: self
+                .provider_rate_limits
+                .get(&name)
+                .map(|&per_minute| Arc::new(RateLimiter::new(name.clone(), per_minute))),
+            models: Arc::new(RwLock::new(None)),
+        };
+        self.providers.insert(name, entry);
+    }
+
+    /// Set the maximum number of provider subprocesses that may run at once
+    ///
+    /// Overrides `PRAXIO_MAX_CONCURRENCY`. Values below 1 are clamped to 1.
+    pub fn with_max_concurrency(mut self, max: usize) -> Self {
+        self.max_concurrency = max.max(1);
+        self.concurrency = Arc::new(Semaphore::new(self.max_concurrency));
+        self
+    }
+
+    /// Set how long a request waits for a free slot before failing with "server at capacity"
+    pub fn with_queue_timeout(mut self, timeout: Duration) -> Self {
+        self.queue_timeout = timeout;
+        self
+    }
+
+    /// Set how long availability check results are reused before re-checking
+    pub fn with_availability_ttl(mut self, ttl: Duration) -> Self {
+        self.availability_ttl = ttl;
+        self
+    }
+
+    /// Set whether delegates auto-approve their own actions unless a request says otherwise
+    ///
+    /// Defaults to `true` for backward compatibility. See
+    /// `LlmRequest::skip_permissions` for what this allows.
+    pub fn with_skip_permissions(mut self, skip: bool) -> Self {
+        self.skip_permissions = skip;
+        self
+    }
+
+    /// Set whether a request's `binary` may replace the provider's CLI
+    ///
+    /// Defaults to `false`: anyone who can call a tool could otherwise run any
+    /// program on this machine. `extra_args` are always accepted.
+    pub fn with_binary_override(mut self, allowed: bool) -> Self {
+        self.allow_binary_override = allowed;
+        self
+    }
+
+    /// Set the longest prompt accepted, in characters
+    ///
+    /// Overrides `PRAXIO_MAX_PROMPT_CHARS`. Values below 1 are clamped to 1.
+    pub fn with_max_prompt_chars(mut self, max: usize) -> Self {
+        self.max_prompt_chars = max.max(1);
+        self
+    }
+
+    /// Set the share of a provider's context window a prompt may fill (default 0.9)
+    ///
+    /// Prompt sizes are estimated, so the rest is headroom for the estimate
+    /// running low. Values are clamped to between 0.1 and 1.0.
+    pub fn with_context_margin(mut self, margin: f64) -> Self {
+        self.context_margin = margin.clamp(0.1, 1.0);
+        self
+    }
+
+    /// Append a JSON line per completed delegation to `path`
+    ///
+    /// Entries carry the timestamp, provider, model, token usage, cost, and a
+    /// SHA-256 hash of the prompt.
+    pub fn with_audit_log(mut self, path: PathBuf) -> Self {
+        self.audit_log = Some(Arc::new(AuditLog::new(path)));
+        self
+    }
+
+    /// Also record full prompt text in the audit log, not just its hash
+    pub fn with_audit_full_prompts(mut self, enabled: bool) -> Self {
+        self.audit_full_prompts = enabled;
+        self
+    }
+
+    /// Reuse responses to identical stateless requests
+    ///
+    /// Holds up to `capacity` responses for `ttl` each. Requests with a session,
+    /// working directory, or attachments always run fresh.
+    pub fn with_response_cache(mut self, capacity: usize, ttl: Duration) -> Self {
+        self.response_cache = Some(Arc::new(ResponseCache::new(capacity, ttl)));
+        self
+    }
+
+    /// Whether identical stateless requests arriving while one is already
+    /// running share its response instead of each spawning a CLI (on by default)
+    ///
+    /// Uses the response cache's notion of "identical": every option that shapes
+    /// the answer must match, and requests the cache bypasses always run on
+    /// their own. Turn it off if callers
+    /// deliberately send the same prompt several times to sample varied answers.
+    pub fn with_request_dedup(mut self, enabled: bool) -> Self {
+        self.dedup = enabled.then(|| Arc::new(Dedup::new()));
+        self
+    }
+
+    /// Re-run a request once when the CLI's output can't be parsed even after repair
+    ///
+    /// Off by default: the retry pays for the prompt twice. Requests that resume
+    /// a session are never retried, since the failed run already added a turn.
+    pub fn with_parse_retry(mut self, enabled: bool) -> Self {
+        self.parse_retry = enabled;
+        self
+    }
+
+    /// Keep every prompt and answer of new sessions for `export_session`
+    ///
+    /// Off by default, since transcripts hold whatever was sent and received.
+    /// They live in memory and are dropped with their session.
+    pub fn with_session_transcripts(mut self, enabled: bool) -> Self {
+        self.session_transcripts = enabled;
+        self
+    }
+
+    /// Leave heavy detail out of tool responses unless a request sets `verbose`
+    ///
+    /// Compact responses drop `all_models_used`, `model_breakdown`, cache token
+    /// counts, and the `uuid` and `service_tier` metadata, which most clients
+    /// never read. Off by default.
+    pub fn with_compact_responses(mut self, enabled: bool) -> Self {
+        self.compact_responses = enabled;
+        self
+    }
+
+    /// Reject model names not known for the provider before running its CLI
+    ///
+    /// Names are checked against the built-in model list and alias targets, or
+    /// the models the CLI lists (Ollama). The error suggests the closest known
+    /// name. Off by default, when unknown names are logged and passed through,
+    /// so newly released models keep working.
+    pub fn with_strict_models(mut self, enabled: bool) -> Self {
+        self.strict_models = enabled;
+        self
+    }
+
+    /// Load prompt templates for `invoke_claude_template` from `dir`
+    ///
+    /// Overrides `PRAXIO_TEMPLATE_DIR`.
+    pub fn with_template_dir(mut self, dir: PathBuf) -> Self {
+        self.template_dir = Some(dir);
+        self
+    }
+
+    /// Create session and per-call temp directories under `root` instead of the system temp dir
+    ///
+    /// For hosts where `/tmp` is small, `noexec`, or a tmpfs. `root` is created
+    /// if missing; if that fails the system temp dir is kept. Overrides `PRAXIO_TEMP_ROOT`.
+    pub fn with_temp_root(mut self, root: PathBuf) -> Self {
+        self.temp_root = prepare_temp_root(root);
+        self
+    }
+
+    /// Start every CLI with a cleared environment holding only `names` (plus `PATH` and `HOME`)
+    ///
+    /// Keeps the server's credentials and other secrets away from delegates; a
+    /// request's own `env` is still applied on top. Overrides `PRAXIO_ENV_ALLOWLIST`.
+    pub fn with_env_allowlist(mut self, names: Vec<String>) -> Self {
+        self.env_allowlist = Some(names);
+        self
+    }
+
+    /// Record every delegation's outcome, tokens, cost, and duration in `metrics`
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Stop accepting delegations and wait for running ones to finish
+    ///
+    /// Delegations still running after `grace` have their CLI subprocesses
+    /// killed. Clones of this server share the shutdown.
+    pub async fn shutdown(&self, grace: Duration) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+        let all = self.max_concurrency as u32;
+
+        let drained = tokio::time::timeout(grace, self.concurrency.acquire_many(all)).await;
+        if drained.is_err() {
+            tracing::warn!(
+                "⚠️  Delegations still running after {}s; killing their subprocesses",
+                grace.as_secs()
+            );
+            process::abort_all();
+            let _ = tokio::time::timeout(SHUTDOWN_KILL_WAIT, self.concurrency.acquire_many(all)).await;
+        }
+
+        self.concurrency.close();
+        tracing::info!("All delegations finished");
+
+        // The process exits without running destructors; remove session directories now
+        self.sessions.write().await.clear();
+    }
+
+    /// Serve MCP over stdio, like `run_stdio`, with each session readable as a resource
+    ///
+    /// turbomcp's transports find resources by exact URI, so the
+    /// `praxio://session/{id}` template only answers once every live session
+    /// has its own URI registered; this keeps those in step with the sessions.
+    pub async fn serve_stdio(self) -> Result<(), Box<dyn std::error::Error>> {
+        let server = self.mcp_server().await?;
+        server.run_stdio().await.map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
+    }
+
+    /// Serve MCP over streamable HTTP at `/mcp`, like `run_http`, with each session readable as a resource
+    #[cfg(feature = "http")]
+    pub async fn serve_http<A: std::net::ToSocketAddrs + Send + std::fmt::Debug>(
+        self,
+        addr: A,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        use turbomcp::turbomcp_transport::streamable_http_v2::StreamableHttpConfigBuilder;
+
+        let server = self.mcp_server().await?;
+        let config = StreamableHttpConfigBuilder::new().with_endpoint_path("/mcp").build();
+        server
+            .run_http_with_config(addr, config)
+            .await
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
+    }
+
+    /// The MCP server for this instance, wired up to register session resources
+    async fn mcp_server(self) -> Result<turbomcp::Server, turbomcp::ServerError> {
+        let server = self.clone().create_server()?;
+        let _ = self.session_resources.set(server.registry().clone());
+        for session_id in self.sessions.read().await.keys() {
+            self.register_session_resource(session_id);
+        }
+        Ok(server)
+    }
+
+    /// Make `praxio://session/{session_id}` readable on the running MCP server
+    fn register_session_resource(&self, session_id: &str) {
+        use turbomcp::turbomcp_protocol::types::{Resource, ResourceContent, TextResourceContents};
+        use turbomcp::turbomcp_protocol::ReadResourceResult;
+
+        let Some(registry) = self.session_resources.get() else {
+            return;
+        };
+        let uri = format!("praxio://session/{}", session_id);
+        let resource = Resource {
+            name: "session".to_string(),
+            title: Some(format!("Praxio session {}", session_id)),
+            uri: uri.clone(),
+            description: Some("One delegation session with its transcript when recorded".to_string()),
+            mime_type: Some("application/json".to_string()),
+            annotations: None,
+            size: None,
+            meta: None,
+        };
+        let server = self.clone();
+        let id = session_id.to_string();
+        let handler = turbomcp_server::handlers::FunctionResourceHandler::new(resource, move |request, _ctx| {
+            let server = server.clone();
+            let session_id = id.clone();
+            async move {
+                let text = server
+                    .session_resource(session_id)
+                    .await
+                    .map_err(|e| turbomcp::ServerError::not_found(e.to_string()))?;
+                Ok(ReadResourceResult {
+                    contents: vec![ResourceContent::Text(TextResourceContents {
+                        uri: request.uri,
+                        mime_type: Some("application/json".to_string()),
+                        text,
+                        meta: None,
+                    })],
+                    _meta: None,
+                })
+            }
+        });
+        if let Err(e) = registry.register_resource(uri, handler) {
+            tracing::warn!("⚠️  Cannot register the resource for session {}: {}", session_id, e);
+        }
+    }
+
+    /// Drop a forgotten session's resource from the running MCP server
+    fn unregister_session_resource(&self, session_id: &str) {
+        if let Some(registry) = self.session_resources.get() {
+            registry.unregister_resource(&format!("praxio://session/{}", session_id));
+        }
+    }
+
+    /// Create a fresh directory under the temp root, removed once the last handle is dropped
+    ///
+    /// Falls back to the system temp dir, with a warning, if the configured
+    /// temp root can't take it (full, removed, or no longer writable).
+    fn create_temp_dir(&self, prefix: &str) -> Result<Arc<TempDir>, LlmError> {
+        let mut builder = tempfile::Builder::new();
+        builder.prefix(prefix);
+        let error = match builder.tempdir_in(&self.temp_root) {
+            Ok(dir) => return Ok(Arc::new(dir)),
+            Err(source) => LlmError::TempDirFailed {
+                operation: "create temp directory in".to_string(),
+                path: self.temp_root.clone(),
+                source,
+            },
+        };
+
+        let fallback = std::env::temp_dir();
+        if fallback == self.temp_root {
+            return Err(error);
+        }
+        match builder.tempdir_in(&fallback) {
+            Ok(dir) => {
+                tracing::warn!("⚠️  {}; using {} instead", error, fallback.display());
+                Ok(Arc::new(dir))
+            }
+            Err(e) => {
+                tracing::warn!("⚠️  Cannot create temp directory in {} either: {}", fallback.display(), e);
+                Err(error)
+            }
+        }
+    }
+
+    fn check_not_shutting_down(&self) -> Result<(), LlmError> {
+        if self.shutting_down.load(Ordering::SeqCst) {
+            return Err(LlmError::InvalidRequest {
+                message: "server is shutting down".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Liveness and readiness from cached availability results; never spawns a CLI
+    pub async fn health_report(&self) -> HealthReport {
+        let mut providers_available = 0;
+        for entry in self.providers.values() {
+            if entry.availability.last_available().await && entry.circuit.status().state != "open" {
+                providers_available += 1;
+            }
+        }
+
+        let shutting_down = self.shutting_down.load(Ordering::SeqCst);
+        HealthReport {
+            status: if shutting_down { "shutting_down" } else { "ok" },
+            ready: !shutting_down && providers_available > 0,
+            uptime_seconds: self.started_at.elapsed().as_secs(),
+            providers_available,
+        }
+    }
+
+    /// Fill a built-in MCP prompt, preferring an override from the template directory
+    fn render_builtin_prompt(&self, name: &str, vars: [(&str, String); 2]) -> Result<String, LlmError> {
+        let template = template::load_builtin(self.template_dir.as_deref(), name)?;
+        let vars = vars.into_iter().map(|(k, v)| (k.to_string(), v)).collect();
+        template::render(&template, &vars)
+    }
+
+    /// Models the provider's CLI reports, reusing a recent list; `None` if it can't list them
+    async fn cli_models(&self, entry: &ProviderEntry) -> Result<Option<Vec<String>>, LlmError> {
+        if let Some((ref models, listed_at)) = *entry.models.read().await {
+            if listed_at.elapsed() < MODEL_LIST_TTL {
+                return Ok(Some(models.clone()));
+            }
+        }
+
+        let models = entry.provider.list_models().await?;
+        if let Some(ref models) = models {
+            *entry.models.write().await = Some((models.clone(), Instant::now()));
+        }
+        Ok(models)
+    }
+
+    /// Reject an unknown model name in strict mode, otherwise just warn about it
+    async fn check_model(&self, name: &str, entry: &ProviderEntry, model: &str) -> Result<(), LlmError> {
+        // A CLI that can't list its models right now is checked against the built-in list
+        let listed = self.cli_models(entry).await.ok().flatten();
+        match models::check_known(name, model, listed.as_deref()) {
+            Err(e) if !self.strict_models => {
+                tracing::warn!("⚠️  Passing model through unchecked: {}", e);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Check a provider's availability, reusing a recent result unless `force` is set
+    pub async fn provider_availability(
+        &self,
+        name: &str,
+        force: bool,
+    ) -> Result<ProviderAvailability, LlmError> {
+        let entry = self.provider_entry(name)?;
+        let availability = entry
+            .availability
+            .get_or_check(entry.provider.as_ref(), self.availability_ttl, force)
+            .await;
+
+        // The CLI check only says whether it can run; recent calls say how well
+        Ok(match (availability, entry.circuit.degraded()) {
+            (ProviderAvailability::Available { .. }, Some(reason)) => ProviderAvailability::Degraded { reason },
+            (availability, _) => availability,
+        })
+    }
+
+    /// A response as returned by the tools, compact unless the server or request asks for detail
+    fn response_value(&self, response: &LlmResponse, verbose: Option<bool>) -> Result<serde_json::Value, LlmError> {
+        let compact = self.compact_responses && !verbose.unwrap_or(false);
+        Ok(to_value_compact(response, compact)?)
+    }
+
+    /// Look up a provider and its server-side state by name
+    fn provider_entry(&self, name: &str) -> Result<&ProviderEntry, LlmError> {
+        self.providers
+            .get(name)
+            .ok_or_else(|| LlmError::ProviderUnavailable {
+                provider: name.to_string(),
+                reason: "unknown provider".to_string(),
+            })
+    }
+
+    /// Add a response's turns, tokens and cost to its session and report the budget left
+    ///
+    /// Also appends `prompt` and the answer to the session's transcript, if it keeps one.
+    async fn record_session_call(&self, session_id: &str, prompt: &str, response: &mut LlmResponse) {
+        let mut sessions = self.sessions.write().await;
+        if let Some(entry) = sessions.get_mut(session_id) {
+            if let Some(ref transcript) = entry.transcript {
+                transcript.lock().unwrap_or_else(|e| e.into_inner()).push(TranscriptTurn {
+                    at: chrono::Utc::now().to_rfc3339(),
+                    provider: response.provider.clone(),
+                    model: response.primary_model.clone(),
+                    prompt: prompt.to_string(),
+                    response: response.content.clone(),
+                    tokens: response.tokens.clone(),
+                    cost_usd: response.cost_usd,
+                });
+            }
+            entry.spent_usd += response.cost_usd.unwrap_or(0.0);
+            entry.calls += 1;
+            let turns = response.metadata.num_turns.unwrap_or(1);
+            entry.turn_count = entry.turn_count.saturating_add(turns);
+            entry.history_turns = entry.history_turns.saturating_add(turns);
+            if let Some(ref tokens) = response.tokens {
+                entry.input_tokens = entry.input_tokens.saturating_add(tokens.input);
+                entry.output_tokens = entry.output_tokens.saturating_add(tokens.output);
+            }
+            entry.last_used_at = chrono::Utc::now();
+            response.metadata.budget_remaining_usd =
+                entry.budget_usd.map(|budget| (budget - entry.spent_usd).max(0.0));
+        }
+    }
+
+    /// Look up a live session, refreshing its expiry
+    async fn session(&self, session_id: &str) -> Result<SessionEntry, LlmError> {
+        let mut sessions = self.sessions.write().await;
+        let expired = match sessions.get(session_id) {
+            Some(entry) => self.session_ttl.is_some_and(|ttl| entry.last_used.elapsed() > ttl),
+            None => true,
+        };
+        if expired {
+            if sessions.remove(session_id).is_some() {
+                self.unregister_session_resource(session_id);
+            }
+            return Err(LlmError::SessionNotFound {
+                session_id: session_id.to_string(),
+            });
+        }
+
+        let entry = sessions.get_mut(session_id).expect("checked above");
+        entry.last_used = Instant::now();
+        entry.last_used_at = chrono::Utc::now();
+        Ok(entry.clone())
+    }
+
+    /// Remember a new session as the provider's most recent, dropping any that have expired
+    async fn insert_session(&self, session_id: String, mut entry: SessionEntry) {
+        if self.session_transcripts && entry.transcript.is_none() {
+            entry.transcript = Some(Default::default());
+        }
+        let mut sessions = self.sessions.write().await;
+        if let Some(ttl) = self.session_ttl {
+            sessions.retain(|expired_id, entry| {
+                let live = entry.last_used.elapsed() <= ttl;
+                if !live {
+                    self.unregister_session_resource(expired_id);
+                }
+                live
+            });
+        }
+        let provider = entry.provider.clone();
+        self.register_session_resource(&session_id);
+        sessions.insert(session_id.clone(), entry);
+
+        self.last_sessions.write().await.insert(provider, session_id);
+    }
+
+    /// The provider's most recently created session, if any
+    async fn last_session(&self, provider: &str) -> Option<String> {
+        self.last_sessions.read().await.get(provider).cloned()
+    }
+
+    /// Summarize a resumed session whose history has grown past `max_history_turns`
+    ///
+    /// None of the CLIs can trim their own history, so the caller continues in
+    /// a fresh session seeded with the returned summary. Returns the summary and
+    /// the old session's entry (including the summary call), or `None` while the
+    /// session is within the limit.
+    async fn compact_history(
+        &self,
+        provider: &str,
+        session_id: Option<&str>,
+        max_history_turns: Option<u32>,
+        request_id: &str,
+    ) -> Result<Option<(String, SessionEntry)>, LlmError> {
+        let Some(max_turns) = max_history_turns else {
+            return Ok(None);
+        };
+        if !self.provider_entry(provider)?.provider.capabilities().supports_sessions {
+            return Err(LlmError::InvalidRequest {
+                message: format!("{} does not support max_history_turns; omit it", provider),
+            });
+        }
+        if max_turns == 0 {
+            return Err(LlmError::InvalidRequest {
+                message: "max_history_turns must be at least 1".to_string(),
+            });
+        }
+        let Some(session_id) = session_id else {
+            return Err(LlmError::InvalidRequest {
+                message: "max_history_turns only applies when resuming a session".to_string(),
+            });
+        };
+
+        let entry = self.session(session_id).await?;
+        if entry.history_turns <= max_turns {
+            return Ok(None);
+        }
+
+        tracing::info!(
+            "Session {} has {} turns of history, over the limit of {}; summarizing into a new session",
+            session_id.chars().take(8).collect::<String>(),
+            entry.history_turns,
+            max_turns
+        );
+
+        let request = LlmRequest::builder(HISTORY_SUMMARY_PROMPT)
+            .with_session_id(session_id)
+            .with_temp_dir(entry.temp_dir.path())
+            .with_optional(entry.working_dir.clone(), LlmRequestBuilder::with_working_dir)
+            .with_skip_permissions(self.skip_permissions)
+            .with_skip_context_check(true)
+            .with_request_id(request_id)
+            .build();
+
+        let mut response = self.invoke_provider(provider, request).await?;
+        self.record_session_call(session_id, HISTORY_SUMMARY_PROMPT, &mut response).await;
+        let entry = self.session(session_id).await?;
+        Ok(Some((response.content, entry)))
+    }
+
+    /// Invoke a registered provider, tagging the response with the request's correlation ID
+    ///
+    /// Also fills in the `session` field of the caller's `delegation` span.
+    async fn invoke_provider(&self, name: &str, mut request: LlmRequest) -> Result<LlmResponse, LlmError> {
+        let request_id = request.request_id.get_or_insert_with(new_request_id).clone();
+        let span = tracing::Span::current();
+        let session_id = request.session_id.clone();
+        if let Some(ref session_id) = session_id {
+            span.record("session", session_id.as_str());
+        }
+
+        // Dropping the run on cancellation kills the CLI (see `process::run_cli`)
+        let guard = self.in_flight.register(&request_id);
+        let mut response = tokio::select! {
+            result = self.run_provider(name, request) => {
+                // Name the provider and request in the error, whatever its kind
+                result.map_err(|e| e.with_context(name, &request_id))?
+            }
+            _ = guard.token.cancelled() => {
+                tracing::info!("Request {} cancelled", request_id);
+                return Err(LlmError::Cancelled { request_id: request_id.clone() }.with_context(name, &request_id));
+            }
+        };
+        drop(guard);
+        response.metadata.request_id = Some(request_id);
+        if let (None, Some(new_session_id)) = (session_id, &response.metadata.session_id) {
+            span.record("session", new_session_id.as_str());
+        }
+        Ok(response)
+    }
+
+    /// Run a registered provider, holding a concurrency permit for the duration
+    async fn run_provider(&self, name: &str, mut request: LlmRequest) -> Result<LlmResponse, LlmError> {
+        self.check_not_shutting_down()?;
+        let entry = self.provider_entry(name)?;
+        let provider = entry.provider.clone();
+
+        request.load_system_prompt_file()?;
+        if !request.skip_base_system_prompt {
+            if let Some(ref preamble) = self.base_system_prompt {
+                apply_base_system_prompt(&mut request, preamble);
+            }
+        }
+        let schema_validator = match request.response_schema {
+            Some(ref response_schema) => {
+                let validator = schema::compile(response_schema)?;
+                let instructions = schema::instructions(response_schema);
+                request.system_prompt = Some(match request.system_prompt.take() {
+                    Some(system_prompt) => format!("{}\n\n{}", system_prompt, instructions),
+                    None => instructions,
+                });
+                Some(validator)
+            }
+            None => None,
+        };
+        request.validate()?;
+        if request.binary.is_some() && !self.allow_binary_override {
+            return Err(LlmError::InvalidRequest {
+                message: "binary overrides are disabled on this server; omit binary".to_string(),
+            });
+        }
+        request.check_prompt_length(self.max_prompt_chars)?;
+        if request.timeout_seconds.is_none() {
+            request.timeout_seconds = self.default_timeout.map(|t| t.as_secs().max(1));
+        }
+        request.max_output_bytes.get_or_insert(self.max_output_bytes);
+        if request.env_allowlist.is_none() {
+            request.env_allowlist = self.env_allowlist.clone();
+        }
+        request.model = request.model.map(|m| models::resolve(name, &m)).transpose()?;
+        request.fallback_model = request
+            .fallback_model
+            .map(|m| models::resolve(name, &m))
+            .transpose()?;
+        for model in request.model.iter().chain(&request.fallback_model) {
+            self.check_model(name, entry, model).await?;
+        }
+        let capabilities = provider.capabilities();
+        check_capabilities(name, &capabilities, &request)?;
+        check_context_window(name, &capabilities, &request, self.context_margin)?;
+
+        // Dry runs never spawn a subprocess, so they don't need a slot
+        if request.dry_run {
+            return Ok(LlmResponse::dry_run(name, provider.command_args(&request)));
+        }
+
+        let cache_key = ResponseCache::key(name, &request);
+        if let (Some(cache), Some(key)) = (&self.response_cache, &cache_key) {
+            if let Some(mut response) = cache.get(key) {
+                tracing::info!("Serving {} response from cache", name);
+                response.cost_usd = Some(0.0);
+                response.metadata.cached = Some(true);
+                // The original call's session is already mapped; don't hand it out twice
+                response.metadata.session_id = None;
+                return Ok(response);
+            }
+        }
+
+        // An identical request already running answers this one too
+        let flight = match (&self.dedup, &cache_key) {
+            (Some(dedup), Some(key)) => match dedup.join(key) {
+                Flight::Leader(guard) => Some(guard),
+                Flight::Follower(slot) => {
+                    tracing::info!("Waiting on an identical {} request already running", name);
+                    if let Some(mut response) = dedup::wait(slot).await {
+                        response.cost_usd = Some(0.0);
+                        response.metadata.deduplicated = Some(true);
+                        // Only the leading call's session gets mapped
+                        response.metadata.session_id = None;
+                        return Ok(response);
+                    }
+                    // That run failed or was cancelled; this one runs on its own
+                    None
+                }
+            },
+            _ => None,
+        };
+
+        entry.circuit.allow()?;
+
+        // The provider's own limits come first, so requests queued behind them don't
+        // hold server-wide permits other providers could use. All queues are FIFO.
+        let queue_deadline = tokio::time::Instant::now() + self.queue_timeout;
+        if let Some(ref rate_limit) = entry.rate_limit {
+            rate_limit.acquire(queue_deadline).await?;
+        }
+        let _provider_permit = match entry.concurrency {
+            Some(ref semaphore) => Some(
+                tokio::time::timeout_at(queue_deadline, semaphore.acquire())
+                    .await
+                    .map_err(|_| LlmError::InvalidRequest {
+                        message: format!(
+                            "{} at capacity: {} delegations already running",
+                            name,
+                            self.provider_concurrency.get(name).copied().unwrap_or_default()
+                        ),
+                    })?
+                    .map_err(|_| LlmError::InvalidRequest {
+                        message: "server is shutting down".to_string(),
+                    })?,
+            ),
+            None => None,
+        };
+        let _permit = tokio::time::timeout_at(queue_deadline, self.concurrency.acquire())
+            .await
+            .map_err(|_| LlmError::InvalidRequest {
+                message: format!(
+                    "server at capacity: {} delegations already running",
+                    self.max_concurrency
+                ),
+            })?
+            .map_err(|_| LlmError::InvalidRequest {
+                message: "server is shutting down".to_string(),
+            })?;
+        // Requests queued before shutdown began must not start new subprocesses
+        self.check_not_shutting_down()?;
+
+        let prompt = self.audit_log.as_ref().map(|_| request.prompt.clone());
+        let retry = (self.parse_retry && request.session_id.is_none() && !request.continue_last)
+            .then(|| request.clone());
+        let mut result = provider.invoke(request).await;
+        if let (Err(LlmError::ParseError { .. }), Some(retry)) = (&result, retry) {
+            tracing::warn!("⚠️  Could not parse {} output; retrying once", name);
+            result = provider.invoke(retry).await;
+        }
+        entry.circuit.record(&result);
+
+        #[cfg(feature = "metrics")]
+        if let Some(ref metrics) = self.metrics {
+            match result {
+                Ok(ref response) => metrics.record_success(name, response),
+                Err(ref e) => metrics.record_failure(name, e),
+            }
+        }
+
+        let mut response = result?;
+        response.metadata.provider_version = entry.availability.version().await;
+
+        if let (Some(threshold), Some(cost)) = (self.cost_warning_usd, response.cost_usd) {
+            if cost > threshold {
+                tracing::warn!(
+                    "💸 {} response cost ${:.4}, over the ${:.4} warning threshold (model {})",
+                    name,
+                    cost,
+                    threshold,
+                    response.primary_model
+                );
+                response.metadata.cost_warning = Some(true);
+            }
+        }
+
+        // A reply that misses the schema was still paid for, so count it first
+        entry.usage.record(&response);
+        if let (Some(audit_log), Some(prompt)) = (&self.audit_log, prompt) {
+            audit_log
+                .record(name, &prompt, self.audit_full_prompts, &response)
+                .await;
+        }
+
+        if let Some(ref validator) = schema_validator {
+            schema::validate(validator, &response.content)
+                .map_err(|e| e.with_raw_output(&response.content))?;
+        }
+
+        if let Some(flight) = flight {
+            flight.finish(&response);
+        }
+        if let (Some(cache), Some(key)) = (&self.response_cache, cache_key) {
+            cache.insert(key, response.clone());
+        }
+
+        Ok(response)
+    }
+}
+
+// MCP tools take their parameters as individual arguments
+#[allow(clippy::too_many_arguments)]
+#[turbomcp::server(name = "praxio", version = "0.1.0")]
+impl PraxioServer {
+    /// Invoke Claude CLI for a task with full control over parameters
+    #[tool(description = "Delegate a task to Claude CLI with session continuity, fallback, and timeout control. continue_last resumes the most recent Claude session when no session_id is given. Every resumed call resends the whole history, so cost grows with each turn; max_history_turns caps this by summarizing a longer history (one extra call) and continuing in a new session seeded with the summary, whose ID is returned in metadata.session_id with the old one in metadata.previous_session_id. Detail beyond the summary is lost. thinking_budget caps extended-thinking tokens. stdin_context is piped to the CLI's stdin as extra context (a log, a diff) that the prompt refers to")]
+    async fn invoke_claude(
+        &self,
+        prompt: String,
+        system_prompt: Option<String>,
+        system_prompt_file: Option<String>,
+        append_system_prompt: Option<String>,
+        skip_base_system_prompt: Option<bool>,
+        model: Option<String>,
+        session_id: Option<String>,
+        fallback_model: Option<String>,
+        timeout_seconds: Option<u64>,
+        temperature: Option<f32>,
+        attachments: Option<Vec<String>>,
+        working_dir: Option<String>,
+        skip_permissions: Option<bool>,
+        allowed_tools: Option<Vec<String>>,
+        disallowed_tools: Option<Vec<String>>,
+        return_raw: Option<bool>,
+        budget_usd: Option<f64>,
+        dry_run: Option<bool>,
+        continue_last: Option<bool>,
+        max_history_turns: Option<u32>,
+        env: Option<HashMap<String, String>>,
+        thinking_budget: Option<u32>,
+        skip_context_check: Option<bool>,
+        stdin_context: Option<String>,
+        verbose: Option<bool>,
+        request_id: Option<String>,
+    ) -> McpResult<serde_json::Value> {
+        let request_id = request_id.unwrap_or_else(new_request_id);
+        let span = tracing::info_span!("delegation", request_id = %request_id, provider = "claude", session = tracing::field::Empty);
+
+        async move {
+            // An explicit session_id wins over continue_last
+            let continue_last = continue_last.unwrap_or(false) && session_id.is_none();
+            let resumed_session = match session_id {
+                Some(ref sid) => Some(sid.clone()),
+                None if continue_last => Some(self.last_session("claude").await.ok_or_else(|| {
+                    LlmError::InvalidRequest {
+                        message: "continue_last: no previous Claude session to continue".to_string(),
+                    }
+                })?),
+                None => None,
+            };
+
+            // Determine temp directory for this session
+            let (temp_dir, session_working_dir) = if let Some(ref sid) = resumed_session {
+                // Look up existing session
+                let entry = self.session(sid).await?;
+
+                if budget_usd.is_some() {
+                    return Err(LlmError::InvalidRequest {
+                        message: "budget_usd can only be set when a session is created".to_string(),
+                    }
+                    .into());
+                }
+                entry.check_budget()?;
+
+                tracing::info!(
+                    "Resuming session {}: {}...",
+                    sid.chars().take(8).collect::<String>(),
+                    prompt.chars().take(50).collect::<String>()
+                );
+                (entry.temp_dir, entry.working_dir)
+            } else {
+                // Create new temp directory
+                let dir = self.create_temp_dir("praxio-")?;
+
+                tracing::info!(
+                    "Creating new session: {}...",
+                    prompt.chars().take(50).collect::<String>()
+                );
+                (dir, None)
+            };
+
+            if budget_usd.is_some_and(|budget| !budget.is_finite() || budget < 0.0) {
+                return Err(LlmError::InvalidRequest {
+                    message: "budget_usd must be a non-negative number".to_string(),
+                }
+                .into());
+            }
+
+            // A history past max_history_turns is summarized and carried into a fresh
+            // session in the same directory; a dry run never pays for the summary
+            let dry_run = dry_run.unwrap_or(false);
+            let compacted = self
+                .compact_history(
+                    "claude",
+                    resumed_session.as_deref(),
+                    max_history_turns.filter(|_| !dry_run),
+                    &request_id,
+                )
+                .await?;
+            // The transcript keeps the caller's prompt, not the summary it may be seeded with
+            let caller_prompt = prompt.clone();
+            let (prompt, session_id, resumed_session, continued_from) = match compacted {
+                Some((summary, entry)) => (
+                    seed_with_summary(&summary, &prompt),
+                    None,
+                    None,
+                    resumed_session.map(|sid| (sid, entry)),
+                ),
+                None => (prompt, session_id, resumed_session, None),
+            };
+            let continue_last = continue_last && continued_from.is_none();
+
+            let is_new_session = resumed_session.is_none();
+            let working_dir = working_dir.map(PathBuf::from).or(session_working_dir);
+
+            let request = LlmRequest::builder(prompt)
+                .with_optional(system_prompt, LlmRequestBuilder::with_system_prompt)
+                .with_optional(system_prompt_file, LlmRequestBuilder::with_system_prompt_file)
+                .with_optional(append_system_prompt, LlmRequestBuilder::with_append_system_prompt)
+                .with_skip_base_system_prompt(skip_base_system_prompt.unwrap_or(false))
+                .with_optional(model, LlmRequestBuilder::with_model)
+                .with_optional(session_id, LlmRequestBuilder::with_session_id)
+                .with_continue_last(continue_last)
+                .with_temp_dir(temp_dir.path())
+                .with_optional(working_dir.clone(), LlmRequestBuilder::with_working_dir)
+                .with_optional(fallback_model, LlmRequestBuilder::with_fallback_model)
+                .with_optional(timeout_seconds, LlmRequestBuilder::with_timeout_seconds)
+                .with_optional(temperature, LlmRequestBuilder::with_temperature)
+                .with_optional(thinking_budget, LlmRequestBuilder::with_thinking_budget)
+                .with_skip_permissions(skip_permissions.unwrap_or(self.skip_permissions))
+                .with_optional(allowed_tools, LlmRequestBuilder::with_allowed_tools)
+                .with_optional(disallowed_tools, LlmRequestBuilder::with_disallowed_tools)
+                .with_optional(attachments.map(|paths| paths.into_iter().map(PathBuf::from).collect()), LlmRequestBuilder::with_attachments)
+                .with_optional(stdin_context, LlmRequestBuilder::with_stdin_context)
+                .with_return_raw(return_raw.unwrap_or(false))
+                .with_dry_run(dry_run)
+                .with_skip_context_check(skip_context_check.unwrap_or(false))
+                .with_request_id(request_id.clone())
+                .with_optional(env, LlmRequestBuilder::with_env)
+                .build();
+
+            let start = std::time::Instant::now();
+            let mut response = self.invoke_provider("claude", request).await?;
+            let elapsed = start.elapsed();
+            response.total_duration_ms = Some(elapsed.as_millis() as u64);
+
+            // Store session mapping if this was a new session
+            if is_new_session {
+                if let Some(new_sid) = response.metadata.session_id.clone() {
+                    let entry = match continued_from {
+                        Some((previous_sid, ref previous)) => {
+                            response.metadata.previous_session_id = Some(previous_sid);
+                            previous.continued()
+                        }
+                        None => SessionEntry::new("claude", temp_dir.clone(), working_dir.clone(), budget_usd),
+                    };
+                    self.insert_session(new_sid.clone(), entry).await;
+                    tracing::info!("Mapped session {} → {:?}",
+                        new_sid.chars().take(8).collect::<String>(),
+                        temp_dir.path()
+                    );
+                }
+            }
+
+            // Count the call against the session's totals and budget
+            if !dry_run {
+                if let Some(sid) = resumed_session.or_else(|| response.metadata.session_id.clone()) {
+                    self.record_session_call(&sid, &caller_prompt, &mut response).await;
+                }
+            }
+
+            tracing::info!(
+                "Claude response received in {}ms (API: {}ms)",
+                elapsed.as_millis(),
+                response.api_duration_ms
+            );
+
+            if let Some(cost) = response.cost_usd {
+                tracing::info!("Cost: ${:.6}", cost);
+            }
+
+            if let Some(ref tokens) = response.tokens {
+                tracing::info!(
+                    "Tokens: {} input, {} output, {} total",
+                    tokens.input, tokens.output, tokens.total
+                );
+            }
+
+            Ok(self.response_value(&response, verbose)?)
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Render a prompt template and invoke Claude with the result
+    #[tool(description = "Fill a named prompt template from the server's template directory with vars ({{name}} placeholders) and delegate the result to Claude CLI")]
+    async fn invoke_claude_template(
+        &self,
+        template_name: String,
+        vars: HashMap<String, String>,
+        system_prompt: Option<String>,
+        model: Option<String>,
+        session_id: Option<String>,
+        timeout_seconds: Option<u64>,
+        working_dir: Option<String>,
+        dry_run: Option<bool>,
+        verbose: Option<bool>,
+        request_id: Option<String>,
+    ) -> McpResult<serde_json::Value> {
+        let dir = self.template_dir.as_ref().ok_or_else(|| LlmError::InvalidRequest {
+            message: "no template directory configured (set PRAXIO_TEMPLATE_DIR)".to_string(),
+        })?;
+        let prompt = template::render(&template::load(dir, &template_name)?, &vars)?;
+
+        self.invoke_claude(
+            prompt,
+            system_prompt,
+            None,
+            None,
+            None,
+            model,
+            session_id,
+            None,
+            timeout_seconds,
+            None,
+            None,
+            working_dir,
+            None,
+            None,
+            None,
+            None,
+            None,
+            dry_run,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            verbose,
+            request_id,
+        )
+        .await
+    }
+
+    /// Invoke Gemini CLI for a task with session continuity
+    #[tool(description = "Delegate a task to Gemini CLI with session continuity and timeout control. stdin_context is piped to the CLI's stdin as extra context (a log, a diff) that the prompt refers to")]
+    async fn invoke_gemini(
+        &self,
+        prompt: String,
+        system_prompt: Option<String>,
+        system_prompt_file: Option<String>,
+        skip_base_system_prompt: Option<bool>,
+        model: Option<String>,
+        session_id: Option<String>,
+        timeout_seconds: Option<u64>,
+        temperature: Option<f32>,
+        attachments: Option<Vec<String>>,
+        working_dir: Option<String>,
+        return_raw: Option<bool>,
+        dry_run: Option<bool>,
+        env: Option<HashMap<String, String>>,
+        skip_context_check: Option<bool>,
+        stdin_context: Option<String>,
+        verbose: Option<bool>,
+        request_id: Option<String>,
+    ) -> McpResult<serde_json::Value> {
+        let request_id = request_id.unwrap_or_else(new_request_id);
+        let span = tracing::info_span!("delegation", request_id = %request_id, provider = "gemini", session = tracing::field::Empty);
+
+        async move {
+            // Determine temp directory for this session
+            let (temp_dir, session_working_dir) = if let Some(ref sid) = session_id {
+                // Resume: look up existing session
+                let entry = self.session(sid).await?;
+
+                tracing::info!(
+                    "Resuming Gemini session {}: {}...",
+                    sid.chars().take(8).collect::<String>(),
+                    prompt.chars().take(50).collect::<String>()
+                );
+                (entry.temp_dir, entry.working_dir)
+            } else {
+                // New: create unique temp dir
+                let dir = self.create_temp_dir("praxio-gemini-")?;
+
+                tracing::info!(
+                    "Creating new Gemini session: {}...",
+                    prompt.chars().take(50).collect::<String>()
+                );
+                (dir, None)
+            };
+
+            let is_new_session = session_id.is_none();
+            let dry_run = dry_run.unwrap_or(false);
+            let working_dir = working_dir.map(PathBuf::from).or(session_working_dir);
+
+            let request = LlmRequest::builder(prompt.clone())
+                .with_optional(system_prompt, LlmRequestBuilder::with_system_prompt)
+                .with_optional(system_prompt_file, LlmRequestBuilder::with_system_prompt_file)
+                .with_skip_base_system_prompt(skip_base_system_prompt.unwrap_or(false))
+                .with_optional(model, LlmRequestBuilder::with_model)
+                .with_optional(session_id.clone(), LlmRequestBuilder::with_session_id)
+                .with_temp_dir(temp_dir.path())
+                .with_optional(working_dir.clone(), LlmRequestBuilder::with_working_dir)
+                .with_optional(timeout_seconds, LlmRequestBuilder::with_timeout_seconds)
+                .with_optional(temperature, LlmRequestBuilder::with_temperature)
+                .with_skip_permissions(self.skip_permissions)
+                .with_optional(attachments.map(|paths| paths.into_iter().map(PathBuf::from).collect()), LlmRequestBuilder::with_attachments)
+                .with_optional(stdin_context, LlmRequestBuilder::with_stdin_context)
+                .with_return_raw(return_raw.unwrap_or(false))
+                .with_dry_run(dry_run)
+                .with_skip_context_check(skip_context_check.unwrap_or(false))
+                .with_request_id(request_id.clone())
+                .with_optional(env, LlmRequestBuilder::with_env)
+                .build();
+
+            let start = std::time::Instant::now();
+            let mut response = self.invoke_provider("gemini", request).await?;
+            let elapsed = start.elapsed();
+            response.total_duration_ms = Some(elapsed.as_millis() as u64);
+
+            // Store session mapping if this was a new session
+            if is_new_session {
+                if let Some(ref new_sid) = response.metadata.session_id {
+                    self.insert_session(
+                        new_sid.clone(),
+                        SessionEntry::new("gemini", temp_dir.clone(), working_dir.clone(), None),
+                    )
+                    .await;
+                    tracing::info!("Mapped Gemini session {} → {:?}",
+                        new_sid.chars().take(8).collect::<String>(),
+                        temp_dir.path()
+                    );
+                }
+            }
+
+            if !dry_run {
+                if let Some(sid) = session_id.or_else(|| response.metadata.session_id.clone()) {
+                    self.record_session_call(&sid, &prompt, &mut response).await;
+                }
+            }
+
+            tracing::info!(
+                "Gemini response received in {}ms (API: {}ms)",
+                elapsed.as_millis(),
+                response.api_duration_ms
+            );
+
+            if let Some(cost) = response.cost_usd {
+                tracing::info!("Estimated cost: ${:.6}", cost);
+            }
+
+            if let Some(ref tokens) = response.tokens {
+                tracing::info!(
+                    "Tokens: {} input, {} output, {} total ({} thoughts)",
+                    tokens.input,
+                    tokens.output,
+                    tokens.total,
+                    tokens.extended_thinking.unwrap_or(0)
+                );
+            }
+
+            Ok(self.response_value(&response, verbose)?)
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Invoke OpenAI Codex CLI for a task with session continuity
+    #[tool(description = "Delegate a task to OpenAI models via the Codex CLI with session continuity and timeout control. stdin_context is piped to the CLI's stdin as extra context (a log, a diff) that the prompt refers to")]
+    async fn invoke_openai(
+        &self,
+        prompt: String,
+        system_prompt: Option<String>,
+        system_prompt_file: Option<String>,
+        skip_base_system_prompt: Option<bool>,
+        model: Option<String>,
+        session_id: Option<String>,
+        timeout_seconds: Option<u64>,
+        attachments: Option<Vec<String>>,
+        dry_run: Option<bool>,
+        env: Option<HashMap<String, String>>,
+        skip_context_check: Option<bool>,
+        stdin_context: Option<String>,
+        verbose: Option<bool>,
+        request_id: Option<String>,
+    ) -> McpResult<serde_json::Value> {
+        let request_id = request_id.unwrap_or_else(new_request_id);
+        let span = tracing::info_span!("delegation", request_id = %request_id, provider = "openai", session = tracing::field::Empty);
+
+        async move {
+            // Determine temp directory for this session
+            let (temp_dir, session_working_dir) = if let Some(ref sid) = session_id {
+                // Resume: look up existing session
+                let entry = self.session(sid).await?;
+
+                tracing::info!(
+                    "Resuming OpenAI session {}: {}...",
+                    sid.chars().take(8).collect::<String>(),
+                    prompt.chars().take(50).collect::<String>()
+                );
+                (entry.temp_dir, entry.working_dir)
+            } else {
+                // New: create unique temp dir
+                let dir = self.create_temp_dir("praxio-openai-")?;
+
+                tracing::info!(
+                    "Creating new OpenAI session: {}...",
+                    prompt.chars().take(50).collect::<String>()
+                );
+                (dir, None)
+            };
+
+            let is_new_session = session_id.is_none();
+            let dry_run = dry_run.unwrap_or(false);
+            let working_dir = session_working_dir;
+
+            let request = LlmRequest::builder(prompt.clone())
+                .with_optional(system_prompt, LlmRequestBuilder::with_system_prompt)
+                .with_optional(system_prompt_file, LlmRequestBuilder::with_system_prompt_file)
+                .with_skip_base_system_prompt(skip_base_system_prompt.unwrap_or(false))
+                .with_optional(model, LlmRequestBuilder::with_model)
+                .with_optional(session_id.clone(), LlmRequestBuilder::with_session_id)
+                .with_temp_dir(temp_dir.path())
+                .with_optional(working_dir.clone(), LlmRequestBuilder::with_working_dir)
+                .with_optional(timeout_seconds, LlmRequestBuilder::with_timeout_seconds)
+                .with_skip_permissions(self.skip_permissions)
+                .with_optional(attachments.map(|paths| paths.into_iter().map(PathBuf::from).collect()), LlmRequestBuilder::with_attachments)
+                .with_optional(stdin_context, LlmRequestBuilder::with_stdin_context)
+                .with_dry_run(dry_run)
+                .with_skip_context_check(skip_context_check.unwrap_or(false))
+                .with_request_id(request_id.clone())
+                .with_optional(env, LlmRequestBuilder::with_env)
+                .build();
+
+            let start = std::time::Instant::now();
+            let mut response = self.invoke_provider("openai", request).await?;
+            let elapsed = start.elapsed();
+            response.total_duration_ms = Some(elapsed.as_millis() as u64);
+
+            // Store session mapping if this was a new session
+            if is_new_session {
+                if let Some(ref new_sid) = response.metadata.session_id {
+                    self.insert_session(
+                        new_sid.clone(),
+                        SessionEntry::new("openai", temp_dir.clone(), working_dir.clone(), None),
+                    )
+                    .await;
+                    tracing::info!("Mapped OpenAI session {} → {:?}",
+                        new_sid.chars().take(8).collect::<String>(),
+                        temp_dir.path()
+                    );
+                }
+            }
+
+            if !dry_run {
+                if let Some(sid) = session_id.or_else(|| response.metadata.session_id.clone()) {
+                    self.record_session_call(&sid, &prompt, &mut response).await;
+                }
+            }
+
+            tracing::info!(
+                "OpenAI response received in {}ms (CLI: {}ms)",
+                elapsed.as_millis(),
+                response.api_duration_ms
+            );
+
+            if let Some(ref tokens) = response.tokens {
+                tracing::info!(
+                    "Tokens: {} input, {} output, {} total",
+                    tokens.input, tokens.output, tokens.total
+                );
+            }
+
+            Ok(self.response_value(&response, verbose)?)
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Invoke a local model through the Ollama CLI
+    #[tool(description = "Delegate a task to a local model via the Ollama CLI. Each call is independent: Ollama has no session resume, so include any needed context in the prompt. stdin_context is piped to the CLI's stdin as extra context (a log, a diff) that the prompt refers to")]
+    async fn invoke_ollama(
+        &self,
+        prompt: String,
+        system_prompt: Option<String>,
+        system_prompt_file: Option<String>,
+        model: Option<String>,
+        timeout_seconds: Option<u64>,
+        dry_run: Option<bool>,
+        env: Option<HashMap<String, String>>,
+        stdin_context: Option<String>,
+        verbose: Option<bool>,
+        request_id: Option<String>,
+    ) -> McpResult<serde_json::Value> {
+        let request_id = request_id.unwrap_or_else(new_request_id);
+        let span = tracing::info_span!("delegation", request_id = %request_id, provider = "ollama", session = tracing::field::Empty);
+
+        async move {
+            let temp_dir = self.create_temp_dir("praxio-ollama-")?;
+
+            tracing::info!(
+                "Invoking Ollama: {}...",
+                prompt.chars().take(50).collect::<String>()
+            );
+
+            let request = LlmRequest::builder(prompt)
+                .with_optional(system_prompt, LlmRequestBuilder::with_system_prompt)
+                .with_optional(system_prompt_file, LlmRequestBuilder::with_system_prompt_file)
+                .with_optional(model, LlmRequestBuilder::with_model)
+                .with_output_format(OutputFormat::Text)
+                .with_temp_dir(temp_dir.path())
+                .with_optional(timeout_seconds, LlmRequestBuilder::with_timeout_seconds)
+                .with_skip_permissions(self.skip_permissions)
+                .with_optional(stdin_context, LlmRequestBuilder::with_stdin_context)
+                .with_dry_run(dry_run.unwrap_or(false))
+                .with_request_id(request_id.clone())
+                .with_optional(env, LlmRequestBuilder::with_env)
+                .build();
+
+            let start = std::time::Instant::now();
+            let mut response = self.invoke_provider("ollama", request).await?;
+            let elapsed = start.elapsed();
+            response.total_duration_ms = Some(elapsed.as_millis() as u64);
+
+            tracing::info!(
+                "Ollama response received in {}ms (model: {}ms)",
+                elapsed.as_millis(),
+                response.api_duration_ms
+            );
+
+            if let Some(ref tokens) = response.tokens {
+                tracing::info!(
+                    "Tokens: {} input, {} output, {} total",
+                    tokens.input, tokens.output, tokens.total
+                );
+            }
+
+            Ok(self.response_value(&response, verbose)?)
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Invoke any registered provider by name
+    #[tool(description = "Delegate a task to the named provider (e.g. claude, gemini, openai, ollama) with session continuity. deadline_unix_ms caps the run so it ends by that time. max_history_turns bounds the cost of a long session, which grows with every resumed turn: a longer history is summarized (one extra call) into a new session, returned in metadata.session_id with the old one in metadata.previous_session_id; providers without sessions reject it. Provider-specific options (append_system_prompt, skip_base_system_prompt, fallback_model, temperature, skip_permissions, allowed_tools, disallowed_tools, return_raw, env, thinking_budget, skip_context_check, stdin_context) go in extra, as does extra_args, raw CLI arguments appended after the ones Praxio sets (they may conflict with them), and binary, a program to run instead of the usual CLI where the server allows it")]
+    async fn invoke(
+        &self,
+        provider: String,
+        prompt: String,
+        system_prompt: Option<String>,
+        system_prompt_file: Option<String>,
+        model: Option<String>,
+        session_id: Option<String>,
+        timeout_seconds: Option<u64>,
+        attachments: Option<Vec<String>>,
+        working_dir: Option<String>,
+        dry_run: Option<bool>,
+        deadline_unix_ms: Option<u64>,
+        max_history_turns: Option<u32>,
+        extra: Option<serde_json::Value>,
+        verbose: Option<bool>,
+        request_id: Option<String>,
+    ) -> McpResult<serde_json::Value> {
+        let request_id = request_id.unwrap_or_else(new_request_id);
+        let span = tracing::info_span!("delegation", request_id = %request_id, provider = %provider, session = tracing::field::Empty);
+
+        async move {
+            // Fail on unknown providers before touching session state
+            self.provider_entry(&provider)?;
+            let timeout_seconds = timeout_for_deadline(deadline_unix_ms, timeout_seconds)?;
+
+            let extra: InvokeExtra = match extra {
+                Some(value) => serde_json::from_value(value).map_err(|e| LlmError::InvalidRequest {
+                    message: format!("invalid extra options: {}", e),
+                })?,
+                None => InvokeExtra::default(),
+            };
+
+            // Determine temp directory for this session
+            let (temp_dir, session_working_dir) = if let Some(ref sid) = session_id {
+                let entry = self.session(sid).await?;
+                entry.check_budget()?;
+
+                tracing::info!(
+                    "Resuming {} session {}: {}...",
+                    provider,
+                    sid.chars().take(8).collect::<String>(),
+                    prompt.chars().take(50).collect::<String>()
+                );
+                (entry.temp_dir, entry.working_dir)
+            } else {
+                let dir = self.create_temp_dir(&format!("praxio-{}-", provider))?;
+
+                tracing::info!(
+                    "Creating new {} session: {}...",
+                    provider,
+                    prompt.chars().take(50).collect::<String>()
+                );
+                (dir, None)
+            };
+
+            // Same history limit as invoke_claude; the summary seeds a fresh session
+            let dry_run = dry_run.unwrap_or(false);
+            let compacted = self
+                .compact_history(
+                    &provider,
+                    session_id.as_deref(),
+                    max_history_turns.filter(|_| !dry_run),
+                    &request_id,
+                )
+                .await?;
+            // The transcript keeps the caller's prompt, not the summary it may be seeded with
+            let caller_prompt = prompt.clone();
+            let (prompt, session_id, continued_from) = match compacted {
+                Some((summary, entry)) => (
+                    seed_with_summary(&summary, &prompt),
+                    None,
+                    session_id.map(|sid| (sid, entry)),
+                ),
+                None => (prompt, session_id, None),
+            };
+
+            let is_new_session = session_id.is_none();
+            let working_dir = working_dir.map(PathBuf::from).or(session_working_dir);
+
+            let request = LlmRequest::builder(prompt)
+                .with_optional(system_prompt, LlmRequestBuilder::with_system_prompt)
+                .with_optional(system_prompt_file, LlmRequestBuilder::with_system_prompt_file)
+                .with_optional(extra.append_system_prompt, LlmRequestBuilder::with_append_system_prompt)
+                .with_skip_base_system_prompt(extra.skip_base_system_prompt.unwrap_or(false))
+                .with_optional(model, LlmRequestBuilder::with_model)
+                .with_optional(session_id.clone(), LlmRequestBuilder::with_session_id)
+                .with_temp_dir(temp_dir.path())
+                .with_optional(working_dir.clone(), LlmRequestBuilder::with_working_dir)
+                .with_optional(extra.fallback_model, LlmRequestBuilder::with_fallback_model)
+                .with_optional(timeout_seconds, LlmRequestBuilder::with_timeout_seconds)
+                .with_optional(extra.temperature, LlmRequestBuilder::with_temperature)
+                .with_optional(extra.thinking_budget, LlmRequestBuilder::with_thinking_budget)
+                .with_skip_permissions(extra.skip_permissions.unwrap_or(self.skip_permissions))
+                .with_optional(extra.allowed_tools, LlmRequestBuilder::with_allowed_tools)
+                .with_optional(extra.disallowed_tools, LlmRequestBuilder::with_disallowed_tools)
+                .with_optional(attachments.map(|paths| paths.into_iter().map(PathBuf::from).collect()), LlmRequestBuilder::with_attachments)
+                .with_optional(extra.stdin_context, LlmRequestBuilder::with_stdin_context)
+                .with_return_raw(extra.return_raw.unwrap_or(false))
+                .with_dry_run(dry_run)
+                .with_skip_context_check(extra.skip_context_check.unwrap_or(false))
+                .with_request_id(request_id.clone())
+                .with_optional(extra.env, LlmRequestBuilder::with_env)
+                .with_optional(extra.extra_args, LlmRequestBuilder::with_extra_args)
+                .with_optional(extra.binary, LlmRequestBuilder::with_binary)
+                .build();
 
-        // Check provider availability
-        match claude.check_availability().await {
-            ProviderAvailability::Available => {
-                tracing::info!("✅ Claude provider available");
+            let start = std::time::Instant::now();
+            let mut response = self.invoke_provider(&provider, request).await?;
+            let elapsed = start.elapsed();
+            response.total_duration_ms = Some(elapsed.as_millis() as u64);
+
+            // Store session mapping if this was a new session
+            if is_new_session {
+                if let Some(new_sid) = response.metadata.session_id.clone() {
+                    let entry = match continued_from {
+                        Some((previous_sid, ref previous)) => {
+                            response.metadata.previous_session_id = Some(previous_sid);
+                            previous.continued()
+                        }
+                        None => SessionEntry::new(&provider, temp_dir.clone(), working_dir.clone(), None),
+                    };
+                    self.insert_session(new_sid, entry).await;
+                }
             }
-            ProviderAvailability::Unavailable { reason } => {
-                tracing::warn!("⚠️  Claude provider unavailable: {}", reason);
+
+            // Keep the session's totals, and any budget set through invoke_claude, accurate
+            if !dry_run {
+                if let Some(sid) = session_id.or_else(|| response.metadata.session_id.clone()) {
+                    self.record_session_call(&sid, &caller_prompt, &mut response).await;
+                }
             }
+
+            tracing::info!(
+                "{} response received in {}ms (CLI: {}ms)",
+                provider,
+                elapsed.as_millis(),
+                response.api_duration_ms
+            );
+
+            Ok(self.response_value(&response, verbose)?)
         }
+        .instrument(span)
+        .await
+    }
+
+    /// Invoke providers in order until one succeeds, or race them all
+    #[tool(description = "Delegate a task to the first provider in provider_order that succeeds, falling back to the next on unavailability, timeout, rate limiting, or an overloaded model; degraded providers are tried after healthy ones. With race=true every provider starts at once, the first success wins, and the rest are cancelled (lower latency, higher cost); the response reports served_by and cancelled. deadline_unix_ms bounds all attempts together")]
+    async fn invoke_with_fallback(
+        &self,
+        provider_order: Vec<String>,
+        prompt: String,
+        system_prompt: Option<String>,
+        timeout_seconds: Option<u64>,
+        deadline_unix_ms: Option<u64>,
+        race: Option<bool>,
+        verbose: Option<bool>,
+        request_id: Option<String>,
+    ) -> McpResult<serde_json::Value> {
+        let request_id = request_id.unwrap_or_else(new_request_id);
+        let span = tracing::info_span!("delegation", request_id = %request_id);
 
-        match gemini.check_availability().await {
-            ProviderAvailability::Available => {
-                tracing::info!("✅ Gemini provider available");
+        async move {
+            if provider_order.is_empty() {
+                return Err(LlmError::InvalidRequest {
+                    message: "provider_order must name at least one provider".to_string(),
+                }
+                .into());
             }
-            ProviderAvailability::Unavailable { reason } => {
-                tracing::warn!("⚠️  Gemini provider unavailable: {}", reason);
+
+            let request_for = |temp_dir: &TempDir, timeout_seconds: Option<u64>| {
+                LlmRequest::builder(prompt.clone())
+                    .with_optional(system_prompt.clone(), LlmRequestBuilder::with_system_prompt)
+                    .with_temp_dir(temp_dir.path())
+                    .with_optional(timeout_seconds, LlmRequestBuilder::with_timeout_seconds)
+                    .with_skip_permissions(self.skip_permissions)
+                    .with_request_id(request_id.clone())
+                    .build()
+            };
+
+            let all_failed = |attempts: &[serde_json::Value]| {
+                let summary = attempts
+                    .iter()
+                    .map(|a| format!("{}: {}", a["provider"].as_str().unwrap_or("?"), a["error"].as_str().unwrap_or("?")))
+                    .collect::<Vec<_>>()
+                    .join("; ");
+
+                LlmError::ProviderUnavailable {
+                    provider: provider_order.join(", "),
+                    reason: format!("all providers failed ({})", summary),
+                }
+            };
+
+            let mut attempts = Vec::new();
+
+            if race.unwrap_or(false) {
+                let timeout_seconds = timeout_for_deadline(deadline_unix_ms, timeout_seconds)?;
+                let start = std::time::Instant::now();
+                let mut runs = futures::stream::FuturesUnordered::new();
+
+                for name in &provider_order {
+                    let temp_dir = self.create_temp_dir(&format!("praxio-{}-", name))?;
+                    let request = request_for(&temp_dir, timeout_seconds);
+                    let attempt_span = tracing::info_span!(
+                        "attempt",
+                        provider = name.as_str(),
+                        session = tracing::field::Empty
+                    );
+                    runs.push(
+                        async move { (name, temp_dir, self.invoke_provider(name, request).await) }
+                            .instrument(attempt_span),
+                    );
+                }
+
+                while let Some((name, temp_dir, result)) = runs.next().await {
+                    let elapsed = start.elapsed();
+                    match result {
+                        Ok(mut response) => {
+                            // Dropping the losers' futures kills their CLIs
+                            let cancelled = runs.len();
+                            drop(runs);
+
+                            response.total_duration_ms = Some(elapsed.as_millis() as u64);
+                            if let Some(new_sid) = response.metadata.session_id.clone() {
+                                self.insert_session(new_sid.clone(), SessionEntry::new(name, temp_dir, None, None))
+                                    .await;
+                                self.record_session_call(&new_sid, &prompt, &mut response).await;
+                            }
+
+                            tracing::info!(
+                                "{} won the race in {}ms; {} failed, {} cancelled",
+                                name,
+                                elapsed.as_millis(),
+                                attempts.len(),
+                                cancelled
+                            );
+
+                            let mut value = self.response_value(&response, verbose)?;
+                            value["served_by"] = serde_json::json!(name);
+                            value["attempts"] = serde_json::json!(attempts);
+                            value["cancelled"] = serde_json::json!(cancelled);
+                            return Ok(value);
+                        }
+                        Err(e) => {
+                            tracing::warn!("⚠️  {} failed during race: {}", name, e);
+                            attempts.push(serde_json::json!({
+                                "provider": name,
+                                "error": e.to_string(),
+                                "duration_ms": elapsed.as_millis() as u64,
+                            }));
+                        }
+                    }
+                }
+
+                return Err(all_failed(&attempts).into());
             }
-        }
 
-        Self {
-            claude,
-            gemini,
-            sessions: Arc::new(RwLock::new(HashMap::new())),
+            // Degraded providers are tried after healthy ones, otherwise in the caller's order
+            let (healthy, degraded): (Vec<&String>, Vec<&String>) = provider_order.iter().partition(|name| {
+                match self.providers.get(name.as_str()).and_then(|entry| entry.circuit.degraded()) {
+                    Some(reason) => {
+                        tracing::info!("{} is degraded ({}); trying it after healthy providers", name, reason);
+                        false
+                    }
+                    None => true,
+                }
+            });
+
+            for name in healthy.into_iter().chain(degraded) {
+                // Each attempt gets only what's left before the deadline
+                let timeout_seconds = match timeout_for_deadline(deadline_unix_ms, timeout_seconds) {
+                    Ok(timeout_seconds) => timeout_seconds,
+                    Err(e) if attempts.is_empty() => return Err(e.into()),
+                    Err(e) => {
+                        attempts.push(serde_json::json!({
+                            "provider": name,
+                            "error": e.to_string(),
+                            "duration_ms": 0,
+                        }));
+                        break;
+                    }
+                };
+                let start = std::time::Instant::now();
+                let temp_dir = self.create_temp_dir(&format!("praxio-{}-", name))?;
+                let request = request_for(&temp_dir, timeout_seconds);
+
+                let attempt_span = tracing::info_span!(
+                    "attempt",
+                    provider = name.as_str(),
+                    session = tracing::field::Empty
+                );
+                let result = self.invoke_provider(name, request).instrument(attempt_span).await;
+                let elapsed = start.elapsed();
+
+                match result {
+                    Ok(mut response) => {
+                        response.total_duration_ms = Some(elapsed.as_millis() as u64);
+                        if let Some(new_sid) = response.metadata.session_id.clone() {
+                            self.insert_session(new_sid.clone(), SessionEntry::new(name, temp_dir, None, None))
+                                .await;
+                            self.record_session_call(&new_sid, &prompt, &mut response).await;
+                        }
+
+                        tracing::info!(
+                            "{} served request in {}ms after {} failed attempt(s)",
+                            name,
+                            elapsed.as_millis(),
+                            attempts.len()
+                        );
+
+                        let mut value = self.response_value(&response, verbose)?;
+                        value["served_by"] = serde_json::json!(name);
+                        value["attempts"] = serde_json::json!(attempts);
+                        return Ok(value);
+                    }
+                    Err(e) if e.is_fallback_eligible() => {
+                        tracing::warn!("⚠️  {} failed, falling back: {}", name, e);
+                        attempts.push(serde_json::json!({
+                            "provider": name,
+                            "error": e.to_string(),
+                            "duration_ms": elapsed.as_millis() as u64,
+                        }));
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+
+            Err(all_failed(&attempts).into())
         }
+        .instrument(span)
+        .await
     }
-}
 
-#[turbomcp::server(name = "praxio", version = "0.1.0")]
-impl PraxioServer {
-    /// Invoke Claude CLI for a task with full control over parameters
-    #[tool(description = "Delegate a task to Claude CLI with session continuity, fallback, and timeout control")]
-    async fn invoke_claude(
+    /// Run one prompt on several providers at once and return every outcome
+    #[tool(description = "Send the same prompt to several providers concurrently and return their answers side by side, each as { provider, response or error, duration_ms, cost_usd }. Defaults to every available provider. One provider failing does not affect the others")]
+    async fn compare_providers(
         &self,
         prompt: String,
+        providers: Option<Vec<String>>,
         system_prompt: Option<String>,
-        model: Option<String>,
-        session_id: Option<String>,
-        fallback_model: Option<String>,
         timeout_seconds: Option<u64>,
+        request_id: Option<String>,
     ) -> McpResult<serde_json::Value> {
-        // Determine temp directory for this session
-        let temp_dir = if let Some(ref sid) = session_id {
-            // Look up existing session
-            let sessions = self.sessions.read().await;
-            let dir = sessions.get(sid).cloned().ok_or_else(|| {
-                McpError::from(ServerError::Internal(
-                    format!("Session not found: {}", sid)
-                ))
-            })?;
+        let request_id = request_id.unwrap_or_else(new_request_id);
+        let providers = match providers {
+            Some(providers) => providers,
+            None => {
+                let mut available = Vec::new();
+                for name in self.providers.keys() {
+                    if let Ok(ProviderAvailability::Available { .. } | ProviderAvailability::Degraded { .. }) =
+                        self.provider_availability(name, false).await
+                    {
+                        available.push(name.clone());
+                    }
+                }
+                available.sort();
+                available
+            }
+        };
 
-            tracing::info!(
-                "Resuming session {}: {}...",
-                sid.chars().take(8).collect::<String>(),
-                prompt.chars().take(50).collect::<String>()
-            );
-            dir
-        } else {
-            // Create new temp directory
-            let new_id = uuid::Uuid::new_v4();
-            let dir = std::env::temp_dir().join(format!("praxio-{}", new_id));
+        if providers.is_empty() {
+            return Err(LlmError::InvalidRequest {
+                message: "no providers to compare: none named and none available".to_string(),
+            }
+            .into());
+        }
 
-            tracing::info!(
-                "Creating new session: {}...",
-                prompt.chars().take(50).collect::<String>()
+        let runs = providers.iter().map(|name| {
+            let temp_dir = self.create_temp_dir(&format!("praxio-{}-", name));
+            let request = LlmRequest::builder(prompt.clone())
+                .with_optional(system_prompt.clone(), LlmRequestBuilder::with_system_prompt)
+                .with_optional(temp_dir.as_ref().ok().map(|dir| dir.path()), LlmRequestBuilder::with_temp_dir)
+                .with_optional(timeout_seconds, LlmRequestBuilder::with_timeout_seconds)
+                .with_skip_permissions(self.skip_permissions)
+                .with_request_id(request_id.clone())
+                .build();
+
+            let span = tracing::info_span!(
+                "delegation",
+                request_id = %request_id,
+                provider = name.as_str(),
+                session = tracing::field::Empty
             );
-            dir
-        };
 
-        let is_new_session = session_id.is_none();
+            let prompt = prompt.as_str();
+            async move {
+                let start = std::time::Instant::now();
+                let result = match temp_dir {
+                    Ok(temp_dir) => self
+                        .invoke_provider(name, request)
+                        .await
+                        .map(|response| (response, temp_dir)),
+                    Err(e) => Err(e),
+                };
+                let duration_ms = start.elapsed().as_millis() as u64;
 
-        let request = LlmRequest {
-            prompt,
-            system_prompt,
-            model,
-            output_format: OutputFormat::Json,
-            max_tokens: None,
-            session_id,
-            temp_dir: Some(temp_dir.clone()),
-            fallback_model,
-            timeout_seconds,
-        };
+                match result {
+                    Ok((mut response, temp_dir)) => {
+                        response.total_duration_ms = Some(duration_ms);
+                        // Keep each answer resumable so the winner can be followed up
+                        if let Some(new_sid) = response.metadata.session_id.clone() {
+                            self.insert_session(new_sid.clone(), SessionEntry::new(name, temp_dir, None, None))
+                                .await;
+                            self.record_session_call(&new_sid, prompt, &mut response).await;
+                        }
+
+                        serde_json::json!({
+                            "provider": name,
+                            "response": response,
+                            "duration_ms": duration_ms,
+                            "cost_usd": response.cost_usd,
+                        })
+                    }
+                    Err(e) => {
+                        tracing::warn!("⚠️  {} failed during comparison: {}", name, e);
+                        serde_json::json!({
+                            "provider": name,
+                            "error": e.to_string(),
+                            "duration_ms": duration_ms,
+                            "cost_usd": null,
+                        })
+                    }
+                }
+            }
+            .instrument(span)
+        });
 
-        let start = std::time::Instant::now();
-        let response = self.claude.invoke(request).await?;
-        let elapsed = start.elapsed();
+        let results = futures::future::join_all(runs).await;
+        Ok(serde_json::json!({ "results": results }))
+    }
 
-        // Store session mapping if this was a new session
-        if is_new_session {
-            if let Some(ref new_sid) = response.metadata.session_id {
-                let mut sessions = self.sessions.write().await;
-                sessions.insert(new_sid.clone(), temp_dir.clone());
-                tracing::info!("Mapped session {} → {:?}",
-                    new_sid.chars().take(8).collect::<String>(),
-                    temp_dir
-                );
+    /// Run many prompts on one provider concurrently and return every outcome
+    #[tool(description = "Run several independent prompts on one provider concurrently (at most max_concurrency at a time, default the server's limit) and return one result per prompt in input order, each as { index, response or error, duration_ms, cost_usd }, plus total_cost_usd. A failed prompt does not affect the others. Items are not resumable sessions. Cancelling the request_id cancels the whole batch")]
+    async fn invoke_batch(
+        &self,
+        provider: String,
+        prompts: Vec<String>,
+        max_concurrency: Option<usize>,
+        system_prompt: Option<String>,
+        model: Option<String>,
+        timeout_seconds: Option<u64>,
+        request_id: Option<String>,
+    ) -> McpResult<serde_json::Value> {
+        let request_id = request_id.unwrap_or_else(new_request_id);
+        if prompts.is_empty() {
+            return Err(LlmError::InvalidRequest {
+                message: "prompts must not be empty".to_string(),
             }
+            .into());
         }
+        self.provider_entry(&provider)?;
 
-        tracing::info!(
-            "Claude response received in {}ms (API: {}ms)",
-            elapsed.as_millis(),
-            response.duration_ms
-        );
+        // The server-wide limit still applies on top of the batch's own
+        let limit = Arc::new(Semaphore::new(
+            max_concurrency.unwrap_or(self.max_concurrency).max(1),
+        ));
 
-        if let Some(cost) = response.cost_usd {
-            tracing::info!("Cost: ${:.6}", cost);
-        }
+        let runs = prompts.into_iter().enumerate().map(|(index, prompt)| {
+            let limit = limit.clone();
+            let provider = provider.as_str();
+            let system_prompt = system_prompt.clone();
+            let model = model.clone();
+            let request_id = request_id.clone();
+            let span = tracing::info_span!(
+                "delegation",
+                request_id = %request_id,
+                provider,
+                batch_index = index,
+                session = tracing::field::Empty
+            );
+
+            async move {
+                let _permit = limit.acquire().await.expect("batch semaphore is never closed");
+                let start = std::time::Instant::now();
+                let result = match self.create_temp_dir(&format!("praxio-{}-", provider)) {
+                    Ok(temp_dir) => {
+                        let request = LlmRequest::builder(prompt)
+                            .with_optional(system_prompt, LlmRequestBuilder::with_system_prompt)
+                            .with_optional(model, LlmRequestBuilder::with_model)
+                            .with_temp_dir(temp_dir.path())
+                            .with_optional(timeout_seconds, LlmRequestBuilder::with_timeout_seconds)
+                            .with_skip_permissions(self.skip_permissions)
+                            .with_request_id(request_id)
+                            .build();
+                        self.invoke_provider(provider, request).await
+                    }
+                    Err(e) => Err(e),
+                };
+                let duration_ms = start.elapsed().as_millis() as u64;
+
+                match result {
+                    Ok(mut response) => {
+                        // The item's temp dir is gone once it returns, so its session can't be resumed
+                        response.metadata.session_id = None;
+                        response.total_duration_ms = Some(duration_ms);
+                        serde_json::json!({
+                            "index": index,
+                            "response": response,
+                            "duration_ms": duration_ms,
+                            "cost_usd": response.cost_usd,
+                        })
+                    }
+                    Err(e) => {
+                        tracing::warn!("⚠️  Batch item {} failed: {}", index, e);
+                        serde_json::json!({
+                            "index": index,
+                            "error": e.to_string(),
+                            "duration_ms": duration_ms,
+                            "cost_usd": null,
+                        })
+                    }
+                }
+            }
+            .instrument(span)
+        });
+
+        let results = futures::future::join_all(runs).await;
+        let total_cost_usd: f64 = results
+            .iter()
+            .filter_map(|result| result["cost_usd"].as_f64())
+            .sum();
+        Ok(serde_json::json!({
+            "results": results,
+            "total_cost_usd": total_cost_usd,
+        }))
+    }
+
+    /// Resume one session until the model signals it is done
+    #[tool(description = "Run an agentic loop on one provider: start a session with initial_prompt, then keep resuming it with continue_prompt (default \"Continue.\") until a response contains stop_marker or max_turns (default 10) responses have been received. Returns { session_id, stop_reason (stop_marker, max_turns, budget_exhausted, session_expired, or error), turns, transcript, tokens, total_cost_usd, error }, where transcript lists each turn's prompt, content, cost_usd, and duration_ms. budget_usd caps the session's spend like invoke_claude's; timeout_seconds applies to each turn. A failed turn ends the loop but keeps the turns before it. The session stays resumable afterwards. Providers without sessions are rejected")]
+    async fn invoke_until(
+        &self,
+        provider: String,
+        initial_prompt: String,
+        continue_prompt: Option<String>,
+        stop_marker: String,
+        max_turns: Option<u32>,
+        system_prompt: Option<String>,
+        model: Option<String>,
+        working_dir: Option<String>,
+        timeout_seconds: Option<u64>,
+        budget_usd: Option<f64>,
+        request_id: Option<String>,
+    ) -> McpResult<serde_json::Value> {
+        let request_id = request_id.unwrap_or_else(new_request_id);
+        let span = tracing::info_span!("delegation", request_id = %request_id, provider = %provider, session = tracing::field::Empty);
+
+        async move {
+            let entry = self.provider_entry(&provider)?;
+            if !entry.provider.capabilities().supports_sessions {
+                return Err(LlmError::InvalidRequest {
+                    message: format!("{} can't resume sessions, so it can't be looped", provider),
+                }
+                .into());
+            }
+            if stop_marker.is_empty() {
+                return Err(LlmError::InvalidRequest {
+                    message: "stop_marker must not be empty".to_string(),
+                }
+                .into());
+            }
+            if budget_usd.is_some_and(|budget| !budget.is_finite() || budget < 0.0) {
+                return Err(LlmError::InvalidRequest {
+                    message: "budget_usd must be a non-negative number".to_string(),
+                }
+                .into());
+            }
+            let max_turns = max_turns.unwrap_or(DEFAULT_UNTIL_MAX_TURNS).max(1);
+            let continue_prompt = continue_prompt.unwrap_or_else(|| DEFAULT_CONTINUE_PROMPT.to_string());
+
+            let temp_dir = self.create_temp_dir(&format!("praxio-{}-", provider))?;
+            let working_dir = working_dir.map(PathBuf::from);
+
+            let mut session_id: Option<String> = None;
+            let mut transcript = Vec::new();
+            let mut tokens = TokenUsage::default();
+            let mut total_cost_usd = 0.0;
+            let mut stop_reason = "max_turns";
+            let mut error = None;
+
+            for turn in 1..=max_turns {
+                if let Some(ref sid) = session_id {
+                    let checked = match self.session(sid).await {
+                        Ok(entry) => entry.check_budget().map_err(|e| ("budget_exhausted", e)),
+                        // Expired (or forgotten) between turns; resuming would start over
+                        Err(e) => Err(("session_expired", e)),
+                    };
+                    if let Err((reason, e)) = checked {
+                        stop_reason = reason;
+                        error = Some(e.to_string());
+                        break;
+                    }
+                }
+
+                let prompt = if turn == 1 { initial_prompt.clone() } else { continue_prompt.clone() };
+                let request = LlmRequest::builder(prompt.clone())
+                    .with_optional(system_prompt.clone(), LlmRequestBuilder::with_system_prompt)
+                    .with_optional(model.clone(), LlmRequestBuilder::with_model)
+                    .with_optional(session_id.clone(), LlmRequestBuilder::with_session_id)
+                    .with_temp_dir(temp_dir.path())
+                    .with_optional(working_dir.clone(), LlmRequestBuilder::with_working_dir)
+                    .with_optional(timeout_seconds, LlmRequestBuilder::with_timeout_seconds)
+                    .with_skip_permissions(self.skip_permissions)
+                    .with_request_id(request_id.clone())
+                    .build();
+
+                let start = std::time::Instant::now();
+                let mut response = match self.invoke_provider(&provider, request).await {
+                    Ok(response) => response,
+                    Err(e) if turn == 1 => return Err(e.into()),
+                    Err(e) => {
+                        tracing::warn!("⚠️  {} turn {} failed: {}", provider, turn, e);
+                        stop_reason = "error";
+                        error = Some(e.to_string());
+                        break;
+                    }
+                };
+                let duration_ms = start.elapsed().as_millis() as u64;
+                response.total_duration_ms = Some(duration_ms);
+
+                if session_id.is_none() {
+                    let Some(new_sid) = response.metadata.session_id.clone() else {
+                        return Err(LlmError::InvalidRequest {
+                            message: format!("{} returned no session to resume", provider),
+                        }
+                        .into());
+                    };
+                    let entry = SessionEntry::new(&provider, temp_dir.clone(), working_dir.clone(), budget_usd);
+                    self.insert_session(new_sid.clone(), entry).await;
+                    tracing::Span::current().record("session", new_sid.as_str());
+                    session_id = Some(new_sid);
+                }
+                if let Some(ref sid) = session_id {
+                    self.record_session_call(sid, &prompt, &mut response).await;
+                }
+
+                if let Some(ref usage) = response.tokens {
+                    tokens.input = tokens.input.saturating_add(usage.input);
+                    tokens.output = tokens.output.saturating_add(usage.output);
+                    tokens.total = tokens.total.saturating_add(usage.total);
+                    tokens.cache_creation = tokens.cache_creation.saturating_add(usage.cache_creation);
+                    tokens.cache_read = tokens.cache_read.saturating_add(usage.cache_read);
+                }
+                total_cost_usd += response.cost_usd.unwrap_or(0.0);
+
+                let done = response.content.contains(&stop_marker);
+                transcript.push(serde_json::json!({
+                    "turn": turn,
+                    "prompt": prompt,
+                    "content": response.content,
+                    "cost_usd": response.cost_usd,
+                    "duration_ms": duration_ms,
+                }));
+                if done {
+                    stop_reason = "stop_marker";
+                    break;
+                }
+            }
 
-        if let Some(ref tokens) = response.tokens {
             tracing::info!(
-                "Tokens: {} input, {} output, {} total",
-                tokens.input, tokens.output, tokens.total
+                "{} loop stopped after {} turn(s): {}",
+                provider,
+                transcript.len(),
+                stop_reason
             );
-        }
 
-        Ok(serde_json::to_value(&response)?)
+            Ok(serde_json::json!({
+                "session_id": session_id,
+                "stop_reason": stop_reason,
+                "turns": transcript.len(),
+                "transcript": transcript,
+                "tokens": tokens,
+                "total_cost_usd": total_cost_usd,
+                "error": error,
+            }))
+        }
+        .instrument(span)
+        .await
     }
 
-    /// Invoke Gemini CLI for a task with session continuity
-    #[tool(description = "Delegate a task to Gemini CLI with session continuity and timeout control")]
-    async fn invoke_gemini(
+    /// Invoke a provider and require its answer to match a JSON Schema
+    #[tool(description = "Delegate a task and get back JSON that conforms to schema (a JSON Schema). The provider (default claude) is told to answer with matching JSON, and the answer is validated; the parsed value is returned as structured. A non-conforming answer is an error unless retry=true, which asks once more with the validation errors fed back")]
+    async fn invoke_structured(
         &self,
         prompt: String,
+        schema: serde_json::Value,
+        provider: Option<String>,
         system_prompt: Option<String>,
         model: Option<String>,
-        session_id: Option<String>,
         timeout_seconds: Option<u64>,
+        retry: Option<bool>,
+        verbose: Option<bool>,
+        request_id: Option<String>,
     ) -> McpResult<serde_json::Value> {
-        // Determine temp directory for this session
-        let temp_dir = if let Some(ref sid) = session_id {
-            // Resume: look up existing session
-            let sessions = self.sessions.read().await;
-            let dir = sessions.get(sid).cloned().ok_or_else(|| {
-                McpError::from(ServerError::Internal(
-                    format!("Session not found: {}", sid)
-                ))
+        let request_id = request_id.unwrap_or_else(new_request_id);
+        let span = tracing::info_span!("delegation", request_id = %request_id, provider = provider.as_deref().unwrap_or("claude"), session = tracing::field::Empty);
+
+        async move {
+            let provider = provider.unwrap_or_else(|| "claude".to_string());
+            let mut attempt_prompt = prompt.clone();
+            let mut retries_left = u32::from(retry.unwrap_or(false));
+
+            loop {
+                let temp_dir = self.create_temp_dir(&format!("praxio-{}-", provider))?;
+                let request = LlmRequest::builder(attempt_prompt.clone())
+                    .with_optional(system_prompt.clone(), LlmRequestBuilder::with_system_prompt)
+                    .with_optional(model.clone(), LlmRequestBuilder::with_model)
+                    .with_temp_dir(temp_dir.path())
+                    .with_optional(timeout_seconds, LlmRequestBuilder::with_timeout_seconds)
+                    .with_skip_permissions(self.skip_permissions)
+                    .with_response_schema(schema.clone())
+                    .with_request_id(request_id.clone())
+                    .build();
+
+                match self.invoke_provider(&provider, request).await {
+                    Ok(mut response) => {
+                        if let Some(new_sid) = response.metadata.session_id.clone() {
+                            self.insert_session(new_sid.clone(), SessionEntry::new(&provider, temp_dir, None, None))
+                                .await;
+                            self.record_session_call(&new_sid, &attempt_prompt, &mut response).await;
+                        }
+
+                        let structured: serde_json::Value =
+                            serde_json::from_str(schema::extract_json(&response.content))?;
+                        let mut value = self.response_value(&response, verbose)?;
+                        value["structured"] = structured;
+                        return Ok(value);
+                    }
+                    Err(e) if retries_left > 0 && schema::is_mismatch(&e) => {
+                        retries_left -= 1;
+                        tracing::warn!("⚠️  {} answer did not match the schema, retrying: {}", provider, e);
+                        attempt_prompt = format!(
+                            "{}\n\nYour previous answer was rejected: {}\n\nAnswer again with only JSON that conforms to the schema.",
+                            prompt, e
+                        );
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Invoke a provider with a chat-style conversation instead of a single prompt
+    #[tool(description = "Delegate a conversation given as messages, a list of { role, content } with role system, user, or assistant, the way chat-completion APIs take it. System messages come first, then user and assistant messages alternate, ending with the user message to answer; anything else is rejected. CLIs that take a single prompt receive the earlier turns written out with role markers. The reply starts a new session that invoke can resume")]
+    async fn invoke_messages(
+        &self,
+        provider: String,
+        messages: serde_json::Value,
+        model: Option<String>,
+        timeout_seconds: Option<u64>,
+        verbose: Option<bool>,
+        request_id: Option<String>,
+    ) -> McpResult<serde_json::Value> {
+        let request_id = request_id.unwrap_or_else(new_request_id);
+        let span = tracing::info_span!("delegation", request_id = %request_id, provider = provider.as_str(), session = tracing::field::Empty);
+
+        async move {
+            let messages: Vec<Message> = serde_json::from_value(messages).map_err(|e| LlmError::InvalidRequest {
+                message: format!("invalid messages: {}", e),
             })?;
+            messages::validate(&messages)?;
+            let (system_prompt, prompt) = self.provider_entry(&provider)?.provider.render_messages(&messages);
 
-            tracing::info!(
-                "Resuming Gemini session {}: {}...",
-                sid.chars().take(8).collect::<String>(),
-                prompt.chars().take(50).collect::<String>()
-            );
-            dir
-        } else {
-            // New: create unique temp dir
-            let new_id = uuid::Uuid::new_v4();
-            let dir = std::env::temp_dir().join(format!("praxio-gemini-{}", new_id));
+            let temp_dir = self.create_temp_dir(&format!("praxio-{}-", provider))?;
+            let request = LlmRequest::builder(prompt.clone())
+                .with_optional(system_prompt, LlmRequestBuilder::with_system_prompt)
+                .with_optional(model, LlmRequestBuilder::with_model)
+                .with_temp_dir(temp_dir.path())
+                .with_optional(timeout_seconds, LlmRequestBuilder::with_timeout_seconds)
+                .with_skip_permissions(self.skip_permissions)
+                .with_request_id(request_id.clone())
+                .build();
 
-            tracing::info!(
-                "Creating new Gemini session: {}...",
-                prompt.chars().take(50).collect::<String>()
-            );
-            dir
+            let mut response = self.invoke_provider(&provider, request).await?;
+            if let Some(new_sid) = response.metadata.session_id.clone() {
+                self.insert_session(new_sid.clone(), SessionEntry::new(&provider, temp_dir, None, None))
+                    .await;
+                tracing::Span::current().record("session", new_sid.as_str());
+                self.record_session_call(&new_sid, &prompt, &mut response).await;
+            }
+
+            Ok(self.response_value(&response, verbose)?)
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Abort a running delegation
+    #[tool(description = "Cancel the running delegation with this request_id (from the call's request_id argument or an earlier response's metadata.request_id). Its CLI is killed and the call fails with a cancelled error. Returns cancelled=false if nothing with that ID is running")]
+    async fn cancel(&self, request_id: String) -> McpResult<serde_json::Value> {
+        let cancelled = self.in_flight.cancel(&request_id);
+        Ok(serde_json::json!({
+            "request_id": request_id,
+            "cancelled": cancelled,
+        }))
+    }
+
+    /// Report what each registered provider supports
+    #[tool(description = "Report what each registered provider (or just the named one) supports: sessions, fallback_model, attachments, temperature, thinking_budget, whether it reports actual cost, and its default model's context window")]
+    async fn capabilities(&self, provider: Option<String>) -> McpResult<serde_json::Value> {
+        let mut report = serde_json::Map::new();
+        match provider {
+            Some(name) => {
+                let entry = self.provider_entry(&name)?;
+                report.insert(name, serde_json::to_value(entry.provider.capabilities())?);
+            }
+            None => {
+                for (name, entry) in &self.providers {
+                    report.insert(name.clone(), serde_json::to_value(entry.provider.capabilities())?);
+                }
+            }
+        }
+
+        Ok(serde_json::Value::Object(report))
+    }
+
+    /// Report which registered providers are currently usable
+    #[tool(description = "Check availability and CLI version of every registered provider, and whether its circuit breaker is failing calls fast after repeated failures. A provider that runs but is failing often (circuit not closed, or at least half of its recent calls failed) is reported available with degraded=true and the reason. Results are cached briefly; pass force=true to re-check now")]
+    async fn check_providers(&self, force: Option<bool>) -> McpResult<serde_json::Value> {
+        let force = force.unwrap_or(false);
+
+        let checks = self.providers.iter().map(|(name, entry)| async move {
+            let availability = self.provider_availability(name, force).await;
+            (name.clone(), availability, entry.circuit.status())
+        });
+
+        let mut report = serde_json::Map::new();
+        for (name, availability, circuit) in futures::future::join_all(checks).await {
+            let status = match availability? {
+                ProviderAvailability::Available { version } => serde_json::json!({
+                    "available": true,
+                    "degraded": false,
+                    "reason": null,
+                    "version": version,
+                    "circuit": circuit,
+                }),
+                ProviderAvailability::Degraded { reason } => serde_json::json!({
+                    "available": true,
+                    "degraded": true,
+                    "reason": reason,
+                    "version": self.providers[&name].availability.version().await,
+                    "circuit": circuit,
+                }),
+                ProviderAvailability::Unavailable { reason } => serde_json::json!({
+                    "available": false,
+                    "degraded": false,
+                    "reason": reason,
+                    "version": null,
+                    "circuit": circuit,
+                }),
+            };
+            report.insert(name, status);
+        }
+
+        Ok(serde_json::Value::Object(report))
+    }
+
+    /// Send a trivial prompt through each provider to warm CLIs and credentials
+    #[tool(description = "Warm up providers before real traffic by sending each available one (or just the named ones) a trivial prompt, exercising CLI startup and authentication. Returns per provider { ok, duration_ms, error }. Each call is bounded by timeout_seconds (default 30) and never served from the response cache; unavailable providers are reported without being called. Safe to call repeatedly, though every call costs a few tokens")]
+    async fn warmup(
+        &self,
+        providers: Option<Vec<String>>,
+        timeout_seconds: Option<u64>,
+    ) -> McpResult<serde_json::Value> {
+        let mut names = match providers {
+            Some(names) => names,
+            None => self.providers.keys().cloned().collect(),
         };
+        names.sort();
+        names.dedup();
+        let request_id = new_request_id();
+        let timeout_seconds = timeout_seconds.unwrap_or(DEFAULT_WARMUP_TIMEOUT_SECONDS).max(1);
 
-        let is_new_session = session_id.is_none();
+        let runs = names.iter().map(|name| {
+            let request_id = request_id.clone();
+            async move {
+                let unavailable = match self.provider_availability(name, false).await {
+                    Ok(ProviderAvailability::Available { .. } | ProviderAvailability::Degraded { .. }) => None,
+                    Ok(ProviderAvailability::Unavailable { reason }) => Some(reason),
+                    Err(e) => Some(e.to_string()),
+                };
+                if let Some(reason) = unavailable {
+                    let status = serde_json::json!({
+                        "ok": false,
+                        "duration_ms": null,
+                        "error": reason,
+                    });
+                    return (name.clone(), status);
+                }
 
-        let request = LlmRequest {
-            prompt,
-            system_prompt,
-            model,
-            output_format: OutputFormat::Json,
-            max_tokens: None,
-            session_id,
-            temp_dir: Some(temp_dir.clone()),
-            fallback_model: None, // Not supported by Gemini CLI
-            timeout_seconds,
+                let start = std::time::Instant::now();
+                let result = match self.create_temp_dir(&format!("praxio-warmup-{}-", name)) {
+                    // A working_dir keeps the response cache from answering in the CLI's place
+                    Ok(temp_dir) => {
+                        let request = LlmRequest::builder(WARMUP_PROMPT)
+                            .with_skip_base_system_prompt(true)
+                            .with_working_dir(temp_dir.path())
+                            .with_timeout_seconds(timeout_seconds)
+                            .with_request_id(request_id)
+                            .build();
+                        self.invoke_provider(name, request).await.map(drop)
+                    }
+                    Err(e) => Err(e),
+                };
+                let duration_ms = start.elapsed().as_millis() as u64;
+
+                let status = match result {
+                    Ok(()) => serde_json::json!({
+                        "ok": true,
+                        "duration_ms": duration_ms,
+                        "error": null,
+                    }),
+                    Err(e) => {
+                        tracing::warn!("⚠️  Warmup of {} failed: {}", name, e);
+                        serde_json::json!({
+                            "ok": false,
+                            "duration_ms": duration_ms,
+                            "error": e.to_string(),
+                        })
+                    }
+                };
+                (name.clone(), status)
+            }
+        });
+
+        let report: serde_json::Map<String, serde_json::Value> =
+            futures::future::join_all(runs).await.into_iter().collect();
+        Ok(serde_json::Value::Object(report))
+    }
+
+    /// List a provider's models for model pickers
+    #[tool(description = "List the models a provider offers, e.g. to populate a model picker. Uses the CLI where it can enumerate models (Ollama's installed models, cached for a minute) and otherwise the models in Praxio's alias table; source says which. Also returns the provider's aliases (fast, smart, cheap, ...) and what they resolve to")]
+    async fn list_models(&self, provider: String) -> McpResult<serde_json::Value> {
+        let entry = self.provider_entry(&provider)?;
+        let aliases = models::aliases(&provider);
+
+        let (source, models) = match self.cli_models(entry).await? {
+            Some(models) => ("cli", models),
+            None => {
+                let models: std::collections::BTreeSet<&String> = aliases.values().collect();
+                ("aliases", models.into_iter().cloned().collect())
+            }
         };
 
-        let start = std::time::Instant::now();
-        let response = self.gemini.invoke(request).await?;
-        let elapsed = start.elapsed();
+        Ok(serde_json::json!({
+            "provider": provider,
+            "source": source,
+            "models": models,
+            "aliases": aliases,
+        }))
+    }
 
-        // Store session mapping if this was a new session
-        if is_new_session {
-            if let Some(ref new_sid) = response.metadata.session_id {
-                let mut sessions = self.sessions.write().await;
-                sessions.insert(new_sid.clone(), temp_dir.clone());
-                tracing::info!("Mapped Gemini session {} → {:?}",
-                    new_sid.chars().take(8).collect::<String>(),
-                    temp_dir
-                );
+    /// Cheap liveness/readiness check for orchestrators
+    #[tool(description = "Report server health without running any CLI: status (ok or shutting_down), ready (at least one provider available), uptime_seconds, and providers_available, based on the last availability checks. Cheap enough to poll")]
+    async fn health(&self) -> McpResult<serde_json::Value> {
+        Ok(serde_json::to_value(self.health_report().await)?)
+    }
+
+    /// Report the configuration the server is running with
+    #[tool(description = "Report the server's effective configuration after builder options, environment variables, and command-line flags are applied: registered providers and their default timeouts, max_concurrency, timeouts, session TTL, response cache and dedup settings, circuit breaker, temp root, and similar. Secrets such as API keys are never included; env_allowlist lists variable names only, and the base system prompt is reported by length")]
+    async fn config(&self) -> McpResult<serde_json::Value> {
+        let mut names: Vec<&String> = self.providers.keys().collect();
+        names.sort();
+        let providers: serde_json::Map<String, serde_json::Value> = names
+            .into_iter()
+            .map(|name| {
+                let timeout = self.providers[name].provider.default_timeout_seconds();
+                let max_concurrency = self.provider_concurrency.get(name);
+                let requests_per_minute = self.provider_rate_limits.get(name);
+                (
+                    name.clone(),
+                    serde_json::json!({
+                        "default_timeout_seconds": timeout,
+                        "max_concurrency": max_concurrency,
+                        "requests_per_minute": requests_per_minute,
+                    }),
+                )
+            })
+            .collect();
+        let (failures, cooldown) = self.circuit_breaker;
+        #[cfg(feature = "metrics")]
+        let metrics = self.metrics.is_some();
+        #[cfg(not(feature = "metrics"))]
+        let metrics = false;
+
+        Ok(serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "providers": providers,
+            "default_timeout_seconds": self.default_timeout.map(|t| t.as_secs()),
+            "max_concurrency": self.max_concurrency,
+            "queue_timeout_seconds": self.queue_timeout.as_secs(),
+            "availability_ttl_seconds": self.availability_ttl.as_secs(),
+            "session_ttl_seconds": self.session_ttl.map(|t| t.as_secs()),
+            "skip_permissions": self.skip_permissions,
+            "allow_binary_override": self.allow_binary_override,
+            "max_prompt_chars": self.max_prompt_chars,
+            "max_output_bytes": self.max_output_bytes,
+            "context_margin": self.context_margin,
+            "response_cache": self.response_cache.as_ref().map(|cache| serde_json::json!({
+                "capacity": cache.capacity(),
+                "ttl_seconds": cache.ttl().as_secs(),
+            })),
+            "request_dedup": self.dedup.is_some(),
+            "parse_retry": self.parse_retry,
+            "session_transcripts": self.session_transcripts,
+            "compact_responses": self.compact_responses,
+            "strict_models": self.strict_models,
+            "process_pool": self.process_pool.map(|(size, idle_timeout)| serde_json::json!({
+                "size": size,
+                "idle_timeout_seconds": idle_timeout.as_secs(),
+            })),
+            "circuit_breaker": {
+                "failure_threshold": failures,
+                "cooldown_seconds": cooldown.as_secs(),
+            },
+            "cost_warning_usd": self.cost_warning_usd,
+            "audit_log": self.audit_log.as_ref().map(|log| log.path().display().to_string()),
+            "audit_full_prompts": self.audit_full_prompts,
+            "template_dir": self.template_dir.as_ref().map(|dir| dir.display().to_string()),
+            "temp_root": self.temp_root.display().to_string(),
+            "env_allowlist": self.env_allowlist,
+            "base_system_prompt_chars": self.base_system_prompt.as_ref().map(|preamble| preamble.chars().count()),
+            "metrics": metrics,
+        }))
+    }
+
+    /// Report cumulative token usage and cost since the server started
+    #[tool(description = "Report total requests, tokens, and cost since the server started, overall and per provider. Reported (Claude) and estimated (other providers) costs are tracked separately. cost_last_60s and tokens_last_60s cover only the last minute, for pacing before provider rate limits. Providers with a requests-per-minute limit also report rate_limit.available, the requests that could start right now")]
+    async fn usage_stats(&self) -> McpResult<serde_json::Value> {
+        let mut total = UsageSnapshot::default();
+        let mut providers = serde_json::Map::new();
+
+        for (name, entry) in &self.providers {
+            let snapshot = entry.usage.snapshot();
+            total.add(&snapshot);
+            let mut report = serde_json::to_value(&snapshot)?;
+            if let Some(ref rate_limit) = entry.rate_limit {
+                report["rate_limit"] = serde_json::json!({
+                    "requests_per_minute": rate_limit.per_minute(),
+                    "available": rate_limit.available(),
+                });
             }
+            providers.insert(name.clone(), report);
         }
 
-        tracing::info!(
-            "Gemini response received in {}ms (API: {}ms)",
-            elapsed.as_millis(),
-            response.duration_ms
-        );
+        Ok(serde_json::json!({
+            "total": total,
+            "total_cost_usd": total.total_cost_usd(),
+            "providers": providers,
+        }))
+    }
+
+    /// Report a session's history without resuming it
+    #[tool(description = "Report a session's provider, creation and last-use times, turn count (history_turns counts only the turns the CLI still carries, for max_history_turns), and cumulative tokens and cost. Does not resume the session or extend its expiry")]
+    async fn session_info(&self, session_id: String) -> McpResult<serde_json::Value> {
+        let sessions = self.sessions.read().await;
+        let entry = sessions
+            .get(&session_id)
+            .filter(|entry| self.session_ttl.is_none_or(|ttl| entry.last_used.elapsed() <= ttl))
+            .ok_or_else(|| LlmError::SessionNotFound {
+                session_id: session_id.clone(),
+            })?;
+
+        Ok(entry.report(&session_id))
+    }
+
+    /// Return a session's recorded prompts and answers
+    #[tool(description = "Export a session's transcript: every prompt and answer so far, each with its time, provider, model, tokens, and cost_usd, for archiving, audit, or handing the conversation to another tool. Only available when the server records transcripts (PRAXIO_SESSION_TRANSCRIPTS); does not resume the session or extend its expiry")]
+    async fn export_session(&self, session_id: String) -> McpResult<serde_json::Value> {
+        let sessions = self.sessions.read().await;
+        let entry = sessions
+            .get(&session_id)
+            .filter(|entry| self.session_ttl.is_none_or(|ttl| entry.last_used.elapsed() <= ttl))
+            .ok_or_else(|| LlmError::SessionNotFound {
+                session_id: session_id.clone(),
+            })?;
+
+        let Some(ref transcript) = entry.transcript else {
+            return Err(LlmError::InvalidRequest {
+                message: format!(
+                    "session {} has no transcript; set PRAXIO_SESSION_TRANSCRIPTS=1 to record new sessions",
+                    session_id
+                ),
+            }
+            .into());
+        };
+        let turns = transcript.lock().unwrap_or_else(|e| e.into_inner()).clone();
+
+        let mut export = entry.report(&session_id);
+        export["turns"] = serde_json::to_value(turns)?;
+        Ok(export)
+    }
+
+    /// Carry a recorded conversation over to a new session on another provider
+    #[tool(description = "Continue a conversation on a different provider: replay from_session's recorded transcript as context for a new session on to_provider, ask prompt there, and return the response with the new session in metadata.session_id (and from_session in metadata.previous_session_id). Works across providers whose CLIs can't resume each other's sessions. Needs the source session's transcript, which the server records only when PRAXIO_SESSION_TRANSCRIPTS is set, and a target provider that supports sessions. The earlier turns are resent as one prompt, so a long transcript costs accordingly")]
+    async fn handoff(
+        &self,
+        from_session: String,
+        to_provider: String,
+        prompt: String,
+        model: Option<String>,
+        timeout_seconds: Option<u64>,
+        verbose: Option<bool>,
+        request_id: Option<String>,
+    ) -> McpResult<serde_json::Value> {
+        let request_id = request_id.unwrap_or_else(new_request_id);
+        let span = tracing::info_span!("delegation", request_id = %request_id, provider = %to_provider, session = tracing::field::Empty);
+
+        async move {
+            let target = self.provider_entry(&to_provider)?;
+            if !target.provider.capabilities().supports_sessions {
+                return Err(LlmError::InvalidRequest {
+                    message: format!("{} can't resume sessions, so it can't take over a conversation", to_provider),
+                }
+                .into());
+            }
+
+            let source = self.session(&from_session).await?;
+            let Some(ref transcript) = source.transcript else {
+                return Err(LlmError::InvalidRequest {
+                    message: format!(
+                        "session {} has no recorded transcript to hand off; set PRAXIO_SESSION_TRANSCRIPTS=1 before starting the session",
+                        from_session
+                    ),
+                }
+                .into());
+            };
+            let turns = transcript.lock().unwrap_or_else(|e| e.into_inner()).clone();
+
+            // Earlier turns become the conversation the new provider continues
+            let mut conversation = Vec::with_capacity(turns.len() * 2 + 1);
+            for turn in &turns {
+                let reply = if turn.response.trim().is_empty() { "(no reply)" } else { turn.response.as_str() };
+                conversation.push(Message { role: messages::Role::User, content: turn.prompt.clone() });
+                conversation.push(Message { role: messages::Role::Assistant, content: reply.to_string() });
+            }
+            conversation.push(Message { role: messages::Role::User, content: prompt.clone() });
+            messages::validate(&conversation)?;
+            let (system_prompt, rendered) = target.provider.render_messages(&conversation);
+
+            let temp_dir = self.create_temp_dir(&format!("praxio-{}-", to_provider))?;
+            let request = LlmRequest::builder(rendered)
+                .with_optional(system_prompt, LlmRequestBuilder::with_system_prompt)
+                .with_optional(model, LlmRequestBuilder::with_model)
+                .with_temp_dir(temp_dir.path())
+                .with_optional(source.working_dir.clone(), LlmRequestBuilder::with_working_dir)
+                .with_optional(timeout_seconds, LlmRequestBuilder::with_timeout_seconds)
+                .with_skip_permissions(self.skip_permissions)
+                .with_request_id(request_id.clone())
+                .build();
+
+            let mut response = self.invoke_provider(&to_provider, request).await?;
+            let Some(new_sid) = response.metadata.session_id.clone() else {
+                return Err(LlmError::InvalidRequest {
+                    message: format!("{} returned no session to continue", to_provider),
+                }
+                .into());
+            };
+
+            // The new session's transcript carries on from the old one
+            let mut entry = SessionEntry::new(&to_provider, temp_dir, source.working_dir.clone(), None);
+            entry.transcript = Some(Arc::new(std::sync::Mutex::new(turns.clone())));
+            self.insert_session(new_sid.clone(), entry).await;
+            tracing::Span::current().record("session", new_sid.as_str());
+            self.record_session_call(&new_sid, &prompt, &mut response).await;
+            response.metadata.previous_session_id = Some(from_session.clone());
 
-        if let Some(ref tokens) = response.tokens {
             tracing::info!(
-                "Tokens: {} input, {} output, {} total ({} thoughts)",
-                tokens.input,
-                tokens.output,
-                tokens.total,
-                tokens.extended_thinking.unwrap_or(0)
+                "Handed {} turn(s) of {} session {} to {} session {}",
+                turns.len(),
+                source.provider,
+                from_session.chars().take(8).collect::<String>(),
+                to_provider,
+                new_sid.chars().take(8).collect::<String>()
             );
+
+            Ok(self.response_value(&response, verbose)?)
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Index of every live session as an MCP resource
+    #[resource(
+        uri = "praxio://sessions",
+        name = "sessions",
+        title = "Praxio sessions",
+        description = "Every live delegation session with its provider, timestamps, turn counts, and cumulative tokens and cost, newest first. Reading it never runs a CLI or extends a session's expiry",
+        mime_type = "application/json"
+    )]
+    async fn sessions_resource(&self) -> McpResult<String> {
+        let sessions = self.sessions.read().await;
+        let mut live: Vec<_> = sessions
+            .iter()
+            .filter(|(_, entry)| self.session_ttl.is_none_or(|ttl| entry.last_used.elapsed() <= ttl))
+            .collect();
+        live.sort_by_key(|(_, entry)| std::cmp::Reverse(entry.created_at));
+
+        let reports: Vec<_> = live.into_iter().map(|(id, entry)| entry.report(id)).collect();
+        Ok(serde_json::to_string_pretty(&serde_json::json!({ "sessions": reports }))?)
+    }
+
+    /// One live session as an MCP resource, with its transcript when recorded
+    #[resource(
+        uri = "praxio://session/{id}",
+        name = "session",
+        title = "Praxio session",
+        description = "One delegation session: the details session_info reports plus, when the server records transcripts (PRAXIO_SESSION_TRANSCRIPTS), every prompt and answer in turns. Reading it never runs a CLI or extends the session's expiry",
+        mime_type = "application/json"
+    )]
+    async fn session_resource(&self, id: String) -> McpResult<String> {
+        let sessions = self.sessions.read().await;
+        let entry = sessions
+            .get(&id)
+            .filter(|entry| self.session_ttl.is_none_or(|ttl| entry.last_used.elapsed() <= ttl))
+            .ok_or_else(|| LlmError::SessionNotFound { session_id: id.clone() })?;
+
+        let mut report = entry.report(&id);
+        if let Some(ref transcript) = entry.transcript {
+            let turns = transcript.lock().unwrap_or_else(|e| e.into_inner()).clone();
+            report["turns"] = serde_json::to_value(turns)?;
+        }
+        Ok(serde_json::to_string_pretty(&report)?)
+    }
+
+    /// Delegation prompt asking for a code review
+    #[prompt(
+        name = "code_review",
+        desc = "Prompt asking a delegate to review code and list concrete problems with fixes. focus defaults to correctness, security, and maintainability. Send the result as the prompt of invoke_claude or invoke"
+    )]
+    async fn code_review_prompt(&self, code: String, focus: Option<String>) -> McpResult<String> {
+        let focus = focus.unwrap_or_else(|| "correctness, security, and maintainability".to_string());
+        Ok(self.render_builtin_prompt("code_review", [("code", code), ("focus", focus)])?)
+    }
+
+    /// Delegation prompt asking for a summary
+    #[prompt(
+        name = "summarize",
+        desc = "Prompt asking a delegate to summarize text, keeping names, numbers, and decisions. length defaults to a short paragraph. Send the result as the prompt of invoke_claude or invoke"
+    )]
+    async fn summarize_prompt(&self, text: String, length: Option<String>) -> McpResult<String> {
+        let length = length.unwrap_or_else(|| "a short paragraph".to_string());
+        Ok(self.render_builtin_prompt("summarize", [("text", text), ("length", length)])?)
+    }
+
+    /// Delegation prompt asking for a diff to be explained
+    #[prompt(
+        name = "explain_diff",
+        desc = "Prompt asking a delegate to explain what a diff changes and flag risky or unintended edits. audience defaults to a reviewer new to the code. Send the result as the prompt of invoke_claude or invoke"
+    )]
+    async fn explain_diff_prompt(&self, diff: String, audience: Option<String>) -> McpResult<String> {
+        let audience = audience.unwrap_or_else(|| "a reviewer new to the code".to_string());
+        Ok(self.render_builtin_prompt("explain_diff", [("diff", diff), ("audience", audience)])?)
+    }
+
+    /// Any template from the template directory, as an MCP prompt
+    #[prompt(
+        name = "template",
+        desc = "Fill a named prompt template from the server's template directory (PRAXIO_TEMPLATE_DIR). vars is a JSON object of {{name}} placeholder values, e.g. {\"file\": \"src/lib.rs\"}. Send the result as the prompt of invoke_claude or invoke"
+    )]
+    async fn template_prompt(&self, template_name: String, vars: Option<String>) -> McpResult<String> {
+        let dir = self.template_dir.as_ref().ok_or_else(|| LlmError::InvalidRequest {
+            message: "no template directory configured (set PRAXIO_TEMPLATE_DIR)".to_string(),
+        })?;
+        let vars: HashMap<String, String> = match vars {
+            Some(vars) => serde_json::from_str(&vars).map_err(|e| LlmError::InvalidRequest {
+                message: format!("vars must be a JSON object of strings: {}", e),
+            })?,
+            None => HashMap::new(),
+        };
+
+        Ok(template::render(&template::load(dir, &template_name)?, &vars)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::MockProvider;
+
+    async fn server_with(mock: Arc<MockProvider>) -> PraxioServer {
+        PraxioServerBuilder::new()
+            .with_default_providers(false)
+            .with_provider("claude", mock)
+            .build()
+            .await
+    }
+
+    async fn invoke_claude(server: &PraxioServer, session_id: Option<String>) -> McpResult<serde_json::Value> {
+        server
+            .invoke_claude(
+                "hello".to_string(),
+                None,
+                None,
+                None,
+                None,
+                None,
+                session_id,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+    }
+
+    fn session_id_of(response: &serde_json::Value) -> String {
+        response["metadata"]["session_id"]
+            .as_str()
+            .expect("response carries a session_id")
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn new_session_is_mapped_to_its_temp_dir() {
+        let mock = Arc::new(MockProvider::new("claude"));
+        let server = server_with(mock.clone()).await;
+
+        let response = invoke_claude(&server, None).await.unwrap();
+        let session_id = session_id_of(&response);
+
+        let entry = server.session(&session_id).await.unwrap();
+        assert_eq!(entry.provider, "claude");
+        assert!(entry.temp_dir.path().is_dir());
+        assert_eq!(
+            mock.last_request().unwrap().temp_dir.as_deref(),
+            Some(entry.temp_dir.path())
+        );
+    }
+
+    #[tokio::test]
+    async fn resumed_session_reuses_its_temp_dir() {
+        let mock = Arc::new(MockProvider::new("claude"));
+        let server = server_with(mock.clone()).await;
+
+        let first = invoke_claude(&server, None).await.unwrap();
+        let session_id = session_id_of(&first);
+        let temp_dir = server.session(&session_id).await.unwrap().temp_dir.path().to_path_buf();
+
+        let second = invoke_claude(&server, Some(session_id.clone())).await.unwrap();
+        assert_eq!(session_id_of(&second), session_id);
+
+        let request = mock.last_request().unwrap();
+        assert_eq!(request.session_id.as_deref(), Some(session_id.as_str()));
+        assert_eq!(request.temp_dir.as_deref(), Some(temp_dir.as_path()));
+
+        let entry = server.session(&session_id).await.unwrap();
+        assert_eq!(entry.temp_dir.path(), temp_dir);
+        assert_eq!(entry.calls, 2);
+        assert_eq!(server.sessions.read().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn unknown_session_is_not_found() {
+        let mock = Arc::new(MockProvider::new("claude"));
+        let server = server_with(mock.clone()).await;
+
+        assert!(matches!(
+            server.session("no-such-session").await,
+            Err(LlmError::SessionNotFound { ref session_id }) if session_id == "no-such-session"
+        ));
+
+        let err = invoke_claude(&server, Some("no-such-session".to_string()))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Session not found: no-such-session"), "{}", err);
+        assert_eq!(mock.calls(), 0);
+    }
+
+    #[tokio::test]
+    async fn session_resource_reports_the_session_and_its_turns() {
+        let mock = Arc::new(MockProvider::new("claude"));
+        let server = PraxioServerBuilder::new()
+            .with_default_providers(false)
+            .with_provider("claude", mock)
+            .build()
+            .await
+            .with_session_transcripts(true);
+
+        let session_id = session_id_of(&invoke_claude(&server, None).await.unwrap());
+        let report: serde_json::Value =
+            serde_json::from_str(&server.session_resource(session_id.clone()).await.unwrap()).unwrap();
+        assert_eq!(report["session_id"], session_id.as_str());
+        assert_eq!(report["calls"], 1);
+        assert_eq!(report["turns"].as_array().unwrap().len(), 1);
+        assert_eq!(report["turns"][0]["prompt"], "hello");
+
+        assert!(server.session_resource("no-such-session".to_string()).await.is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn identical_concurrent_requests_share_one_run() {
+        let mock = Arc::new(MockProvider::new("claude").with_latency(Duration::from_secs(1)));
+        let server = server_with(mock.clone()).await;
+
+        let (first, second) = tokio::join!(invoke_claude(&server, None), invoke_claude(&server, None));
+        let (first, second) = (first.unwrap(), second.unwrap());
+
+        assert_eq!(mock.calls(), 1);
+        assert_eq!(first["content"], second["content"]);
+        let deduplicated = [&first, &second]
+            .iter()
+            .filter(|response| response["metadata"]["deduplicated"] == true)
+            .count();
+        assert_eq!(deduplicated, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limit_paces_a_burst() {
+        let mock = Arc::new(MockProvider::new("claude"));
+        let server = PraxioServerBuilder::new()
+            .with_default_providers(false)
+            .with_provider("claude", mock.clone())
+            .with_provider_rate_limit("claude", 120)
+            .build()
+            .await;
+
+        // A minute's worth goes through at once...
+        let started = tokio::time::Instant::now();
+        for i in 0..120 {
+            let request = LlmRequest::builder(format!("burst {}", i)).build();
+            server.invoke_provider("claude", request).await.unwrap();
+        }
+        assert_eq!(started.elapsed(), Duration::ZERO);
+
+        // ...then requests are spaced at the refill rate, half a second apart
+        let started = tokio::time::Instant::now();
+        for i in 0..2 {
+            let request = LlmRequest::builder(format!("paced {}", i)).build();
+            server.invoke_provider("claude", request).await.unwrap();
         }
+        assert_eq!(started.elapsed(), Duration::from_secs(1));
+        assert_eq!(mock.calls(), 122);
+    }
 
-        Ok(serde_json::to_value(&response)?)
+    #[tokio::test(start_paused = true)]
+    async fn saturated_provider_does_not_block_others() {
+        let slow = Arc::new(MockProvider::new("claude").with_latency(Duration::from_millis(800)));
+        let fast = Arc::new(MockProvider::new("gemini"));
+        let server = PraxioServerBuilder::new()
+            .with_default_providers(false)
+            .with_provider("claude", slow.clone())
+            .with_provider("gemini", fast.clone())
+            .with_max_concurrency(2)
+            .with_provider_concurrency("claude", 1)
+            .build()
+            .await;
+
+        // One running and two queued: without a per-provider limit they'd hold both server permits
+        let burst: Vec<_> = (0..3)
+            .map(|i| {
+                let server = server.clone();
+                tokio::spawn(async move {
+                    let request = LlmRequest::builder(format!("slow {}", i)).build();
+                    server.invoke_provider("claude", request).await
+                })
+            })
+            .collect();
+        // The paused clock only moves once every task is blocked, so by now one
+        // call is running and the other two are queued
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let started = tokio::time::Instant::now();
+        let request = LlmRequest::builder("fast").build();
+        server.invoke_provider("gemini", request).await.unwrap();
+        assert_eq!(started.elapsed(), Duration::ZERO, "gemini waited for a permit");
+        assert_eq!(fast.calls(), 1);
+        assert_eq!(slow.calls(), 1);
+
+        for run in burst {
+            run.await.unwrap().unwrap();
+        }
+        assert_eq!(slow.calls(), 3);
     }
 }
@@ -0,0 +1,124 @@
+//! Prompt templates with `{{name}}` placeholders
+//!
+//! Templates live as plain files in a directory configured at startup, so
+//! prompt skeletons can be versioned alongside the code that uses them.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path};
+
+use crate::error::LlmError;
+
+/// Fill every `{{name}}` placeholder in `template` from `vars`
+///
+/// Whitespace inside the braces is ignored. A placeholder without a value, a
+/// variable that no placeholder uses, or an unclosed `{{` is an `InvalidRequest`,
+/// so typos surface instead of producing a half-filled prompt.
+pub fn render(template: &str, vars: &HashMap<String, String>) -> Result<String, LlmError> {
+    let mut out = String::with_capacity(template.len());
+    let mut used = HashSet::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after.find("}}").ok_or_else(|| LlmError::InvalidRequest {
+            message: "template has an unclosed '{{'".to_string(),
+        })?;
+
+        let name = after[..end].trim();
+        if name.is_empty() {
+            return Err(LlmError::InvalidRequest {
+                message: "template has an empty placeholder".to_string(),
+            });
+        }
+
+        let value = vars.get(name).ok_or_else(|| LlmError::InvalidRequest {
+            message: format!("missing value for template variable '{}'", name),
+        })?;
+        out.push_str(value);
+        used.insert(name);
+
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+
+    let mut unknown: Vec<&str> = vars
+        .keys()
+        .map(String::as_str)
+        .filter(|name| !used.contains(name))
+        .collect();
+    if !unknown.is_empty() {
+        unknown.sort_unstable();
+        return Err(LlmError::InvalidRequest {
+            message: format!("template has no placeholder for: {}", unknown.join(", ")),
+        });
+    }
+
+    Ok(out)
+}
+
+/// Read the template named `name` from `dir`
+///
+/// The name must be a plain file name; paths that would leave `dir` are rejected.
+pub fn load(dir: &Path, name: &str) -> Result<String, LlmError> {
+    let mut components = Path::new(name).components();
+    let is_plain_name = matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    );
+    if !is_plain_name {
+        return Err(LlmError::InvalidRequest {
+            message: format!("invalid template name '{}'", name),
+        });
+    }
+
+    let path = dir.join(name);
+    std::fs::read_to_string(&path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => LlmError::InvalidRequest {
+            message: format!("no template named '{}' in {}", name, dir.display()),
+        },
+        _ => LlmError::Io(e),
+    })
+}
+
+/// Templates behind the built-in MCP prompts, by prompt name
+const BUILTIN: &[(&str, &str)] = &[
+    (
+        "code_review",
+        "Review the following code, focusing on {{focus}}. List concrete problems \
+         with their location and a suggested fix, most severe first. Say so if you \
+         find nothing worth changing.\n\n{{code}}",
+    ),
+    (
+        "summarize",
+        "Summarize the following in {{length}}. Keep names, numbers, and decisions; \
+         drop repetition and filler.\n\n{{text}}",
+    ),
+    (
+        "explain_diff",
+        "Explain what this diff changes and why it might have been made, for \
+         {{audience}}. Call out behavior changes, risky edits, and anything that \
+         looks unintended.\n\n{{diff}}",
+    ),
+];
+
+/// Template for the built-in prompt `name`
+///
+/// A file named `<name>.md` in `dir` replaces the bundled text; it is filled
+/// with the same variables, so it must use all of them.
+pub fn load_builtin(dir: Option<&Path>, name: &str) -> Result<String, LlmError> {
+    if let Some(dir) = dir {
+        match load(dir, &format!("{}.md", name)) {
+            Err(LlmError::InvalidRequest { .. }) => {}
+            result => return result,
+        }
+    }
+
+    BUILTIN
+        .iter()
+        .find(|(builtin, _)| *builtin == name)
+        .map(|(_, template)| template.to_string())
+        .ok_or_else(|| LlmError::InvalidRequest {
+            message: format!("no built-in prompt named '{}'", name),
+        })
+}
@@ -0,0 +1,168 @@
+//! Running token and cost totals across a server's lifetime
+//!
+//! Alongside the lifetime totals, a ring of one-second buckets tracks the
+//! last minute so callers can pace themselves before hitting rate limits.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::llm::{CostSource, LlmResponse};
+
+/// Costs are accumulated in micro-dollars so they fit in an atomic integer
+const MICROS_PER_USD: f64 = 1_000_000.0;
+
+/// Length of the recent-usage window, in seconds (one bucket per second)
+const RECENT_WINDOW_SECS: u64 = 60;
+
+/// Usage recorded during one second
+#[derive(Debug, Default)]
+struct Bucket {
+    /// Unix second the counts belong to; older counts are stale
+    second: AtomicU64,
+    tokens: AtomicU64,
+    cost_micros: AtomicU64,
+}
+
+/// Lock-free usage counters for one provider
+#[derive(Debug)]
+pub struct UsageCounters {
+    requests: AtomicU64,
+    input_tokens: AtomicU64,
+    output_tokens: AtomicU64,
+    reported_cost_micros: AtomicU64,
+    estimated_cost_micros: AtomicU64,
+    recent: [Bucket; RECENT_WINDOW_SECS as usize],
+}
+
+/// Point-in-time copy of usage counters
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UsageSnapshot {
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// Cost reported by the provider CLI (Claude)
+    pub reported_cost_usd: f64,
+    /// Cost estimated from token counts and the pricing table
+    pub estimated_cost_usd: f64,
+    /// Reported plus estimated cost of responses in the last 60 seconds
+    pub cost_last_60s: f64,
+    /// Input plus output tokens of responses in the last 60 seconds
+    pub tokens_last_60s: u64,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl Default for UsageCounters {
+    fn default() -> Self {
+        Self {
+            requests: AtomicU64::new(0),
+            input_tokens: AtomicU64::new(0),
+            output_tokens: AtomicU64::new(0),
+            reported_cost_micros: AtomicU64::new(0),
+            estimated_cost_micros: AtomicU64::new(0),
+            recent: std::array::from_fn(|_| Bucket::default()),
+        }
+    }
+}
+
+impl UsageCounters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a completed response to the totals
+    pub fn record(&self, response: &LlmResponse) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        let bucket = self.current_bucket();
+
+        if let Some(ref tokens) = response.tokens {
+            self.input_tokens.fetch_add(tokens.input, Ordering::Relaxed);
+            self.output_tokens.fetch_add(tokens.output, Ordering::Relaxed);
+            bucket
+                .tokens
+                .fetch_add(tokens.input.saturating_add(tokens.output), Ordering::Relaxed);
+        }
+
+        if let Some(cost) = response.cost_usd {
+            let micros = (cost * MICROS_PER_USD).round() as u64;
+            let counter = match response.metadata.cost_source {
+                Some(CostSource::Estimated) => &self.estimated_cost_micros,
+                _ => &self.reported_cost_micros,
+            };
+            counter.fetch_add(micros, Ordering::Relaxed);
+            bucket.cost_micros.fetch_add(micros, Ordering::Relaxed);
+        }
+    }
+
+    /// The bucket for the current second, cleared first if it still holds an older second
+    ///
+    /// Only the caller that wins the swap clears it, so there is no lock; a
+    /// response recorded by another caller in that same instant may be lost
+    /// from the window (never from the lifetime totals), which is fine for pacing.
+    fn current_bucket(&self) -> &Bucket {
+        let now = now_secs();
+        let bucket = &self.recent[(now % RECENT_WINDOW_SECS) as usize];
+        let seen = bucket.second.load(Ordering::Acquire);
+        if seen != now
+            && bucket
+                .second
+                .compare_exchange(seen, now, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+        {
+            bucket.tokens.store(0, Ordering::Relaxed);
+            bucket.cost_micros.store(0, Ordering::Relaxed);
+        }
+        bucket
+    }
+
+    /// Tokens and cost (in micro-dollars) recorded in the last `RECENT_WINDOW_SECS` seconds
+    fn recent(&self) -> (u64, u64) {
+        let now = now_secs();
+        self.recent
+            .iter()
+            .filter(|bucket| now.saturating_sub(bucket.second.load(Ordering::Acquire)) < RECENT_WINDOW_SECS)
+            .fold((0, 0), |(tokens, cost), bucket| {
+                (
+                    tokens + bucket.tokens.load(Ordering::Relaxed),
+                    cost + bucket.cost_micros.load(Ordering::Relaxed),
+                )
+            })
+    }
+
+    pub fn snapshot(&self) -> UsageSnapshot {
+        let (tokens_last_60s, cost_last_60s_micros) = self.recent();
+        UsageSnapshot {
+            requests: self.requests.load(Ordering::Relaxed),
+            input_tokens: self.input_tokens.load(Ordering::Relaxed),
+            output_tokens: self.output_tokens.load(Ordering::Relaxed),
+            reported_cost_usd: self.reported_cost_micros.load(Ordering::Relaxed) as f64 / MICROS_PER_USD,
+            estimated_cost_usd: self.estimated_cost_micros.load(Ordering::Relaxed) as f64 / MICROS_PER_USD,
+            cost_last_60s: cost_last_60s_micros as f64 / MICROS_PER_USD,
+            tokens_last_60s,
+        }
+    }
+}
+
+impl UsageSnapshot {
+    /// Sum of reported and estimated cost
+    pub fn total_cost_usd(&self) -> f64 {
+        self.reported_cost_usd + self.estimated_cost_usd
+    }
+
+    /// Add another snapshot's counts to this one
+    pub fn add(&mut self, other: &UsageSnapshot) {
+        self.requests = self.requests.saturating_add(other.requests);
+        self.input_tokens = self.input_tokens.saturating_add(other.input_tokens);
+        self.output_tokens = self.output_tokens.saturating_add(other.output_tokens);
+        self.reported_cost_usd += other.reported_cost_usd;
+        self.estimated_cost_usd += other.estimated_cost_usd;
+        self.cost_last_60s += other.cost_last_60s;
+        self.tokens_last_60s = self.tokens_last_60s.saturating_add(other.tokens_last_60s);
+    }
+}